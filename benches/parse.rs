@@ -0,0 +1,60 @@
+//! Time and allocations of parsing the GMime test gir.
+//! Run with `cargo bench --bench parse`.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use gir_to_stub::parse::parse_gir;
+
+/// Counts the bytes allocated in total and at most at once
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        TOTAL.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let gir = std::fs::read("testdata/girs/GMime-3.0.gir").expect("the GMime test gir");
+
+    // once to warm up and to count the allocations of one parse
+    let base = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    TOTAL.store(0, Ordering::Relaxed);
+    let repo = parse_gir(gir.as_slice()).unwrap();
+    let peak = PEAK.load(Ordering::Relaxed) - base;
+    let total = TOTAL.load(Ordering::Relaxed);
+    drop(repo);
+
+    let mut times = vec![];
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let repo = parse_gir(gir.as_slice()).unwrap();
+        times.push(start.elapsed());
+        drop(repo);
+    }
+    times.sort();
+    let mean = times.iter().sum::<Duration>() / ITERATIONS;
+
+    println!("parse_gir GMime-3.0.gir ({} KiB)", gir.len() / 1024);
+    println!("  time:  median {:?}, mean {:?}, min {:?}", times[times.len() / 2], mean, times[0]);
+    println!("  memory: peak {} KiB, allocated {} KiB", peak / 1024, total / 1024);
+}
@@ -0,0 +1,116 @@
+//! Comparing generated files with the ones in an output directory, to
+//! find stubs that are out of date.
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use similar::TextDiff;
+
+use crate::sink::MemorySink;
+
+/// A file in the output directory that isn't what would be generated
+#[derive(Debug, PartialEq, Eq)]
+pub enum Stale {
+    /// The file would be generated but isn't in the directory
+    Missing(PathBuf),
+    /// The file isn't generated anymore
+    Extra(PathBuf),
+    /// The file has other contents, with a unified diff from the
+    /// directory to the generated file, None if either isn't text
+    Changed(PathBuf, Option<String>),
+}
+
+impl Stale {
+    pub fn path(&self) -> &Path {
+        match self {
+            Stale::Missing(path) | Stale::Extra(path) | Stale::Changed(path, _) => path,
+        }
+    }
+}
+
+impl fmt::Display for Stale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stale::Missing(path) => writeln!(f, "Only generated: {}", path.display()),
+            Stale::Extra(path) => writeln!(f, "No longer generated: {}", path.display()),
+            Stale::Changed(_, Some(diff)) => f.write_str(diff),
+            Stale::Changed(path, None) => writeln!(f, "Binary files differ: {}", path.display()),
+        }
+    }
+}
+
+/// Compare the files in `generated` with the ones below `dir`. `outputs`
+/// are the files earlier runs generated, the ones that aren't generated
+/// anymore but are still in the directory are `Extra`.
+pub fn compare(generated: &MemorySink, dir: &Path, outputs: &BTreeSet<PathBuf>) -> Result<Vec<Stale>> {
+    let mut stale = vec![];
+    let paths = generated.paths();
+    for path in paths.iter() {
+        let new = generated.get(path).unwrap_or_default();
+        let old = match fs::read(dir.join(path)) {
+            Ok(old) => old,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                stale.push(Stale::Missing(path.clone()));
+                continue
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if old != new {
+            stale.push(Stale::Changed(path.clone(), unified_diff(path, &old, &new)));
+        }
+    }
+    for path in outputs.iter().filter(|p| !paths.contains(p)) {
+        if dir.join(path).is_file() {
+            stale.push(Stale::Extra(path.clone()));
+        }
+    }
+    stale.sort_by(|a, b| a.path().cmp(b.path()));
+    Ok(stale)
+}
+
+fn unified_diff(path: &Path, old: &[u8], new: &[u8]) -> Option<String> {
+    let old = std::str::from_utf8(old).ok()?;
+    let new = std::str::from_utf8(new).ok()?;
+    let diff = TextDiff::from_lines(old, new);
+    let name = path.display().to_string();
+    Some(diff.unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", name), &format!("b/{}", name))
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use super::{compare, Stale};
+    use crate::sink::{MemorySink, Sink};
+
+    #[test]
+    fn compare_works() {
+        let dir = std::env::temp_dir().join(format!("gir-to-stub-check-{}", std::process::id()));
+        fs::create_dir_all(dir.join("GMime")).unwrap();
+        fs::write(dir.join("GMime/init.lua"), "local a = 1\nlocal b = 2\n").unwrap();
+        fs::write(dir.join("GMime/Same.lua"), "return {}\n").unwrap();
+        fs::write(dir.join("GMime/Gone.lua"), "").unwrap();
+
+        let sink = MemorySink::new();
+        sink.write(Path::new("GMime/init.lua"), b"local a = 1\nlocal b = 3\n").unwrap();
+        sink.write(Path::new("GMime/Same.lua"), b"return {}\n").unwrap();
+        sink.write(Path::new("GMime/New.lua"), b"").unwrap();
+        let outputs: BTreeSet<PathBuf> = ["GMime/init.lua", "GMime/Same.lua", "GMime/Gone.lua"]
+            .iter().map(PathBuf::from).collect();
+
+        let stale = compare(&sink, &dir, &outputs).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let diff = "--- a/GMime/init.lua\n+++ b/GMime/init.lua\n@@ -1,2 +1,2 @@\n local a = 1\n-local b = 2\n+local b = 3\n";
+        assert_eq!(stale, vec![
+            Stale::Extra(PathBuf::from("GMime/Gone.lua")),
+            Stale::Missing(PathBuf::from("GMime/New.lua")),
+            Stale::Changed(PathBuf::from("GMime/init.lua"), Some(diff.to_string())),
+        ]);
+    }
+}
@@ -0,0 +1,321 @@
+//! What the lua stubs leave out and why. Symbols the gir marks as not
+//! introspectable are missing because of upstream, parse failures and
+//! unsupported kinds are missing because of the generator.
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::library::*;
+use crate::lang::lua::{unsupported, Element};
+use crate::parse::{parse_failures, ParseFailure};
+use crate::resolve::read_repository;
+use crate::visit::{self, Context, Visitor};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reason {
+    /// Marked `introspectable="0"` in the gir
+    NotIntrospectable,
+    /// Not introspectable because it takes varargs
+    VarArgs,
+    /// Not introspectable because another function replaces it
+    Shadowed,
+    /// The element couldn't be read from the gir
+    ParseFailure,
+    /// A kind or place the stubs don't have, like macros, boxed types and
+    /// interfaces, see [`unsupported`]
+    Unsupported,
+}
+
+const REASONS: &[Reason] = &[
+    Reason::NotIntrospectable,
+    Reason::VarArgs,
+    Reason::Shadowed,
+    Reason::ParseFailure,
+    Reason::Unsupported,
+];
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::NotIntrospectable => f.write_str("non-introspectable"),
+            Reason::VarArgs => f.write_str("varargs"),
+            Reason::Shadowed => f.write_str("shadowed"),
+            Reason::ParseFailure => f.write_str("parse failure"),
+            Reason::Unsupported => f.write_str("unsupported kind"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Skipped {
+    /// Like `Gtk.Widget.show`
+    pub path: String,
+    /// Like `method` or `interface`
+    pub kind: String,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Coverage {
+    pub namespace: String,
+    /// Symbols that are in the stubs
+    pub generated: usize,
+    pub skipped: Vec<Skipped>,
+}
+
+impl Coverage {
+    /// The number of symbols skipped for `reason`
+    pub fn count(&self, reason: Reason) -> usize {
+        self.skipped.iter().filter(|s| s.reason == reason).count()
+    }
+
+    fn skip(&mut self, path: String, kind: &str, reason: Reason) {
+        self.skipped.push(Skipped { path, kind: kind.to_string(), reason });
+    }
+}
+
+/// The coverage of every namespace in `repo`, `failures` are the elements
+/// the parser skipped
+pub fn coverage(repo: &Repository, failures: &[ParseFailure]) -> Result<Vec<Coverage>> {
+    let mut ret = vec![];
+    for ns in repo.namespace.iter() {
+        let name = ns.name.as_deref().unwrap_or("");
+        let mut cov = Coverage {
+            namespace: name.to_string(),
+            ..Coverage::default()
+        };
+        cov.visit_namespace(ns, Context::new(name))?;
+        for failure in failures.iter().filter(|f| f.ns == name) {
+            cov.skip(failure.path.clone(), &failure.kind, Reason::ParseFailure);
+        }
+        ret.push(cov);
+    }
+    Ok(ret)
+}
+
+/// The coverage of a gir or typelib file. Typelibs have no parse failures.
+pub fn read_coverage<P: AsRef<Path>>(filename: P) -> Result<Vec<Coverage>> {
+    let path = filename.as_ref();
+    let repo = read_repository(path)?;
+    let failures = if path.extension() == Some(OsStr::new("gir")) && path.exists() {
+        parse_failures(BufReader::new(File::open(path)?))?
+    } else {
+        vec![]
+    };
+    coverage(&repo, &failures)
+}
+
+/// A table per namespace of what was skipped and the totals per reason
+pub fn report(coverage: &[Coverage]) -> String {
+    let mut out = String::new();
+    let mut generated = 0;
+    let mut skipped = 0;
+    for cov in coverage.iter() {
+        out += &format!("{}: {} generated, {} skipped\n", cov.namespace, cov.generated, cov.skipped.len());
+        for reason in REASONS.iter() {
+            for s in cov.skipped.iter().filter(|s| s.reason == *reason) {
+                out += &format!("  {:<20} {:<16} {}\n", reason.to_string(), s.kind, s.path);
+            }
+        }
+        out += &totals(|r| cov.count(r));
+        generated += cov.generated;
+        skipped += cov.skipped.len();
+    }
+    if coverage.len() > 1 {
+        out += &format!("total: {} generated, {} skipped\n", generated, skipped);
+        out += &totals(|r| coverage.iter().map(|c| c.count(r)).sum());
+    }
+    out
+}
+
+fn totals<F: Fn(Reason) -> usize>(count: F) -> String {
+    let parts: Vec<String> = REASONS.iter()
+        .map(|r| format!("{}: {}", r, count(*r)))
+        .collect();
+    format!("  {}\n", parts.join(", "))
+}
+
+impl Visitor for Coverage {
+    fn visit_namespace<'a>(&mut self, ns: &'a Namespace, cx: Context<'a>) -> Result<()> {
+        for mac in ns.macros.iter() {
+            if unsupported(Element::Macro, cx.parent) {
+                self.skip(cx.path(&mac.name), "function-macro", Reason::Unsupported);
+            }
+        }
+        visit::walk_namespace(self, ns, cx)
+    }
+
+    fn visit_class<'a>(&mut self, class: &'a Class, cx: Context<'a>) -> Result<()> {
+        if !class.info.is_introspectable() {
+            self.skip(cx.path(&class.name), "class", Reason::NotIntrospectable);
+            return Ok(())
+        }
+        self.generated += 1;
+        visit::walk_class(self, class, cx)
+    }
+
+    fn visit_interface<'a>(&mut self, interface: &'a Interface, cx: Context<'a>) -> Result<()> {
+        if unsupported(Element::Interface, cx.parent) {
+            self.skip(cx.path(&interface.name), "interface", Reason::Unsupported);
+            return Ok(())
+        }
+        self.generated += 1;
+        visit::walk_interface(self, interface, cx)
+    }
+
+    fn visit_record<'a>(&mut self, record: &'a Record, cx: Context<'a>) -> Result<()> {
+        let path = cx.path(&record.name);
+        if !record.info.is_introspectable() {
+            self.skip(path, "record", Reason::NotIntrospectable);
+            return Ok(())
+        }
+        if unsupported(Element::Record(record), cx.parent) {
+            self.skip(path, "record", Reason::Unsupported);
+            return Ok(())
+        }
+        self.generated += 1;
+        visit::walk_record(self, record, cx)
+    }
+
+    fn visit_union<'a>(&mut self, union: &'a Union, cx: Context<'a>) -> Result<()> {
+        let path = cx.path(union.name.as_deref().unwrap_or(""));
+        if !union.info.is_introspectable() {
+            self.skip(path, "union", Reason::NotIntrospectable);
+            return Ok(())
+        }
+        if unsupported(Element::Union(union), cx.parent) {
+            self.skip(path, "union", Reason::Unsupported);
+            return Ok(())
+        }
+        self.generated += 1;
+        visit::walk_union(self, union, cx)
+    }
+
+    fn visit_enum<'a>(&mut self, e: &'a Enumeration, cx: Context<'a>) -> Result<()> {
+        if !e.info.is_introspectable() {
+            self.skip(cx.path(&e.name), "enumeration", Reason::NotIntrospectable);
+            return Ok(())
+        }
+        self.generated += 1;
+        visit::walk_enum(self, e, cx)
+    }
+
+    fn visit_bitfield<'a>(&mut self, b: &'a Bitfield, cx: Context<'a>) -> Result<()> {
+        if !b.info.is_introspectable() {
+            self.skip(cx.path(&b.name), "bitfield", Reason::NotIntrospectable);
+            return Ok(())
+        }
+        self.generated += 1;
+        visit::walk_bitfield(self, b, cx)
+    }
+
+    fn visit_boxed<'a>(&mut self, boxed: &'a Boxed, cx: Context<'a>) -> Result<()> {
+        if unsupported(Element::Boxed, cx.parent) {
+            self.skip(cx.path(&boxed.glib_name), "boxed", Reason::Unsupported);
+            return Ok(())
+        }
+        self.generated += 1;
+        visit::walk_boxed(self, boxed, cx)
+    }
+
+    fn visit_function<'a>(&mut self, fun: &'a Function, cx: Context<'a>) -> Result<()> {
+        let path = cx.path(&fun.name);
+        let kind = fun.kind();
+        if !fun.info.is_introspectable() {
+            let reason = if fun.shadowed_by.is_some() {
+                Reason::Shadowed
+            } else if fun.parameters.iter().any(|p| matches!(p.typ, AnyType::VarArg)) {
+                Reason::VarArgs
+            } else {
+                Reason::NotIntrospectable
+            };
+            self.skip(path, kind, reason);
+            return Ok(())
+        }
+        if unsupported(Element::Function(fun), cx.parent) {
+            self.skip(path, kind, Reason::Unsupported);
+            return Ok(())
+        }
+        self.generated += 1;
+        Ok(())
+    }
+
+    fn visit_signal<'a>(&mut self, signal: &'a Signal, cx: Context<'a>) -> Result<()> {
+        if !signal.info.is_introspectable() {
+            self.skip(cx.path(&signal.name), "signal", Reason::NotIntrospectable);
+            return Ok(())
+        }
+        self.generated += 1;
+        Ok(())
+    }
+
+    fn visit_property<'a>(&mut self, prop: &'a Property, cx: Context<'a>) -> Result<()> {
+        if !prop.info.is_introspectable() {
+            self.skip(cx.path(&prop.name), "property", Reason::NotIntrospectable);
+            return Ok(())
+        }
+        self.generated += 1;
+        Ok(())
+    }
+
+    fn visit_member<'a>(&mut self, member: &'a Member, cx: Context<'a>) -> Result<()> {
+        if !member.info.is_introspectable() {
+            self.skip(cx.path(&member.name), "member", Reason::NotIntrospectable);
+            return Ok(())
+        }
+        self.generated += 1;
+        Ok(())
+    }
+
+    fn visit_constant<'a>(&mut self, constant: &'a Constant, cx: Context<'a>) -> Result<()> {
+        let path = cx.path(&constant.name);
+        if !constant.info.is_introspectable() {
+            self.skip(path, "constant", Reason::NotIntrospectable);
+            return Ok(())
+        }
+        if unsupported(Element::Constant, cx.parent) {
+            self.skip(path, "constant", Reason::Unsupported);
+            return Ok(())
+        }
+        self.generated += 1;
+        Ok(())
+    }
+
+    fn visit_alias<'a>(&mut self, alias: &'a Alias, cx: Context<'a>) -> Result<()> {
+        if !alias.info.is_introspectable() {
+            self.skip(cx.path(&alias.name), "alias", Reason::NotIntrospectable);
+            return Ok(())
+        }
+        self.generated += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{coverage, Reason};
+    use crate::parse::{parse_failures, parse_gir};
+
+    #[test]
+    fn coverage_works() {
+        let gir = std::fs::read_to_string("testdata/girs/GMime-3.0.gir").unwrap();
+        // a method without a name can't be read
+        let gir = gir.replacen(r#"<method name="get_subject""#, "<method", 1);
+        let repo = parse_gir(gir.as_bytes()).unwrap();
+        let failures = parse_failures(gir.as_bytes()).unwrap();
+        let coverage = coverage(&repo, &failures).unwrap();
+        assert_eq!(coverage.len(), 1);
+        let gmime = &coverage[0];
+        assert_eq!(gmime.namespace, "GMime");
+        assert!(gmime.generated > 0);
+        assert!(gmime.skipped.iter().any(|s| s.path == "GMime.Stream.printf" && s.reason == Reason::VarArgs));
+        assert!(gmime.skipped.iter().any(|s| s.kind == "function-macro" && s.reason == Reason::Unsupported));
+        assert!(gmime.skipped.iter().any(|s| s.path == "GMime.Message" && s.reason == Reason::ParseFailure));
+        assert_eq!(gmime.count(Reason::ParseFailure), 1);
+    }
+}
@@ -0,0 +1,505 @@
+//! Comparing two versions of a repository, like Gtk 4.10 and 4.14.
+//! Changes are breaking when code written against the old stubs may
+//! not work with the new ones: something is removed, a parameter is
+//! added, a lua type changes or a return value may now be nil.
+use std::fmt;
+use serde::Serialize;
+
+use crate::library::*;
+use crate::lang::lua::{in_param, lua_params, out_param, show_anytyp};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// What changed, like `class`, `method` or `parameter`
+    pub what: &'static str,
+    /// The name as written in lua, like `Gtk.Widget:show`
+    pub path: String,
+    /// What is different, for changes
+    pub detail: String,
+    pub breaking: bool,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = match self.kind {
+            ChangeKind::Added => '+',
+            ChangeKind::Removed => '-',
+            ChangeKind::Changed => '~',
+        };
+        write!(f, "{} {} {}", sign, self.what, self.path)?;
+        if !self.detail.is_empty() {
+            write!(f, ": {}", self.detail)?;
+        }
+        if self.breaking {
+            write!(f, " (breaking)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The changes from `old` to `new`, namespaces are matched by name
+pub fn diff(old: &Repository, new: &Repository) -> Vec<Change> {
+    let mut d = Differ { changes: vec![] };
+    let (removed, both, added) = pair(&old.namespace, &new.namespace, |ns| ns.name.as_deref().unwrap_or(""));
+    for ns in removed {
+        d.push(ChangeKind::Removed, "namespace", ns.name.clone().unwrap_or_default(), String::new(), true);
+    }
+    for (o, n) in both {
+        d.namespace(o, n);
+    }
+    for ns in added {
+        d.added("namespace", ns.name.clone().unwrap_or_default());
+    }
+    d.changes
+}
+
+/// Match up elements by name: the ones only in `old`, the ones in both
+/// and the ones only in `new`
+#[allow(clippy::type_complexity)]
+fn pair<'a, T>(old: &'a [T], new: &'a [T], name: fn(&T) -> &str)
+    -> (Vec<&'a T>, Vec<(&'a T, &'a T)>, Vec<&'a T>) {
+    let mut removed = vec![];
+    let mut both = vec![];
+    for o in old.iter() {
+        match new.iter().find(|n| name(n) == name(o)) {
+            Some(n) => both.push((o, n)),
+            None => removed.push(o),
+        }
+    }
+    let added = new.iter()
+        .filter(|n| !old.iter().any(|o| name(o) == name(n)))
+        .collect();
+    (removed, both, added)
+}
+
+/// The type as in the gir, like `GLib.List<utf8>` or `guint8[]`
+fn type_name(typ: &AnyType) -> String {
+    match typ {
+        AnyType::Type(t) => {
+            let name = t.name.clone().unwrap_or_default();
+            if t.children.is_empty() {
+                name
+            } else {
+                let children: Vec<String> = t.children.iter().map(type_name).collect();
+                format!("{}<{}>", name, children.join(", "))
+            }
+        }
+        AnyType::Array(a) => format!("{}[]", type_name(&a.typ)),
+        AnyType::Callback(_) => "callback".to_string(),
+        AnyType::VarArg => "...".to_string(),
+    }
+}
+
+fn nullable(p: &Parameter) -> bool {
+    p.nullable || p.allow_none
+}
+
+/// The values a function returns in lua, named by their out parameter
+fn returns<'a>(params: &[&'a Parameter], ret: Option<&'a Parameter>, ns: &str) -> Vec<(Option<&'a str>, &'a Parameter)> {
+    ret.filter(|r| show_anytyp(&r.typ, ns) != "nil")
+        .map(|r| (None, r))
+        .into_iter()
+        .chain(params.iter().filter(|p| out_param(&p.direction)).map(|p| (Some(p.name.as_str()), *p)))
+        .collect()
+}
+
+fn deprecated(info: &InfoAttrs) -> bool {
+    info.deprecated == Some(true)
+}
+
+struct Differ {
+    changes: Vec<Change>,
+}
+
+impl Differ {
+    fn push(&mut self, kind: ChangeKind, what: &'static str, path: String, detail: String, breaking: bool) {
+        self.changes.push(Change { kind, what, path, detail, breaking });
+    }
+
+    fn added(&mut self, what: &'static str, path: String) {
+        self.push(ChangeKind::Added, what, path, String::new(), false);
+    }
+
+    /// Only breaking if the stubs had it, i.e. it was introspectable
+    fn removed(&mut self, what: &'static str, path: String, info: &InfoAttrs) {
+        self.push(ChangeKind::Removed, what, path, String::new(), info.is_introspectable());
+    }
+
+    fn changed(&mut self, what: &'static str, path: &str, detail: String, breaking: bool) {
+        self.push(ChangeKind::Changed, what, path.to_string(), detail, breaking);
+    }
+
+    /// Changes to the attributes every element has
+    fn info(&mut self, what: &'static str, path: &str, old: &InfoAttrs, new: &InfoAttrs) {
+        if !deprecated(old) && deprecated(new) {
+            let detail = match new.deprecated_version {
+                Some(ref version) => format!("deprecated since {}", version),
+                None => "deprecated".to_string(),
+            };
+            self.changed(what, path, detail, false);
+        } else if deprecated(old) && !deprecated(new) {
+            self.changed(what, path, "no longer deprecated".to_string(), false);
+        }
+        if old.is_introspectable() && !new.is_introspectable() {
+            self.changed(what, path, "no longer introspectable".to_string(), true);
+        } else if !old.is_introspectable() && new.is_introspectable() {
+            self.changed(what, path, "now introspectable".to_string(), false);
+        }
+    }
+
+    /// A type change, breaking if the type is different in lua
+    fn typ(&mut self, what: &'static str, path: &str, old: &AnyType, new: &AnyType, ns: &str) {
+        let (o, n) = (type_name(old), type_name(new));
+        if o != n {
+            let breaking = show_anytyp(old, ns) != show_anytyp(new, ns);
+            self.changed(what, path, format!("type {} -> {}", o, n), breaking);
+        }
+    }
+
+    fn namespace(&mut self, old: &Namespace, new: &Namespace) {
+        let ns = old.name.as_deref().unwrap_or("");
+        if old.version != new.version {
+            self.changed("namespace", ns, format!("version {} -> {}",
+                old.version.as_deref().unwrap_or("?"), new.version.as_deref().unwrap_or("?")), false);
+        }
+
+        let (removed, both, added) = pair(&old.classes, &new.classes, |c| &c.name);
+        for c in removed {
+            self.removed("class", format!("{}.{}", ns, c.name), &c.info);
+        }
+        for (o, n) in both {
+            self.class(o, n, ns);
+        }
+        for c in added {
+            self.added("class", format!("{}.{}", ns, c.name));
+        }
+
+        let (removed, both, added) = pair(&old.interfaces, &new.interfaces, |i| &i.name);
+        for i in removed {
+            self.removed("interface", format!("{}.{}", ns, i.name), &i.info);
+        }
+        for (o, n) in both {
+            self.interface(o, n, ns);
+        }
+        for i in added {
+            self.added("interface", format!("{}.{}", ns, i.name));
+        }
+
+        let (removed, both, added) = pair(&old.record, &new.record, |r| &r.name);
+        for r in removed {
+            self.removed("record", format!("{}.{}", ns, r.name), &r.info);
+        }
+        for (o, n) in both {
+            self.record(o, n, ns);
+        }
+        for r in added {
+            self.added("record", format!("{}.{}", ns, r.name));
+        }
+
+        let (removed, both, added) = pair(&old.enums, &new.enums, |e| &e.name);
+        for e in removed {
+            self.removed("enum", format!("{}.{}", ns, e.name), &e.info);
+        }
+        for (o, n) in both {
+            let path = format!("{}.{}", ns, o.name);
+            self.info("enum", &path, &o.info, &n.info);
+            self.members(&o.members, &n.members, &path);
+            self.functions(&o.functions, &n.functions, &path, ns);
+        }
+        for e in added {
+            self.added("enum", format!("{}.{}", ns, e.name));
+        }
+
+        let (removed, both, added) = pair(&old.bitfield, &new.bitfield, |b| &b.name);
+        for b in removed {
+            self.removed("bitfield", format!("{}.{}", ns, b.name), &b.info);
+        }
+        for (o, n) in both {
+            let path = format!("{}.{}", ns, o.name);
+            self.info("bitfield", &path, &o.info, &n.info);
+            self.members(&o.members, &n.members, &path);
+            self.functions(&o.functions, &n.functions, &path, ns);
+        }
+        for b in added {
+            self.added("bitfield", format!("{}.{}", ns, b.name));
+        }
+
+        self.functions(&old.functions, &new.functions, ns, ns);
+        self.functions(&old.callback, &new.callback, ns, ns);
+
+        let (removed, both, added) = pair(&old.constant, &new.constant, |c| &c.name);
+        for c in removed {
+            self.removed("constant", format!("{}.{}", ns, c.name), &c.info);
+        }
+        for (o, n) in both {
+            let path = format!("{}.{}", ns, o.name);
+            self.info("constant", &path, &o.info, &n.info);
+            if let (Some(ot), Some(nt)) = (&o.typ, &n.typ) {
+                self.typ("constant", &path, ot, nt, ns);
+            }
+            if o.value != n.value {
+                self.changed("constant", &path, format!("value {} -> {}", o.value, n.value), false);
+            }
+        }
+        for c in added {
+            self.added("constant", format!("{}.{}", ns, c.name));
+        }
+    }
+
+    fn class(&mut self, old: &Class, new: &Class, ns: &str) {
+        let path = format!("{}.{}", ns, old.name);
+        self.info("class", &path, &old.info, &new.info);
+        if old.parent != new.parent {
+            self.changed("class", &path, format!("parent {} -> {}",
+                old.parent.as_deref().unwrap_or("none"), new.parent.as_deref().unwrap_or("none")), true);
+        }
+        for i in old.implements.iter().filter(|i| !new.implements.iter().any(|n| n.name == i.name)) {
+            self.changed("class", &path, format!("no longer implements {}", i.name), true);
+        }
+        for i in new.implements.iter().filter(|i| !old.implements.iter().any(|o| o.name == i.name)) {
+            self.changed("class", &path, format!("implements {}", i.name), false);
+        }
+        self.functions(&old.constructor, &new.constructor, &path, ns);
+        self.functions(&old.method, &new.method, &path, ns);
+        self.functions(&old.functions, &new.functions, &path, ns);
+        self.properties(&old.properties, &new.properties, &path, ns);
+        self.signals(&old.signals, &new.signals, &path, ns);
+    }
+
+    fn interface(&mut self, old: &Interface, new: &Interface, ns: &str) {
+        let path = format!("{}.{}", ns, old.name);
+        self.info("interface", &path, &old.info, &new.info);
+        self.functions(&old.method, &new.method, &path, ns);
+        self.functions(&old.functions, &new.functions, &path, ns);
+        self.properties(&old.properties, &new.properties, &path, ns);
+        self.signals(&old.signals, &new.signals, &path, ns);
+    }
+
+    fn record(&mut self, old: &Record, new: &Record, ns: &str) {
+        let path = format!("{}.{}", ns, old.name);
+        self.info("record", &path, &old.info, &new.info);
+        let (removed, both, added) = pair(&old.fields, &new.fields, |f| &f.name);
+        for f in removed {
+            self.removed("field", format!("{}.{}", path, f.name), &f.info);
+        }
+        for (o, n) in both {
+            let fpath = format!("{}.{}", path, o.name);
+            self.typ("field", &fpath, &o.typ, &n.typ, ns);
+            if o.writeable && !n.writeable {
+                self.changed("field", &fpath, "no longer writable".to_string(), true);
+            }
+        }
+        for f in added {
+            self.added("field", format!("{}.{}", path, f.name));
+        }
+        self.functions(&old.constructor, &new.constructor, &path, ns);
+        self.functions(&old.method, &new.method, &path, ns);
+        self.functions(&old.functions, &new.functions, &path, ns);
+    }
+
+    fn members(&mut self, old: &[Member], new: &[Member], path: &str) {
+        let (removed, both, added) = pair(old, new, |m| &m.name);
+        for m in removed {
+            self.removed("member", format!("{}.{}", path, m.name.to_uppercase()), &m.info);
+        }
+        for (o, n) in both {
+            if o.value != n.value {
+                self.changed("member", &format!("{}.{}", path, o.name.to_uppercase()),
+                    format!("value {} -> {}", o.value, n.value), true);
+            }
+        }
+        for m in added {
+            self.added("member", format!("{}.{}", path, m.name.to_uppercase()));
+        }
+    }
+
+    fn functions(&mut self, old: &[Function], new: &[Function], static_ns: &str, ns: &str) {
+        let name = |f: &Function| {
+            let sep = if f.typ == FunctionType::Method { ":" } else { "." };
+            format!("{}{}{}", static_ns, sep, f.name)
+        };
+        let (removed, both, added) = pair(old, new, |f| &f.name);
+        for f in removed {
+            self.removed(f.kind(), name(f), &f.info);
+        }
+        for (o, n) in both {
+            self.function(o, n, &name(o), ns);
+        }
+        for f in added {
+            self.added(f.kind(), name(f));
+        }
+    }
+
+    fn function(&mut self, old: &Function, new: &Function, path: &str, ns: &str) {
+        let what = old.kind();
+        self.info(what, path, &old.info, &new.info);
+        if old.typ != new.typ {
+            self.changed(what, path, format!("now a {}", new.kind()), true);
+        }
+        if old.throws.unwrap_or(false) != new.throws.unwrap_or(false) {
+            let detail = if new.throws.unwrap_or(false) { "now throws" } else { "no longer throws" };
+            self.changed(what, path, detail.to_string(), true);
+        }
+        self.signature(&old.parameters, old.ret.as_ref(), &new.parameters, new.ret.as_ref(), path, ns);
+    }
+
+    /// Compares what the stubs show: the parameters passed from lua and
+    /// the values returned, the return value followed by the out parameters
+    fn signature(&mut self, old: &[Parameter], old_ret: Option<&Parameter>,
+        new: &[Parameter], new_ret: Option<&Parameter>, path: &str, ns: &str) {
+        let old = lua_params(old, old_ret);
+        let new = lua_params(new, new_ret);
+        let old_in: Vec<&Parameter> = old.iter().copied().filter(|p| in_param(&p.direction)).collect();
+        let new_in: Vec<&Parameter> = new.iter().copied().filter(|p| in_param(&p.direction)).collect();
+        self.params(&old_in, &new_in, path, ns);
+        self.returns(&returns(&old, old_ret, ns), &returns(&new, new_ret, ns), path, ns);
+    }
+
+    fn params(&mut self, old: &[&Parameter], new: &[&Parameter], path: &str, ns: &str) {
+        for (i, o) in old.iter().enumerate() {
+            let ppath = format!("{}({})", path, o.name);
+            let n = match new.get(i) {
+                Some(n) => n,
+                None => {
+                    self.push(ChangeKind::Removed, "parameter", ppath, String::new(), true);
+                    continue;
+                }
+            };
+            if o.name != n.name {
+                self.changed("parameter", &ppath, format!("renamed to {}", n.name), false);
+            }
+            self.typ("parameter", &ppath, &o.typ, &n.typ, ns);
+            if nullable(o) && !nullable(n) {
+                self.changed("parameter", &ppath, "no longer nullable".to_string(), true);
+            } else if !nullable(o) && nullable(n) {
+                self.changed("parameter", &ppath, "now nullable".to_string(), false);
+            }
+        }
+        for n in new.iter().skip(old.len()) {
+            // a trailing parameter that can be nil can be left out in lua
+            let breaking = !(nullable(n) || n.optional);
+            self.push(ChangeKind::Added, "parameter", format!("{}({})", path, n.name), String::new(), breaking);
+        }
+    }
+
+    /// The return values by position, `None` is the return value itself
+    fn returns(&mut self, old: &[(Option<&str>, &Parameter)], new: &[(Option<&str>, &Parameter)], path: &str, ns: &str) {
+        let name = |name: Option<&str>| match name {
+            Some(name) => format!("{}({})", path, name),
+            None => path.to_string(),
+        };
+        for (i, (oname, o)) in old.iter().enumerate() {
+            let rpath = name(*oname);
+            let n = match new.get(i) {
+                Some((_, n)) => n,
+                None => {
+                    self.push(ChangeKind::Removed, "return value", rpath, String::new(), true);
+                    continue;
+                }
+            };
+            self.typ("return value", &rpath, &o.typ, &n.typ, ns);
+            if !nullable(o) && nullable(n) {
+                self.changed("return value", &rpath, "may now be nil".to_string(), true);
+            } else if nullable(o) && !nullable(n) {
+                self.changed("return value", &rpath, "no longer nil".to_string(), false);
+            }
+        }
+        // extra return values are ignored by existing callers
+        for (nname, _) in new.iter().skip(old.len()) {
+            self.push(ChangeKind::Added, "return value", name(*nname), String::new(), false);
+        }
+    }
+
+    fn properties(&mut self, old: &[Property], new: &[Property], path: &str, ns: &str) {
+        let name = |p: &Property| format!("{}.{}", path, p.name.replace('-', "_"));
+        let (removed, both, added) = pair(old, new, |p| &p.name);
+        for p in removed {
+            self.removed("property", name(p), &p.info);
+        }
+        for (o, n) in both {
+            let ppath = name(o);
+            self.info("property", &ppath, &o.info, &n.info);
+            self.typ("property", &ppath, &o.typ, &n.typ, ns);
+            if o.readable && !n.readable {
+                self.changed("property", &ppath, "no longer readable".to_string(), true);
+            }
+            if o.writable && !n.writable {
+                self.changed("property", &ppath, "no longer writable".to_string(), true);
+            } else if !o.writable && n.writable {
+                self.changed("property", &ppath, "now writable".to_string(), false);
+            }
+        }
+        for p in added {
+            self.added("property", name(p));
+        }
+    }
+
+    fn signals(&mut self, old: &[Signal], new: &[Signal], path: &str, ns: &str) {
+        let name = |s: &Signal| format!("{}::{}", path, s.name);
+        let (removed, both, added) = pair(old, new, |s| &s.name);
+        for s in removed {
+            self.removed("signal", name(s), &s.info);
+        }
+        for (o, n) in both {
+            let spath = name(o);
+            self.info("signal", &spath, &o.info, &n.info);
+            self.signature(&o.parameters, o.ret.as_ref(), &n.parameters, n.ret.as_ref(), &spath, ns);
+        }
+        for s in added {
+            self.added("signal", name(s));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, ChangeKind};
+    use crate::parse::parse_gir;
+
+    #[test]
+    fn removed_works() {
+        let gir = std::fs::read_to_string("testdata/girs/GMime-3.0.gir").unwrap();
+        let old = parse_gir(gir.as_bytes()).unwrap();
+        let new = parse_gir(gir.replacen(r#"<method name="get_subject""#, r#"<method name="get_title""#, 1).as_bytes()).unwrap();
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, ChangeKind::Removed);
+        assert_eq!(changes[0].path, "GMime.Message:get_subject");
+        assert!(changes[0].breaking);
+        assert_eq!(changes[1].path, "GMime.Message:get_title");
+        assert!(!changes[1].breaking);
+    }
+
+    #[test]
+    fn breaking_works() {
+        let gir = std::fs::read_to_string("testdata/girs/GMime-3.0.gir").unwrap();
+
+        // never in the stubs, so removing it breaks nothing
+        let old = gir.replacen(r#"<method name="get_subject""#, r#"<method name="get_subject" introspectable="0""#, 1);
+        let new = gir.replacen(r#"<method name="get_subject""#, r#"<method name="get_title""#, 1);
+        let changes = diff(&parse_gir(old.as_bytes()).unwrap(), &parse_gir(new.as_bytes()).unwrap());
+        assert_eq!(changes[0].path, "GMime.Message:get_subject");
+        assert!(!changes[0].breaking);
+
+        // an out parameter is an extra return value in lua
+        let verify = gir.find(r#"<method name="verify""#).unwrap();
+        let start = verify + gir[verify..].find(r#"<parameter name="entity""#).unwrap();
+        let end = start + gir[start..].find("</parameter>").unwrap() + "</parameter>".len();
+        let old = format!("{}{}", &gir[..start], &gir[end..]);
+        let changes = diff(&parse_gir(old.as_bytes()).unwrap(), &parse_gir(gir.as_bytes()).unwrap());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, ChangeKind::Added);
+        assert_eq!(changes[0].what, "return value");
+        assert_eq!(changes[0].path, "GMime.ApplicationPkcs7Mime:verify(entity)");
+        assert!(!changes[0].breaking);
+    }
+}
@@ -0,0 +1,484 @@
+//! Choosing a generator and running it over gir files, everything the
+//! command line does.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use anyhow::{bail, Result};
+
+use crate::check::{self, Stale};
+use crate::lang::Generator;
+use crate::lang::augment::Augment;
+use crate::lang::lua::LuaCodegen;
+use crate::manifest::{self, Hashes, Input, Manifest};
+use crate::output;
+use crate::library::Repository;
+use crate::resolve::{locate, read_repository, Resolver};
+use crate::sink::{DirSink, MemorySink, RecordingSink, Sink};
+
+/// What to generate, stubs or one of the documentation formats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Stub,
+    Vimdoc,
+    Markdown,
+    Html,
+    Man,
+    Docset,
+    Json,
+    Gir,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stub" => Ok(Format::Stub),
+            "vimdoc" => Ok(Format::Vimdoc),
+            "markdown" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            "man" => Ok(Format::Man),
+            "docset" => Ok(Format::Docset),
+            "json" => Ok(Format::Json),
+            "gir" => Ok(Format::Gir),
+            format => {
+                let ret = format!("{} format not supported", format);
+                Err(ret)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Stub => f.write_str("stub"),
+            Format::Vimdoc => f.write_str("vimdoc"),
+            Format::Markdown => f.write_str("markdown"),
+            Format::Html => f.write_str("html"),
+            Format::Man => f.write_str("man"),
+            Format::Docset => f.write_str("docset"),
+            Format::Json => f.write_str("json"),
+            Format::Gir => f.write_str("gir"),
+        }
+    }
+}
+
+/// The language of the stubs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lang {
+    Lua,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lua" => Ok(Lang::Lua),
+            lang => {
+                let ret = format!("{} not supported", lang);
+                Err(ret)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Lang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lang::Lua => f.write_str("lua"),
+        }
+    }
+}
+
+/// Everything that decides what is generated from which files
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub format: Format,
+    /// The language of the stubs, the documentation formats use lua names
+    pub lang: Lang,
+    /// gir or typelib files, every installed gir file if empty
+    pub files: Vec<String>,
+    /// Defaults to a directory depending on the format, like `types` for stubs
+    pub output_dir: Option<String>,
+    /// Directory with hand-written snippets that are merged into the stubs
+    pub augment: Option<String>,
+    /// Generate every input, even if the manifest says it's unchanged
+    pub force: bool,
+    /// The number of files generated at the same time, 0 for one per cpu
+    pub jobs: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            format: Format::Stub,
+            lang: Lang::Lua,
+            files: vec![],
+            output_dir: None,
+            augment: None,
+            force: false,
+            jobs: 0,
+        }
+    }
+}
+
+impl Options {
+    /// The generator for the format, types from other namespaces
+    /// are looked up in `resolver`
+    pub fn generator(&self, resolver: Arc<Resolver>) -> Result<Box<dyn Generator>> {
+        let gen: Box<dyn Generator> = match self.format {
+            Format::Stub => match self.lang {
+                Lang::Lua => {
+                    let mut cg = LuaCodegen::new()
+                        .resolver(resolver);
+                    if let Some(ref dir) = self.augment {
+                        cg = cg.augment(Augment::load(dir, "lua")?);
+                    }
+                    Box::new(cg)
+                }
+            },
+            Format::Vimdoc => Box::new(output::vimdoc::VimDoc::new()),
+            Format::Markdown => Box::new(output::markdown::Markdown::new().resolver(resolver)),
+            Format::Html => Box::new(output::html::Html::new().resolver(resolver)),
+            Format::Man => Box::new(output::man::Man::new().resolver(resolver)),
+            Format::Docset => Box::new(output::docset::Docset::new().resolver(resolver)),
+            Format::Json => Box::new(output::json::Json::new()),
+            Format::Gir => Box::new(output::gir::Gir::new()),
+        };
+        Ok(gen)
+    }
+
+    /// Generate into the output directory. Inputs that haven't changed
+    /// since the last run, according to the manifest in the directory,
+    /// are skipped and files that aren't generated anymore are deleted.
+    pub fn run(&self) -> Result<()> {
+        let resolver = Arc::new(Resolver::default());
+        let gen = self.generator(resolver.clone())?;
+        let dir = Path::new(self.output_dir.as_deref().unwrap_or(gen.default_dir()));
+        let sink = DirSink::new(dir);
+
+        let old = if self.force { Manifest::default() } else { Manifest::load(dir) };
+        let mut new = Manifest::new(self.fingerprint()?);
+        let mut hashes = Hashes::default();
+        let mut todo = vec![];
+        let mut unchanged = vec![];
+        let inputs = self.inputs()?;
+        let same_inputs = inputs.len() == old.inputs.len()
+            && inputs.iter().all(|f| old.inputs.contains_key(f));
+        for file in inputs.iter() {
+            match old.inputs.get(file) {
+                Some(input) if new.compatible(&old) && hashes.unchanged(input, &locate(file), dir) => {
+                    new.inputs.insert(file.clone(), input.clone());
+                    unchanged.push(file.clone());
+                }
+                _ => todo.push(file.clone()),
+            }
+        }
+        let finished = hashes.written(&old.finished, dir);
+        if todo.is_empty() && finished && (same_inputs || !self.files.is_empty()) {
+            return Ok(())
+        }
+        if !gen.incremental() {
+            todo.append(&mut unchanged);
+            todo.sort();
+        }
+        // files given in earlier runs keep their output, with --gen-all
+        // the ones that aren't installed anymore are removed
+        if !self.files.is_empty() {
+            for (file, input) in old.inputs.iter().filter(|(f, _)| !inputs.contains(f)) {
+                let mut input = input.clone();
+                if !new.compatible(&old) {
+                    // generated with other options, not skipped next time
+                    input.hash.clear();
+                }
+                new.inputs.insert(file.clone(), input);
+            }
+        }
+
+        let hashes = Mutex::new(hashes);
+        let repos = self.load(&resolver, &todo)?;
+        gen.prepare(&repos.iter().map(|(_, repo)| repo.clone()).collect::<Vec<_>>());
+        let generated = pool(self.jobs(), &repos, |(file, repo)| {
+            if self.files.is_empty() {
+                println!("Generating file {}", file);
+            }
+            let recording = RecordingSink::new(&sink);
+            gen.generate(repo, &recording)?;
+            let mut hashes = hashes.lock().unwrap();
+            let mut includes = BTreeMap::new();
+            for path in resolver.include_files(repo) {
+                if let Some(hash) = hashes.file(&path) {
+                    includes.insert(path, hash);
+                }
+            }
+            Ok(Input {
+                hash: hashes.file(locate(file)).unwrap_or_default(),
+                includes,
+                outputs: recording.into_files(),
+            })
+        })?;
+        new.inputs.extend(todo.into_iter().zip(generated));
+        let recording = RecordingSink::new(&sink);
+        gen.finish(&recording)?;
+        new.finished = recording.into_files();
+
+        new.remove_stale(&old, dir)?;
+        new.save(dir)
+    }
+
+    /// Generate into memory and compare with the output directory, without
+    /// writing anything. Files the manifest says were generated from the
+    /// inputs that aren't generated anymore are stale too.
+    pub fn check(&self) -> Result<Vec<Stale>> {
+        Ok(self.compare()?.1)
+    }
+
+    /// The files `run` would write, the ones that are missing from the
+    /// output directory or have other contents. Nothing is written.
+    pub fn dry_run(&self) -> Result<Vec<PathBuf>> {
+        let (dir, stale) = self.compare()?;
+        Ok(stale.iter()
+            .filter(|s| !matches!(s, Stale::Extra(_)))
+            .map(|s| dir.join(s.path()))
+            .collect())
+    }
+
+    /// Generate into memory, only the files of `name` if it's given: a
+    /// type like `Gio.File`, or a namespace like `Gio` for all of them
+    pub fn files(&self, name: Option<&str>) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+        self.files_with(Arc::new(Resolver::default()), name)
+    }
+
+    /// Like `files`, with includes looked up in `resolver`
+    pub fn files_with(&self, resolver: Arc<Resolver>, name: Option<&str>) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+        let gen = self.generator(resolver.clone())?;
+        let sink = MemorySink::new();
+        let repos = self.load(&resolver, &self.inputs()?)?;
+        self.generate(gen.as_ref(), &repos, &sink)?;
+        let mut files = sink.into_files();
+        let Some(name) = name else {
+            return Ok(files)
+        };
+
+        let (ns, typ) = match name.split_once('.') {
+            Some((ns, typ)) => (ns, Some(typ)),
+            None => (name, None),
+        };
+        let found = repos.iter()
+            .any(|(_, repo)| repo.namespace.iter().any(|n| n.name.as_deref() == Some(ns)));
+        if !found {
+            bail!("Namespace {} isn't generated from the given files", ns)
+        }
+        if let Some(typ) = typ {
+            // the files are named after the type, like Gio_2_0/File.lua
+            // or man3/Gio.File.3lua
+            files.retain(|path, _| {
+                path.file_stem().is_some_and(|stem| stem == typ || stem.to_string_lossy() == name)
+            });
+            if files.is_empty() {
+                bail!("No file is generated for {}", name)
+            }
+        }
+        Ok(files)
+    }
+
+    /// Generate into memory and compare with the output directory
+    fn compare(&self) -> Result<(PathBuf, Vec<Stale>)> {
+        let resolver = Arc::new(Resolver::default());
+        let gen = self.generator(resolver.clone())?;
+        let dir = PathBuf::from(self.output_dir.as_deref().unwrap_or(gen.default_dir()));
+        let sink = MemorySink::new();
+        self.run_with(gen.as_ref(), &resolver, &sink)?;
+
+        let old = Manifest::load(&dir);
+        let mut outputs: BTreeSet<PathBuf> = old.finished.keys().cloned().collect();
+        for file in self.inputs()? {
+            if let Some(input) = old.inputs.get(&file) {
+                outputs.extend(input.outputs.keys().cloned());
+            }
+        }
+        let stale = check::compare(&sink, &dir, &outputs)?;
+        Ok((dir, stale))
+    }
+
+    /// Generate every file with `gen` into `sink`
+    pub fn run_with(&self, gen: &dyn Generator, resolver: &Resolver, sink: &dyn Sink) -> Result<()> {
+        let repos = self.load(resolver, &self.inputs()?)?;
+        self.generate(gen, &repos, sink)
+    }
+
+    fn generate(&self, gen: &dyn Generator, repos: &[(String, Arc<Repository>)], sink: &dyn Sink) -> Result<()> {
+        gen.prepare(&repos.iter().map(|(_, repo)| repo.clone()).collect::<Vec<_>>());
+        pool(self.jobs(), repos, |(_, repo)| gen.generate(repo, sink))?;
+        gen.finish(sink)
+    }
+
+    /// Parse `files` and add them to `resolver` before anything is generated,
+    /// so an include that is also an input is always the input, whichever
+    /// file is generated first
+    fn load(&self, resolver: &Resolver, files: &[String]) -> Result<Vec<(String, Arc<Repository>)>> {
+        let repos = pool(self.jobs(), files, |file| read_repository(file))?;
+        Ok(files.iter()
+            .zip(repos)
+            .map(|(file, repo)| (file.clone(), resolver.add_file(file, repo)))
+            .collect())
+    }
+
+    /// The number of threads, one per cpu if not set
+    fn jobs(&self) -> usize {
+        match self.jobs {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            jobs => jobs,
+        }
+    }
+
+    /// The files to generate, every installed gir file if none are given
+    fn inputs(&self) -> Result<Vec<String>> {
+        if !self.files.is_empty() {
+            return Ok(self.files.clone())
+        }
+        let mut files = vec![];
+        for path in fs::read_dir("/usr/share/gir-1.0/")? {
+            files.push(path?.path().to_string_lossy().into_owned());
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// The options that change the output, with the contents of the
+    /// augment snippets
+    fn fingerprint(&self) -> Result<String> {
+        let mut fingerprint = format!("format={}", self.format);
+        if self.format == Format::Stub {
+            fingerprint += &format!(" lang={}", self.lang);
+        }
+        if let Some(ref dir) = self.augment {
+            let mut files = vec![];
+            augment_files(Path::new(dir), &mut files)?;
+            files.sort();
+            let mut data = vec![];
+            for file in files {
+                data.extend(file.to_string_lossy().as_bytes());
+                data.extend(fs::read(&file)?);
+            }
+            fingerprint += &format!(" augment={}:{}", dir, manifest::hash(&data));
+        }
+        Ok(fingerprint)
+    }
+}
+
+/// Call `f` for every item on `jobs` threads, the results are in the order
+/// of the items. After an error no more items are started and the error of
+/// the first failed item is returned.
+fn pool<T, R, F>(jobs: usize, items: &[T], f: F) -> Result<Vec<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<R>>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|s| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(i) else {
+                    break
+                };
+                let result = f(item);
+                if result.is_err() {
+                    next.store(items.len(), Ordering::Relaxed);
+                }
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+fn augment_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            augment_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use super::{Format, Options};
+    use crate::resolve::Resolver;
+    use crate::sink::MemorySink;
+
+    /// Includes are only searched next to the test girs
+    fn generate(format: Format, files: &[&str], jobs: usize) -> MemorySink {
+        let options = Options {
+            format,
+            files: files.iter().map(|f| format!("testdata/girs/{}", f)).collect(),
+            jobs,
+            ..Options::default()
+        };
+        let resolver = Arc::new(Resolver::new(vec![]));
+        let gen = options.generator(resolver.clone()).unwrap();
+        let sink = MemorySink::new();
+        options.run_with(gen.as_ref(), &resolver, &sink).unwrap();
+        sink
+    }
+
+    #[test]
+    fn jobs_works() {
+        // the same output, whatever order the files are generated in
+        let files = ["GMime-3.0.gir", "GObject-2.0.gir", "Toolkit-3.0.gir", "Toolkit-4.0.gir"];
+        let one = generate(Format::Html, &files, 1).into_files();
+        let four = generate(Format::Html, &files, 4).into_files();
+        assert_eq!(one, four);
+
+        // every version has its own pages, not the ones of whichever was generated last
+        for (dir, prop, other) in [("Toolkit-3.0", "has_tooltip", "css_name"), ("Toolkit-4.0", "css_name", "has_tooltip")] {
+            for page in ["index.html", "Widget.html", "Button.html"] {
+                assert!(one.contains_key(&PathBuf::from(format!("{}/{}", dir, page))), "{}/{}", dir, page);
+            }
+            let widget = String::from_utf8(one[&PathBuf::from(format!("{}/Widget.html", dir))].clone()).unwrap();
+            assert!(widget.contains(prop) && !widget.contains(other));
+        }
+        assert!(one.contains_key(&PathBuf::from("GMime-3.0/Message.html")));
+        assert!(one.contains_key(&PathBuf::from("GObject-2.0/Object.html")));
+    }
+
+    #[test]
+    fn versions_works() {
+        // two versions of a namespace in one run, each built from its own classes
+        for jobs in [1, 4] {
+            let sink = generate(Format::Stub, &["Toolkit-3.0.gir", "Toolkit-4.0.gir"], jobs);
+            let tk3 = String::from_utf8(sink.get("Toolkit_3_0/Toolkit_3_0/Button.lua").unwrap()).unwrap();
+            let tk4 = String::from_utf8(sink.get("Toolkit_4_0/Toolkit_4_0/Button.lua").unwrap()).unwrap();
+            assert!(tk3.contains("--- @field has_tooltip? string") && !tk3.contains("css_name"));
+            assert!(tk4.contains("--- @field css_name? string") && !tk4.contains("has_tooltip"));
+        }
+    }
+
+    #[test]
+    fn files_works() {
+        let options = Options {
+            files: vec!["testdata/girs/GMime-3.0.gir".to_string()],
+            ..Options::default()
+        };
+        let files = |name| options.files_with(Arc::new(Resolver::new(vec![])), Some(name));
+        let certificate = files("GMime.Certificate").unwrap();
+        assert_eq!(certificate.keys().collect::<Vec<_>>(), vec![Path::new("GMime_3_0/GMime_3_0/Certificate.lua")]);
+        assert!(files("GMime").unwrap().len() > 1);
+        assert!(files("Gtk.Widget").is_err());
+    }
+}
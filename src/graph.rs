@@ -0,0 +1,266 @@
+//! The class hierarchy, the interfaces classes implement and the
+//! namespaces repositories include, as Graphviz DOT or Mermaid.
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::str::FromStr;
+use std::sync::Arc;
+use anyhow::Result;
+
+use crate::library::*;
+use crate::resolve::Resolver;
+use crate::lang::lua::translate_ns;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            format => Err(format!("{} graph format not supported", format)),
+        }
+    }
+}
+
+impl std::fmt::Display for GraphFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphFormat::Dot => f.write_str("dot"),
+            GraphFormat::Mermaid => f.write_str("mermaid"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NodeKind {
+    Class,
+    Interface,
+    Namespace,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    /// A class and its parent
+    Inherits,
+    /// A class and an interface it implements
+    Implements,
+    /// An interface and its prerequisite
+    Requires,
+    /// A namespace and a namespace it includes
+    Includes,
+}
+
+/// Edges point from a type to its parent or interface, and from a
+/// namespace to its include
+#[derive(Debug, Default)]
+pub struct Graph {
+    pub nodes: BTreeMap<String, NodeKind>,
+    pub edges: BTreeSet<(String, String, EdgeKind)>,
+}
+
+impl Graph {
+    /// A type or namespace that is referenced, its kind is a guess
+    /// until it's defined
+    fn node(&mut self, name: &str, kind: NodeKind) {
+        self.nodes.entry(name.to_string()).or_insert(kind);
+    }
+
+    fn define(&mut self, name: &str, kind: NodeKind) {
+        self.nodes.insert(name.to_string(), kind);
+    }
+
+    fn edge(&mut self, from: &str, to: &str, kind: EdgeKind) {
+        self.edges.insert((from.to_string(), to.to_string(), kind));
+    }
+
+    /// The classes and interfaces of `repo`. Parents and interfaces
+    /// from other namespaces are added as nodes, not their parents.
+    pub fn types(&mut self, repo: &Repository) {
+        for ns in repo.namespace.iter() {
+            let name = ns.name.as_deref().unwrap_or("");
+            for class in ns.classes.iter() {
+                let qualified = format!("{}.{}", name, class.name);
+                self.define(&qualified, NodeKind::Class);
+                if let Some(ref parent) = class.parent {
+                    let parent = translate_ns(parent, name);
+                    self.node(&parent, NodeKind::Class);
+                    self.edge(&qualified, &parent, EdgeKind::Inherits);
+                }
+                for implement in class.implements.iter() {
+                    let interface = translate_ns(&implement.name, name);
+                    self.node(&interface, NodeKind::Interface);
+                    self.edge(&qualified, &interface, EdgeKind::Implements);
+                }
+            }
+            for interface in ns.interfaces.iter() {
+                let qualified = format!("{}.{}", name, interface.name);
+                self.define(&qualified, NodeKind::Interface);
+                for prerequisite in interface.prerequisites.iter() {
+                    let prerequisite = translate_ns(prerequisite, name);
+                    // mostly a class, like GObject.Object
+                    self.node(&prerequisite, NodeKind::Class);
+                    self.edge(&qualified, &prerequisite, EdgeKind::Requires);
+                }
+            }
+        }
+    }
+
+    /// The namespaces `repo` includes, and their includes, as far as
+    /// `resolver` can find them
+    pub fn namespaces(&mut self, repo: &Repository, resolver: &Resolver) {
+        let version = |name: &str, version: Option<&str>| format!("{}-{}", name, version.unwrap_or("?"));
+        let mut todo: VecDeque<(String, Option<Arc<Repository>>)> = VecDeque::new();
+        for ns in repo.namespace.iter() {
+            let name = version(ns.name.as_deref().unwrap_or(""), ns.version.as_deref());
+            self.define(&name, NodeKind::Namespace);
+            for include in repo.include.iter() {
+                let inc = version(&include.name, include.version.as_deref());
+                self.edge(&name, &inc, EdgeKind::Includes);
+                todo.push_back((inc, resolver.load(&include.name, include.version.as_deref())));
+            }
+        }
+        while let Some((name, repo)) = todo.pop_front() {
+            if self.nodes.contains_key(&name) {
+                continue;
+            }
+            self.define(&name, NodeKind::Namespace);
+            for include in repo.iter().flat_map(|r| r.include.iter()) {
+                let inc = version(&include.name, include.version.as_deref());
+                self.edge(&name, &inc, EdgeKind::Includes);
+                todo.push_back((inc, resolver.load(&include.name, include.version.as_deref())));
+            }
+        }
+    }
+
+    /// Only the nodes within `depth` edges of `root`: its ancestors and
+    /// the types below it. Without a root the depth is counted from the
+    /// nodes without parents.
+    pub fn limit(&self, root: Option<&str>, depth: Option<usize>) -> Result<Graph> {
+        let depth = depth.unwrap_or(usize::MAX);
+        let mut keep = BTreeSet::new();
+        let tops: Vec<&str> = match root {
+            Some(root) => {
+                let root = match self.nodes.get_key_value(root) {
+                    Some((root, _)) => root.as_str(),
+                    None => anyhow::bail!("{} isn't in the graph", root),
+                };
+                // up, following the edges
+                self.walk(root, depth, true, &mut keep);
+                vec![root]
+            }
+            None => self.nodes.keys()
+                .map(String::as_str)
+                .filter(|n| !self.edges.iter().any(|(from, _, _)| from == n))
+                .collect(),
+        };
+        // down, against the edges
+        for top in tops {
+            self.walk(top, depth, false, &mut keep);
+        }
+        Ok(Graph {
+            nodes: self.nodes.iter()
+                .filter(|(n, _)| keep.contains(n.as_str()))
+                .map(|(n, k)| (n.clone(), *k))
+                .collect(),
+            edges: self.edges.iter()
+                .filter(|(from, to, _)| keep.contains(from.as_str()) && keep.contains(to.as_str()))
+                .cloned()
+                .collect(),
+        })
+    }
+
+    fn walk<'g>(&'g self, start: &'g str, depth: usize, up: bool, keep: &mut BTreeSet<&'g str>) {
+        let mut queue = VecDeque::from([(start, 0)]);
+        let mut seen = BTreeSet::from([start]);
+        while let Some((node, d)) = queue.pop_front() {
+            keep.insert(node);
+            if d == depth {
+                continue;
+            }
+            for (from, to, _) in self.edges.iter() {
+                let next = if up && from == node {
+                    to
+                } else if !up && to == node {
+                    from
+                } else {
+                    continue;
+                };
+                if seen.insert(next.as_str()) {
+                    queue.push_back((next.as_str(), d + 1));
+                }
+            }
+        }
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.dot(),
+            GraphFormat::Mermaid => self.mermaid(),
+        }
+    }
+
+    /// Parents above children, interfaces as ellipses and edges to
+    /// interfaces dashed
+    pub fn dot(&self) -> String {
+        let mut out = String::from("digraph {\n  rankdir=BT;\n  node [shape=box];\n");
+        for (name, kind) in self.nodes.iter() {
+            out += &match kind {
+                NodeKind::Interface => format!("  \"{}\" [shape=ellipse];\n", name),
+                NodeKind::Class | NodeKind::Namespace => format!("  \"{}\";\n", name),
+            };
+        }
+        for (from, to, kind) in self.edges.iter() {
+            out += &match kind {
+                EdgeKind::Inherits | EdgeKind::Includes => format!("  \"{}\" -> \"{}\";\n", from, to),
+                EdgeKind::Implements | EdgeKind::Requires =>
+                    format!("  \"{}\" -> \"{}\" [style=dashed];\n", from, to),
+            };
+        }
+        out += "}\n";
+        out
+    }
+
+    /// A flowchart, interfaces are rounded and edges to interfaces dotted
+    pub fn mermaid(&self) -> String {
+        let id = |name: &str| name.replace(['.', '-'], "_");
+        let mut out = String::from("graph BT\n");
+        for (name, kind) in self.nodes.iter() {
+            out += &match kind {
+                NodeKind::Interface => format!("  {}([\"{}\"])\n", id(name), name),
+                NodeKind::Class | NodeKind::Namespace => format!("  {}[\"{}\"]\n", id(name), name),
+            };
+        }
+        for (from, to, kind) in self.edges.iter() {
+            out += &match kind {
+                EdgeKind::Inherits | EdgeKind::Includes => format!("  {} --> {}\n", id(from), id(to)),
+                EdgeKind::Implements | EdgeKind::Requires => format!("  {} -.-> {}\n", id(from), id(to)),
+            };
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Graph;
+    use crate::parse::parse_gir;
+
+    #[test]
+    fn limit_works() {
+        let f = std::fs::File::open("testdata/girs/GMime-3.0.gir").unwrap();
+        let repo = parse_gir(std::io::BufReader::new(f)).unwrap();
+        let mut graph = Graph::default();
+        graph.types(&repo);
+        let part = graph.limit(Some("GMime.Part"), Some(1)).unwrap();
+        // the parent and the direct subclasses, not the grandparent
+        assert!(part.nodes.contains_key("GMime.Object"));
+        assert!(part.nodes.contains_key("GMime.TextPart"));
+        assert!(!part.nodes.contains_key("GObject.Object"));
+        assert!(part.dot().contains("\"GMime.TextPart\" -> \"GMime.Part\";"));
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use anyhow::{Result, Context};
+
+/// Where in a generated file a snippet should be spliced in.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Position {
+    /// Directly after the `@class` annotation of the type
+    Class,
+    /// At the end of the type (or namespace), before it's returned
+    End,
+}
+
+/// Hand-written snippets that are merged into the generated stubs.
+///
+/// The snippets are read from a directory with one subdirectory per namespace:
+///
+/// ```text
+/// <dir>/<Namespace>/<Type>.class.<ext>  spliced in after the @class line of <Type>
+/// <dir>/<Namespace>/<Type>.<ext>        appended to the end of <Type>
+/// <dir>/<Namespace>/init.<ext>          appended to the end of the namespace
+/// ```
+#[derive(Debug, Default)]
+pub struct Augment {
+    snippets: HashMap<(String, String, Position), String>,
+}
+
+impl Augment {
+    pub fn load<P: AsRef<Path>>(dir: P, ext: &str) -> Result<Augment> {
+        let dir = dir.as_ref();
+        let mut snippets = HashMap::new();
+        let namespaces = fs::read_dir(dir)
+            .with_context(|| format!("Couldn't read augment directory {}", dir.display()))?;
+        for ns in namespaces {
+            let ns = ns?;
+            if !ns.file_type()?.is_dir() {
+                continue;
+            }
+            let ns_name = ns.file_name().to_string_lossy().into_owned();
+            for file in fs::read_dir(ns.path())? {
+                let path = file?.path();
+                if path.extension().and_then(|e| e.to_str()) != Some(ext) {
+                    continue;
+                }
+                let stem = match path.file_stem().and_then(|s| s.to_str()) {
+                    Some(stem) => stem,
+                    None => continue,
+                };
+                let (name, pos) = match stem.strip_suffix(".class") {
+                    Some(name) => (name, Position::Class),
+                    None => (stem, Position::End),
+                };
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Couldn't read snippet {}", path.display()))?;
+                snippets.insert((ns_name.clone(), name.to_string(), pos), content);
+            }
+        }
+        Ok(Augment { snippets })
+    }
+
+    /// Get the snippet for `name` in the namespace `ns`, the namespace itself
+    /// is named `init`.
+    pub fn get(&self, ns: &str, name: &str, pos: Position) -> Option<&str> {
+        self.snippets
+            .get(&(ns.to_string(), name.to_string(), pos))
+            .map(|s| s.as_str())
+    }
+}
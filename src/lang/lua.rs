@@ -1,11 +1,14 @@
-use std::{ffi::OsStr, io::BufWriter};
-use std::io::{Write, Read};
-use std::fs;
+use std::io::Write;
+use std::sync::Arc;
 use crate::library::*;
-use crate::parse;
+use crate::resolve::{Resolver, Scope, split_name};
+use crate::sink::Sink;
+use crate::visit::{self, Context, Parent, Visitor};
+use std::collections::{BTreeMap, HashSet};
 use std::path::Path;
 use super::*;
-use anyhow::{Result, Context};
+use super::augment::{Augment, Position};
+use anyhow::{Context as _, Result};
 
 // The amount of code/doc we should generate
 // Generating full docs for a mock file could be
@@ -14,29 +17,47 @@ use anyhow::{Result, Context};
 // struct LuaDoc {}
 pub struct LuaCodegen {
     // level: Level
+    augment: Option<Augment>,
+    resolver: Arc<Resolver>,
 }
 
 impl LuaCodegen {
     pub fn new() -> LuaCodegen {
-        LuaCodegen{}
+        LuaCodegen{
+            augment: None,
+            resolver: Arc::new(Resolver::default()),
+        }
     }
-}
 
-impl Default for LuaCodegen {
-    fn default() -> Self {
-        Self::new()
+    /// Look up types from other namespaces in `resolver`
+    pub fn resolver(mut self, resolver: Arc<Resolver>) -> LuaCodegen {
+        self.resolver = resolver;
+        self
     }
-}
 
-impl LuaCodegen {
-    fn gen<R: Read>(&self, r: R,  dir: &str, p: &Path) -> Result<()> {
-        let repo = parse::parse_gir(r).expect("Couldn't parse gir file");
+    /// Splice the hand-written snippets in `augment` into the generated files
+    pub fn augment(mut self, augment: Augment) -> LuaCodegen {
+        self.augment = Some(augment);
+        self
+    }
 
-        repo.namespace[0].gen(dir, p)?;
+    fn gen_augment<W: Write>(&self, ns: &str, name: &str, pos: Position, w: &mut W) -> Result<()> {
+        if let Some(snippet) = self.augment.as_ref().and_then(|a| a.get(ns, name, pos)) {
+            write!(w, "{}", snippet)?;
+            if !snippet.ends_with('\n') {
+                writeln!(w)?;
+            }
+        }
         Ok(())
     }
 }
 
+impl Default for LuaCodegen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 fn fix_filename(str: &str) -> String {
     str.chars()
     .map(|x| match x { 
@@ -46,238 +67,464 @@ fn fix_filename(str: &str) -> String {
 }
 
 impl Generator for LuaCodegen {
-    fn genfile(&self, filename: &str, output_dir: Option<&str>) -> Result<()> {
-        let path = Path::new(filename);
-        if path.extension() != Some(OsStr::new("gir")) {
-            return Err(anyhow::anyhow!(format!("{} Filetype isn't gir", path.to_string_lossy())))
-        }
-        let file = path.file_stem().ok_or_else(|| 
-            anyhow::anyhow!(format!("Cannot get filename for outputwriter")))?;
-        let file = fix_filename(file.to_str().ok_or_else(||
-            anyhow::anyhow!(format!("Cannot convert filename")))?);
-
-        let output_dir = Path::new(output_dir.unwrap_or("types"));
-
-        let output_dir = output_dir.join(&file).join(&file);
-        if !output_dir.is_dir() {
-            fs::create_dir_all(&output_dir)?;
-        }
-        generate_gobject(&output_dir)?;
-
-        let in_file = open_gir(filename)?;
-        self.gen(in_file, &file, &output_dir)?;
-        Ok(())
+    fn default_dir(&self) -> &'static str {
+        "types"
     }
-}
-
-fn generate_gobject<P: AsRef<Path>>(path: &P) -> Result<()> {
-    // let path = Path::new(path.as_ref()).join("GObject").join("init.lua");
-    let path = Path::new(path.as_ref()).join("init.lua");
-    let mut w = &fs::File::create(path)?;
-
-    writeln!(w, "--- @class GObject.Object")?;
-    writeln!(w, "local Object = {{}}")?;
 
-    Ok(())
-}
-
-macro_rules! section {
-    ( $w:expr, $self:ident, $name:ident, $section:ident ) => {
-        {
-            if !$self.$section.is_empty() {
-                // create_section(&$name, stringify!($section), $w)?;
-                for section in $self.$section.iter() {
-                    section.gen(&$name, $w)?;
-                }
-            }
+    fn generate(&self, repo: &Arc<Repository>, sink: &dyn Sink) -> Result<()> {
+        self.resolver.insert(repo);
+        let ns = repo.namespace.first().context("Repository has no namespace")?;
+        let name = ns.name.as_ref().context("Failed to read name")?;
+        // named like the gir file, Gtk-4.0 is Gtk_4_0
+        let file = match ns.version {
+            Some(ref version) => fix_filename(&format!("{}-{}", name, version)),
+            None => fix_filename(name),
         };
+        let dir = Path::new(&file).join(&file);
+        let scope = self.resolver.scope(repo);
+        let mut stubs = Stubs {
+            cg: self,
+            scope: &scope,
+            sink,
+            module: &file,
+            dir: &dir,
+            init: BTreeMap::new(),
+            typ: BTreeMap::new(),
+        };
+        stubs.visit_namespace(ns, Context::new(name))
     }
 }
 
-fn gen_file(ns: &str, p: &Path) -> Result<BufWriter<File>> {
-    let mut path = p.join(ns);
-    path.set_extension("lua");
-    let mut w = BufWriter::new(fs::File::create(path)?);
+fn gen_file() -> Result<Vec<u8>> {
+    let mut w = vec![];
     writeln!(w, "---@diagnostic disable: unused-local, duplicate-doc-field")?;
     writeln!(w, "---@meta")?;
     writeln!(w, "-- THIS FILE WAS GENERATED BY gir-to-stub! DO NOT MODIFY!\n")?;
     Ok(w)
 }
 
-impl Namespace {
-    pub fn gen(&self, dir: &str, p: &Path) -> Result<()> {
-        let name = self.name.as_ref().context("Failed to read name")?;
-        let mut w = gen_file("init", p)?;
-        writeln!(w, "local {} = {{}}\n", name)?;
+/// The parts of `init.lua`, in the order they are written
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum NsPart {
+    /// The classes of the records with their fields
+    Records,
+    Callbacks,
+    /// The classes of the unions with their fields
+    UnionTypes,
+    Requires,
+    Enums,
+    Bitfields,
+    Functions,
+    Constants,
+    Aliases,
+    /// The constructors and methods of the unions
+    Unions,
+}
 
-        for types in self.record.iter() {
-            types.gen_type(name, &mut w)?;
-        }
-        for types in self.callback.iter() {
-            types.gen_callback_type(name, &mut w)?;
+/// The parts of the file of a class or record, in the order they are written
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TypePart {
+    Class,
+    Signals,
+    Fields,
+    Properties,
+    Table,
+    Functions,
+}
+
+/// Writes the stubs of a namespace: `init.lua` and a file per class and
+/// record. The walk visits the functions of a type before its fields, so
+/// every element is written to the part of the file it belongs in and the
+/// parts are joined when the file is done.
+struct Stubs<'s> {
+    cg: &'s LuaCodegen,
+    scope: &'s Scope,
+    sink: &'s dyn Sink,
+    /// The module the files are required from, like `Gtk_4_0`
+    module: &'s str,
+    dir: &'s Path,
+    init: BTreeMap<NsPart, Vec<u8>>,
+    /// The file of the class or record being visited
+    typ: BTreeMap<TypePart, Vec<u8>>,
+}
+
+impl Stubs<'_> {
+    fn init(&mut self, part: NsPart) -> &mut Vec<u8> {
+        self.init.entry(part).or_default()
+    }
+
+    fn typ(&mut self, part: TypePart) -> &mut Vec<u8> {
+        self.typ.entry(part).or_default()
+    }
+
+    /// Load the file of a type into the namespace, whether it's generated or not
+    fn require(&mut self, name: &str, ns: &str) -> Result<()> {
+        let module = self.module;
+        let w = self.init(NsPart::Requires);
+        writeln!(w, "local _{} = require('{}.{}')", name, module, name)?;
+        writeln!(w, "{}.{} = _{}\n", ns, name, name)?;
+        Ok(())
+    }
+
+    /// Write the file of the type `name`, its parts followed by `end`
+    fn write_type(&mut self, name: &str, end: &[u8]) -> Result<()> {
+        let mut w = gen_file()?;
+        for (_, part) in std::mem::take(&mut self.typ) {
+            w.extend(part);
         }
-        for types in self.unions.iter() {
-            types.gen_type(name, &mut w)?;
+        w.extend(end);
+        self.sink.write(&self.dir.join(format!("{}.lua", name)), &w)
+    }
+}
+
+impl Visitor for Stubs<'_> {
+    fn visit_namespace<'a>(&mut self, ns: &'a Namespace, cx: Context<'a>) -> Result<()> {
+        visit::walk_namespace(self, ns, cx)?;
+        let mut w = gen_file()?;
+        writeln!(w, "local {} = {{}}\n", cx.ns)?;
+        let mut types = std::mem::take(&mut self.init);
+        let rest = types.split_off(&NsPart::Requires);
+        for (_, part) in types {
+            w.extend(part);
         }
         writeln!(w)?;
-        for class in self.classes.iter() {
-            writeln!(w, "local _{} = require('{}.{}')", class.name, dir, class.name)?;
-            writeln!(w, "{}.{} = _{}\n", name, class.name, class.name)?;
-            class.gen(name, p)?;
-        }
-        for record in self.record.iter() {
-            if record.name.ends_with("Class") {
-                continue;
-            }
-            writeln!(w, "local _{} = require('{}.{}')", record.name, dir, record.name)?;
-            writeln!(w, "{}.{} = _{}\n", name, record.name, record.name)?;
-            record.gen(name, p)?;
+        for (_, part) in rest {
+            w.extend(part);
         }
-        // section!(&mut w, self, name, record);
+        self.cg.gen_augment(cx.ns, "init", Position::End, &mut w)?;
+        writeln!(w, "return {}", cx.ns)?;
+        self.sink.write(&self.dir.join("init.lua"), &w)
+    }
 
-        section!(&mut w, self, name, enums);
-        section!(&mut w, self, name, bitfield);
+    fn visit_class<'a>(&mut self, class: &'a Class, cx: Context<'a>) -> Result<()> {
+        self.require(&class.name, cx.ns)?;
+        if !class.info.is_introspectable() {
+            return Ok(())
+        }
+        let (cg, scope, ns) = (self.cg, self.scope, cx.ns);
+        let w = self.typ(TypePart::Class);
+        let callable = gen_construct_params(scope, ns, class, w)?;
+        class.info.gen(w)?;
+        if let Some(ref parent) = class.parent {
+            writeln!(w, "--- @class {}.{} : {}", ns, class.name, translate_ns(parent, ns))?;
+        } else {
+            writeln!(w, "--- @class {}.{}", ns, class.name)?;
+        }
+        cg.gen_augment(ns, &class.name, Position::Class, w)?;
+
+        visit::walk_class(self, class, cx)?;
 
-        for function in self.functions.iter() {
-            function.gen(name, name, &mut w)?;
+        let w = self.typ(TypePart::Table);
+        if callable {
+            gen_default_construtor(ns, class, w)?;
         }
+        writeln!(w, "local {} = {{}}", class.name)?;
+
+        let mut end = vec![];
+        gen_signal_connect(scope, class, ns, &mut end)?;
+        gen_signal_emit(scope, class, ns, &mut end)?;
+        writeln!(end, "--- @param obj GObject.Object")?;
+        writeln!(end, "--- @return boolean")?;
+        writeln!(end, "function {}:is_type_of(obj) end", class.name)?;
+        cg.gen_augment(ns, &class.name, Position::End, &mut end)?;
+        writeln!(end, "return {}", &class.name)?;
+        self.write_type(&class.name, &end)
+    }
 
-        section!(&mut w, self, name, constant);
-        section!(&mut w, self, name, alias);
-        section!(&mut w, self, name, unions);
-        writeln!(&mut w, "return {}", name)?;
-        w.flush()?;
+    fn visit_interface<'a>(&mut self, _interface: &'a Interface, _cx: Context<'a>) -> Result<()> {
         Ok(())
     }
-}
 
-impl Alias {
-    pub fn gen<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
-        writeln!(w, "--- @alias {}.{} {}", ns, &self.name, show_anytyp(&self.typ, ns))?;
-        Ok(())
+    fn visit_record<'a>(&mut self, record: &'a Record, cx: Context<'a>) -> Result<()> {
+        if unsupported(Element::Record(record), cx.parent) {
+            return Ok(())
+        }
+        self.require(&record.name, cx.ns)?;
+        if !record.info.is_introspectable() {
+            return Ok(())
+        }
+        let cg = self.cg;
+        let w = self.init(NsPart::Records);
+        writeln!(w, "--- @class {}.{}", cx.ns, record.name)?;
+        cg.gen_augment(cx.ns, &record.name, Position::Class, w)?;
+
+        visit::walk_record(self, record, cx)?;
+
+        writeln!(self.init(NsPart::Records), "local {} = {{}}", record.name)?;
+        let mut end = vec![];
+        cg.gen_augment(cx.ns, &record.name, Position::End, &mut end)?;
+        self.write_type(&record.name, &end)
     }
-}
 
-fn gen_default_construtor<W:Write>(ns: &str, constrs: &[Function], w: &mut W) -> Result<()> {
-    for constr in constrs {
-        if constr.name == "new" {
-            if let Some(ret) = gen_return_names_typed(constr, ns) {
-                writeln!(w, "--- @overload fun(params: {{}}):{}", ret)?;
+    /// This is like a record (but not), we should generate it the same way
+    fn visit_union<'a>(&mut self, union: &'a Union, cx: Context<'a>) -> Result<()> {
+        if unsupported(Element::Union(union), cx.parent) || !union.info.is_introspectable() {
+            return Ok(())
+        }
+        let name = union.name.as_deref().unwrap_or_default();
+        if cx.parent.is_some() {
+            // the union of a record goes in the file of the record
+            let union_ns = cx.path(name);
+            let w = self.typ(TypePart::Functions);
+            for fun in union.constructor.iter().chain(union.method.iter()) {
+                fun.gen(&union_ns, &cx.qualified(), w)?;
             }
             return Ok(())
         }
+        writeln!(self.init(NsPart::UnionTypes), "--- @class {}.{}", cx.ns, name)?;
+        visit::walk_union(self, union, cx)?;
+        writeln!(self.init(NsPart::UnionTypes), "local {} = {{}}", name)?;
+        Ok(())
     }
-    Ok(())
-}
 
-macro_rules! introspectable {
-    ($id:ident) => {
-        if let Some(false) = $id.info.introspectable {
+    fn visit_enum<'a>(&mut self, e: &'a Enumeration, cx: Context<'a>) -> Result<()> {
+        if !e.info.is_introspectable() {
             return Ok(())
         }
-    };
-}
+        let w = self.init(NsPart::Enums);
+        writeln!(w, "--- @enum {}.{}", cx.ns, e.name)?;
+        writeln!(w, "{}.{} = {{", cx.ns, e.name)?;
+        visit::walk_enum(self, e, cx)?;
+        writeln!(self.init(NsPart::Enums), "}}")?;
+        Ok(())
+    }
 
-impl Class {
-    pub fn gen_type<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
-        introspectable!(self);
-        self.info.gen(w)?;
-        if let Some(ref parent) = self.parent {
-            writeln!(w, "--- @class {}.{} : {}", ns, self.name, translate_ns(parent, ns))?;
-        } else {
-            writeln!(w, "--- @class {}.{}", ns, self.name)?;
+    fn visit_bitfield<'a>(&mut self, b: &'a Bitfield, cx: Context<'a>) -> Result<()> {
+        if !b.info.is_introspectable() {
+            return Ok(())
         }
-        section!(w, self, ns, signals);
-        section!(w, self, ns, fields);
-        section!(w, self, ns, properties);
-        gen_default_construtor(ns, &self.constructor, w)?;
-        writeln!(w, "local {} = {{}}", self.name)?;
+        let w = self.init(NsPart::Bitfields);
+        writeln!(w, "--- @enum {}.{}", cx.ns, b.name)?;
+        writeln!(w, "--- @overload fun({{any}}): {}.{}", cx.ns, b.name)?;
+        writeln!(w, "{}.{} = {{", cx.ns, b.name)?;
+        visit::walk_bitfield(self, b, cx)?;
+        writeln!(self.init(NsPart::Bitfields), "}}")?;
         Ok(())
     }
-    pub fn gen(&self, ns: &str, p: &Path) -> Result<()> {
-        introspectable!(self);
-        let mut w = gen_file(&self.name, p)?;
-
-        self.gen_type(ns, &mut w)?;
 
-        // let class_ns = &self.name;
-
-        // section!(&mut w, self, self.name, implements);
+    fn visit_boxed<'a>(&mut self, _boxed: &'a Boxed, _cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
 
-        for constructor in self.constructor.iter() {
-            constructor.gen(&self.name, ns, &mut w)?;
+    fn visit_function<'a>(&mut self, fun: &'a Function, cx: Context<'a>) -> Result<()> {
+        if unsupported(Element::Function(fun), cx.parent) {
+            return Ok(())
         }
-        for method in self.method.iter() {
-            method.gen(&self.name, ns, &mut w)?;
+        match cx.parent {
+            None if fun.typ == FunctionType::Callback => fun.gen_callback_type(cx.ns, self.init(NsPart::Callbacks)),
+            None => fun.gen(cx.ns, cx.ns, self.init(NsPart::Functions)),
+            Some(Parent::Class(class)) => {
+                let w = self.typ(TypePart::Functions);
+                if fun.typ == FunctionType::Method {
+                    gen_accessor_doc(fun, &class.properties, w)?;
+                }
+                fun.gen(&class.name, cx.ns, w)
+            }
+            Some(Parent::Record(_)) => fun.gen(&cx.qualified(), cx.ns, self.typ(TypePart::Functions)),
+            // only what makes and uses an instance
+            Some(Parent::Union(_)) => match fun.typ {
+                FunctionType::Constructor | FunctionType::Method =>
+                    fun.gen(&cx.qualified(), cx.ns, self.init(NsPart::Unions)),
+                _ => Ok(()),
+            },
+            Some(Parent::Enum(_)) => fun.gen("", cx.ns, self.init(NsPart::Enums)),
+            Some(Parent::Bitfield(_)) => fun.gen("", cx.ns, self.init(NsPart::Bitfields)),
+            Some(Parent::Interface(_) | Parent::Boxed(_)) => Ok(()),
         }
-        for func in self.functions.iter() {
-            func.gen(&self.name, ns, &mut w)?;
+    }
+
+    fn visit_signal<'a>(&mut self, signal: &'a Signal, cx: Context<'a>) -> Result<()> {
+        match cx.parent {
+            Some(Parent::Class(_)) => signal.gen(cx.ns, self.typ(TypePart::Signals)),
+            _ => Ok(()),
         }
-        // TODO: Should we re-add this in some way?
+    }
 
-        // if !self.virtual_method.is_empty() {
-        //     for virt in self.virtual_method.iter() {
-        //         virt.gen_method(&self.name, ns, &mut w)?;
-        //     }
-        // }
-        for callback in self.callbacks.iter() {
-            callback.gen(&self.name, ns, &mut w)?;
+    fn visit_property<'a>(&mut self, prop: &'a Property, cx: Context<'a>) -> Result<()> {
+        match cx.parent {
+            Some(Parent::Class(_)) => prop.gen(cx.ns, self.typ(TypePart::Properties)),
+            _ => Ok(()),
         }
+    }
 
-        // section!(&mut w, self, self.name, record);
-        // section!(&mut w, self, self.name, unions);
-        // section!(&mut w, self, self.name, constant);
+    fn visit_field<'a>(&mut self, field: &'a Field, cx: Context<'a>) -> Result<()> {
+        match cx.parent {
+            Some(Parent::Class(_)) => field.gen(cx.ns, self.typ(TypePart::Fields)),
+            Some(Parent::Record(_)) => field.gen(cx.ns, self.init(NsPart::Records)),
+            Some(Parent::Union(_)) => field.gen(cx.ns, self.init(NsPart::UnionTypes)),
+            _ => Ok(()),
+        }
+    }
 
-        writeln!(w, "--- @param obj GObject.Object")?;
-        writeln!(w, "--- @return boolean")?;
-        writeln!(w, "function {}:is_type_of(obj) end", self.name)?;
+    fn visit_member<'a>(&mut self, member: &'a Member, cx: Context<'a>) -> Result<()> {
+        match cx.parent {
+            Some(Parent::Enum(_)) => member.gen(self.init(NsPart::Enums)),
+            Some(Parent::Bitfield(_)) => member.gen(self.init(NsPart::Bitfields)),
+            _ => Ok(()),
+        }
+    }
 
-        writeln!(w, "return {}", &self.name)?;
-        w.flush()?;
+    fn visit_constant<'a>(&mut self, constant: &'a Constant, cx: Context<'a>) -> Result<()> {
+        if unsupported(Element::Constant, cx.parent) {
+            return Ok(())
+        }
+        constant.gen(cx.ns, self.init(NsPart::Constants))
+    }
 
+    fn visit_alias<'a>(&mut self, alias: &'a Alias, cx: Context<'a>) -> Result<()> {
+        alias.gen(cx.ns, self.init(NsPart::Aliases))
+    }
+}
+impl Alias {
+    pub fn gen<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
+        writeln!(w, "--- @alias {}.{} {}", ns, &self.name, show_anytyp(&self.typ, ns))?;
         Ok(())
     }
 }
 
-//
-// impl Implement {
-//     pub fn gen<W: Write>(&self, _ns: &str, w: &mut W) -> Result<()> {
-//         Ok(writeln!(w, "-- implements: {}", self.name)?)
-//     }
-// }
+fn gen_default_construtor<W:Write>(ns: &str, class: &Class, w: &mut W) -> Result<()> {
+    for constr in class.constructor.iter() {
+        if constr.name == "new" {
+            if let Some(ret) = gen_return_names_typed(constr, ns) {
+                writeln!(w, "--- @overload fun(params: {}.{}Params):{}", ns, class.name, ret)?;
+            }
+            return Ok(())
+        }
+    }
+    Ok(())
+}
 
-impl Union {
-    pub fn gen_type<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
-        introspectable!(self);
+/// The table a class can be constructed with, containing every property
+/// that can be set at construction, including those of its ancestors and
+/// implemented interfaces. Only classes with a `new` constructor and
+/// properties to set get one, returns whether it was written.
+fn gen_construct_params<W:Write>(scope: &Scope, ns: &str, class: &Class, w: &mut W) -> Result<bool> {
+    let new = class.constructor.iter().find(|c| c.name == "new");
+    if new.and_then(|c| gen_return_names_typed(c, ns)).is_none() {
+        return Ok(false)
+    }
+    let params = collect_construct_params(scope, &class.name, ns);
+    if params.is_empty() {
+        return Ok(false)
+    }
+    writeln!(w, "--- @class {}.{}Params", ns, class.name)?;
+    for (name, typ) in params.iter() {
+        writeln!(w, "--- @field {}? {}", name, typ)?;
+    }
+    writeln!(w)?;
+    Ok(true)
+}
 
-        if let Some(ref name) = self.name {
-            writeln!(w, "--- @class {}.{}", ns, name)?;
-            section!(w, self, ns, fields);
-            writeln!(w, "local {} = {{}}", name)?;
-        }
-        Ok(())
+/// Walks the inheritance of `typ`, across namespaces, and calls `visit`
+/// with the namespace, the properties and the signals of the type, of the
+/// interfaces it implements and of its ancestors. Subtypes come first, so
+/// what they declare can shadow what they inherit.
+fn walk_hierarchy<F>(scope: &Scope, typ: &str, ns: &str, seen: &mut HashSet<String>, visit: &mut F)
+where
+    F: FnMut(&str, &[Property], &[Signal]),
+{
+    let (ns, name) = split_name(typ, ns);
+    if !seen.insert(format!("{}.{}", ns, name)) {
+        return
     }
-    /// this is like a record (but not), we should generate it the same way
-    pub fn gen<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
-        introspectable!(self);
-        if let Some(false) = self.info.introspectable {
-            return Ok(())
+    let repo = match scope.namespace(ns) {
+        Some(repo) => repo,
+        None => return,
+    };
+    let namespace = match repo.get_namespace(ns) {
+        Some(namespace) => namespace,
+        None => return,
+    };
+    if let Some(class) = namespace.get_class(name) {
+        visit(ns, &class.properties, &class.signals);
+        for implement in class.implements.iter() {
+            walk_hierarchy(scope, &implement.name, ns, seen, visit);
+        }
+        if let Some(ref parent) = class.parent {
+            walk_hierarchy(scope, parent, ns, seen, visit);
         }
-        if let Some(ref name) = self.name {
-            let union_ns = format!("{}.{}", ns, name);
+    } else if let Some(interface) = namespace.get_interface(name) {
+        visit(ns, &interface.properties, &interface.signals);
+        for prereq in interface.prerequisites.iter() {
+            walk_hierarchy(scope, prereq, ns, seen, visit);
+        }
+    }
+}
 
-            for constructor in self.constructor.iter() {
-                constructor.gen(&union_ns, ns, w)?;
-            }
-            for method in self.method.iter() {
-                method.gen(&union_ns, ns, w)?;
+/// The properties of `class` and everything it inherits that can be set
+/// at construction, with their types
+fn collect_construct_params(scope: &Scope, class: &str, ns: &str) -> Vec<(String, String)> {
+    let mut params: Vec<(String, String)> = vec![];
+    walk_hierarchy(scope, class, ns, &mut HashSet::new(), &mut |ns, props, _| {
+        for prop in props.iter() {
+            if let Some(typ) = prop.param_type(ns) {
+                let name = prop.name.replace('-', "_");
+                if !params.iter().any(|(n, _)| *n == name) {
+                    params.push((name, typ));
+                }
             }
         }
+    });
+    params
+}
 
-        Ok(())
+/// Mark methods that are the getter or setter of a property
+fn gen_accessor_doc<W: Write>(method: &Function, props: &[Property], w: &mut W) -> Result<()> {
+    if let Some(false) = method.info.introspectable {
+        return Ok(())
     }
+    for prop in props.iter() {
+        if prop.getter.as_deref() == Some(&method.name) {
+            writeln!(w, "--- Getter for the `{}` property.", prop.name)?;
+        }
+        if prop.setter.as_deref() == Some(&method.name) {
+            writeln!(w, "--- Setter for the `{}` property.", prop.name)?;
+        }
+    }
+    Ok(())
+}
+
+/// An element the stubs may leave out, see [`unsupported`]
+pub(crate) enum Element<'a> {
+    Interface,
+    Boxed,
+    Macro,
+    Record(&'a Record),
+    Union(&'a Union),
+    Function(&'a Function),
+    Constant,
 }
 
+/// Whether the stubs leave out an introspectable element declared in
+/// `parent`, because there is no lua for its kind or its place. The
+/// coverage report counts these as unsupported.
+pub(crate) fn unsupported(element: Element, parent: Option<Parent>) -> bool {
+    match element {
+        Element::Interface | Element::Boxed | Element::Macro => true,
+        // only the records of the namespace and not the class structs,
+        // like GtkWidgetClass
+        Element::Record(record) => parent.is_some() || record.name.ends_with("Class"),
+        Element::Union(union) => union.name.is_none() || matches!(parent, Some(Parent::Class(_))),
+        Element::Function(fun) => fun.typ == FunctionType::Virtual,
+        Element::Constant => parent.is_some(),
+    }
+}
+
+macro_rules! introspectable {
+    ($id:ident) => {
+        if let Some(false) = $id.info.introspectable {
+            return Ok(())
+        }
+    };
+}
+
+//
+// impl Implement {
+//     pub fn gen<W: Write>(&self, _ns: &str, w: &mut W) -> Result<()> {
+//         Ok(writeln!(w, "-- implements: {}", self.name)?)
+//     }
+// }
+
 impl Field {
     pub fn gen<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
         if !self.private {
@@ -289,9 +536,42 @@ impl Field {
 }
 
 impl Property {
+    /// LuaLS has no readonly fields, so the access is noted in the
+    /// description. Construct-only properties can only be set in the
+    /// table passed to the class, like in the Params class.
     pub fn gen<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
+        introspectable!(self);
         let typ = show_anytyp(&self.typ, ns);
-        Ok(writeln!(w, "--- @field {} {}", self.name.replace('-', "_"), typ)?)
+        let mut notes = vec![];
+        if self.construct_only {
+            notes.push("construct-only".to_string());
+        } else if !self.writable {
+            notes.push("read-only".to_string());
+        } else if !self.readable {
+            notes.push("write-only".to_string());
+        }
+        if let Some(ref getter) = self.getter {
+            notes.push(format!("getter: {}", getter));
+        }
+        if let Some(ref setter) = self.setter {
+            notes.push(format!("setter: {}", setter));
+        }
+        let name = self.name.replace('-', "_");
+        if notes.is_empty() {
+            Ok(writeln!(w, "--- @field {} {}", name, typ)?)
+        } else {
+            Ok(writeln!(w, "--- @field {} {} ({})", name, typ, notes.join(", "))?)
+        }
+    }
+    /// The type of the property if it can be set at construction
+    fn param_type(&self, ns: &str) -> Option<String> {
+        if let Some(false) = self.info.introspectable {
+            return None
+        }
+        if !self.writable && !self.construct && !self.construct_only {
+            return None
+        }
+        Some(show_anytyp(&self.typ, ns))
     }
 }
 
@@ -302,18 +582,18 @@ fn gen_return_signal(fun: &Signal, ns: &str) -> Option<String> {
         params.push(p)
     }
 
-    for p in fun.parameters.iter().filter(|p| out_param(&p.direction)) {
+    for p in lua_params(&fun.parameters, fun.ret.as_ref()).into_iter().filter(|p| out_param(&p.direction)) {
         params.push(p)
     }
 
-    if params.is_empty() {
-        return None
-    }
-
     let param_names: Vec<String> = params
         .iter()
         .map(|p| show_anytyp(&p.typ, ns))
+        .filter(|t| t != "nil")
         .collect();
+    if param_names.is_empty() {
+        return None
+    }
     Some(param_names.join(", "))
 }
 
@@ -321,10 +601,98 @@ fn signal_name(str: &str) -> String {
     format!("on_{}", str.replace('-', "_"))
 }
 
+/// Generate a `connect` method with an overload per signal of the class and
+/// of what it inherits, taking a handler with the signature of the signal.
+/// `notify` gets an overload for every property, other detailed signals one
+/// taking any detail.
+fn gen_signal_connect<W: Write>(scope: &Scope, class: &Class, ns: &str, w: &mut W) -> Result<()> {
+    let this = format!("{}.{}", ns, class.name);
+    let mut signals: Vec<(String, bool, String)> = vec![];
+    let mut props: Vec<String> = vec![];
+    walk_hierarchy(scope, &class.name, ns, &mut HashSet::new(), &mut |ns, properties, sigs| {
+        for signal in sigs.iter().filter(|s| s.info.is_introspectable()) {
+            if !signals.iter().any(|(name, _, _)| *name == signal.name) {
+                signals.push((signal.name.clone(), signal.detailed == Some(true), signal.handler(&this, ns)));
+            }
+        }
+        for prop in properties.iter().filter(|p| p.info.is_introspectable()) {
+            if !props.contains(&prop.name) {
+                props.push(prop.name.clone());
+            }
+        }
+    });
+    if signals.is_empty() && props.is_empty() {
+        return Ok(())
+    }
+    for (name, detailed, handler) in signals.iter() {
+        writeln!(w, "--- @overload fun(self: {}, signal: \"{}\", handler: {}): number",
+            this, name, handler)?;
+        if *detailed && name != "notify" {
+            writeln!(w, "--- @overload fun(self: {}, signal: string, handler: {}): number",
+                this, handler)?;
+        }
+    }
+    for prop in props.iter() {
+        writeln!(w, "--- @overload fun(self: {}, signal: \"notify::{}\", handler: fun(self: {}, pspec: GObject.ParamSpec)): number",
+            this, prop, this)?;
+    }
+    writeln!(w, "--- @param signal string")?;
+    writeln!(w, "--- @param handler function")?;
+    writeln!(w, "--- @return number")?;
+    writeln!(w, "function {}:connect(signal, handler) end\n", class.name)?;
+    Ok(())
+}
+
+/// Generate an `emit` method with an overload per action signal of the
+/// class and of what it inherits.
+fn gen_signal_emit<W: Write>(scope: &Scope, class: &Class, ns: &str, w: &mut W) -> Result<()> {
+    let this = format!("{}.{}", ns, class.name);
+    let mut seen: Vec<String> = vec![];
+    let mut actions: Vec<String> = vec![];
+    walk_hierarchy(scope, &class.name, ns, &mut HashSet::new(), &mut |ns, _, sigs| {
+        for signal in sigs.iter().filter(|s| s.info.is_introspectable()) {
+            if seen.contains(&signal.name) {
+                continue
+            }
+            seen.push(signal.name.clone());
+            if signal.action != Some(true) {
+                continue
+            }
+            let mut params = gen_param_names_typed(&lua_params(&signal.parameters, signal.ret.as_ref()), ns);
+            params.insert(0, format!("signal: \"{}\"", signal.name));
+            params.insert(0, format!("self: {}", this));
+            match gen_return_signal(signal, ns) {
+                Some(ret) => actions.push(format!("fun({}): {}", params.join(", "), ret)),
+                None => actions.push(format!("fun({})", params.join(", "))),
+            }
+        }
+    });
+    if actions.is_empty() {
+        return Ok(())
+    }
+    for action in actions.iter() {
+        writeln!(w, "--- @overload {}", action)?;
+    }
+    writeln!(w, "--- @param signal string")?;
+    writeln!(w, "function {}:emit(signal, ...) end\n", class.name)?;
+    Ok(())
+}
+
 impl Signal {
+    /// The type of a function handling this signal for an object of type `this`
+    pub(crate) fn handler(&self, this: &str, ns: &str) -> String {
+        let mut param_names = gen_param_names_typed(&lua_params(&self.parameters, self.ret.as_ref()), ns);
+        param_names.insert(0, format!("self: {}", this));
+        let param_names = param_names.join(", ");
+        if let Some(ret) = gen_return_signal(self, ns) {
+            format!("fun({}): {}", param_names, ret)
+        } else {
+            format!("fun({})", param_names)
+        }
+    }
     pub fn gen<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
         introspectable!(self);
-        let mut param_names = gen_param_names_typed(&self.parameters, ns);
+        let mut param_names = gen_param_names_typed(&lua_params(&self.parameters, self.ret.as_ref()), ns);
         param_names.insert(0, "self".to_string());
         let param_names = param_names.join(", ");
         let name = signal_name(&self.name);
@@ -337,7 +705,7 @@ impl Signal {
     }
 }
 
-const KEYWORDS: &'static [&'static str] = &[
+const KEYWORDS: &[&str] = &[
     "and",       "break",     "do",        "else",      "elseif",
     "end",       "false",     "for",       "function",  "if",
     "in",        "local",     "nil",       "not",       "or",
@@ -353,10 +721,13 @@ pub fn unkeyword(param_name: &str) -> String {
     param_name.to_owned()
 }
 
-fn show_anytyp(typ: &AnyType, ns: &str) -> String {
+pub(crate) fn show_anytyp(typ: &AnyType, ns: &str) -> String {
     match typ {
         AnyType::Array(array) => {
-            let typ = array.typ.clone();
+            let typ = match *array.typ {
+                AnyType::Type(ref t) => t.name.clone().unwrap_or_default(),
+                ref elem => return format!("{}[]", show_anytyp(elem, ns)),
+            };
             if let Some(name) = &array.name {
                 if name == "GLib.ByteArray" && typ == "guint8" {
                     "string".to_string()
@@ -369,7 +740,6 @@ fn show_anytyp(typ: &AnyType, ns: &str) -> String {
                 if typ == "guint8" {
                     return "string".to_string()
                 }
-                let typ = array.typ.clone();
                 let mut array = translate(&typ, ns);
                 array.push_str("[]");
                 array
@@ -391,12 +761,13 @@ fn show_anytyp(typ: &AnyType, ns: &str) -> String {
                 "any".to_string()
             }
         }
+        AnyType::Callback(fun) => callback_type(fun, ns),
         AnyType::VarArg => "...".to_owned(),
     }
 }
 
 // todo we should just remove const etc from the type
-fn translate(name: &str, ns: &str) -> String {
+pub(crate) fn translate(name: &str, ns: &str) -> String {
     match name {
         "gboolean" => "boolean".to_string(),
         "gpointer" => "any".to_string(),
@@ -423,7 +794,7 @@ fn translate(name: &str, ns: &str) -> String {
     }
 }
 
-fn translate_ns(name: &str, ns: &str) -> String {
+pub(crate) fn translate_ns(name: &str, ns: &str) -> String {
     if !name.contains('.') {
         return format!("{}.{}", ns, name)
     }
@@ -482,25 +853,22 @@ impl Parameter {
     }
 }
 
-fn gen_doc_params<W: Write>(params: &[Parameter], ns: &str, skip: bool, w: &mut W) -> Result<()> {
-    let mut num = 0;
-    if skip {
-        num = 1;
-    }
-    for param in params.iter().skip(num).filter(|p| in_param(&p.direction)) {
+fn gen_doc_params<W: Write>(params: &[&Parameter], ns: &str, w: &mut W) -> Result<()> {
+    for param in params.iter().filter(|p| in_param(&p.direction)) {
         param.gen(ns,w)?;
     }
     Ok(())
 }
 
-fn gen_doc_return<W: Write>(fun: &Function, ns: &str, w: &mut W) -> Result<Option<String>> {
+/// The types returned by `fun`, the return value followed by the out parameters
+pub(crate) fn return_types(fun: &Function, ns: &str) -> Option<String> {
     let mut params = vec![];
 
     if let Some(ref p) = fun.ret {
         params.push(p)
     }
 
-    for p in fun.parameters.iter().filter(|p| out_param(&p.direction)) {
+    for p in lua_params(&fun.parameters, fun.ret.as_ref()).into_iter().filter(|p| out_param(&p.direction)) {
         params.push(p)
     }
     if !params.is_empty() {
@@ -516,42 +884,84 @@ fn gen_doc_return<W: Write>(fun: &Function, ns: &str, w: &mut W) -> Result<Optio
         }
         if !rets.is_empty() {
             let retlist = rets.join(", ");
-            return Ok(Some(retlist));
-            // writeln!(w, "--- @return {}", retlist)?;
+            return Some(retlist);
         }
     }
-    Ok(None)
+    None
+}
+
+/// The function type of a callback, like `fun(widget: Gtk.Widget):boolean`
+pub(crate) fn callback_type(fun: &Function, ns: &str) -> String {
+    let param_names = gen_param_names_typed(&lua_params(&fun.parameters, fun.ret.as_ref()), ns).join(", ");
+    if let Some(ret) = gen_return_names_typed(fun, ns) {
+        format!("fun({}):{}", param_names, ret)
+    } else {
+        format!("fun({})", param_names)
+    }
+}
+
+/// A lua signature for `fun`, like `Gtk.Box:append(child: Gtk.Widget)`
+pub(crate) fn signature(fun: &Function, static_ns: &str, ns: &str) -> String {
+    let params: Vec<String> = lua_params(&fun.parameters, fun.ret.as_ref())
+        .into_iter()
+        .filter(|p| in_param(&p.direction))
+        .map(|p| format!("{}: {}{}", unkeyword(&p.name), show_anytyp(&p.typ, ns), optional(p)))
+        .collect();
+    let sep = if fun.typ == FunctionType::Method { ":" } else { "." };
+    let mut sig = format!("{}{}{}({})", static_ns, sep, fun.name, params.join(", "));
+    if let Some(ret) = return_types(fun, ns) {
+        sig.push_str(": ");
+        sig.push_str(&ret);
+    }
+    sig
 }
 
-fn in_param(direction: &Option<ParameterDirection>) -> bool {
+/// The parameters as seen from lua: the instance parameter is `self` and
+/// the lengths of arrays are implied by the arrays, so neither is passed.
+pub(crate) fn lua_params<'a>(params: &'a [Parameter], ret: Option<&Parameter>) -> Vec<&'a Parameter> {
+    // array lengths are indexes into the parameters without the instance
+    let params: Vec<&Parameter> = params.iter().filter(|p| !p.instance).collect();
+    let lengths: Vec<usize> = params
+        .iter()
+        .copied()
+        .chain(ret)
+        .filter_map(|p| match p.typ {
+            AnyType::Array(ref array) => array.length,
+            _ => None,
+        })
+        .collect();
+    params
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !lengths.contains(i))
+        .map(|(_, p)| p)
+        .collect()
+}
+
+pub(crate) fn in_param(direction: &Option<ParameterDirection>) -> bool {
     if let Some(direct) = direction {
         return matches!(direct, ParameterDirection::In | ParameterDirection::InOut);
     }
     true
 }
 
-fn out_param(direction: &Option<ParameterDirection>) -> bool {
+pub(crate) fn out_param(direction: &Option<ParameterDirection>) -> bool {
     if let Some(direct) = direction {
         return matches!(direct, ParameterDirection::Out | ParameterDirection::InOut);
     }
     false
 }
 
-fn gen_param_names(params: &[Parameter], skip: bool) -> String {
-    let mut num = 0;
-    if skip {
-        num = 1;
-    }
+fn gen_param_names(params: &[&Parameter]) -> String {
     let param_names: Vec<String> = params
         .iter()
-        .skip(num)
         .filter(|p| in_param(&p.direction))
         .map(|p| unkeyword(&p.name))
         .collect();
     param_names.join(", ")
 }
 
-fn gen_param_names_typed(params: &[Parameter], ns: &str) -> Vec<String> {
+fn gen_param_names_typed(params: &[&Parameter], ns: &str) -> Vec<String> {
     params
         .iter()
         .filter(|p| in_param(&p.direction))
@@ -566,7 +976,7 @@ fn gen_return_names_typed(fun: &Function, ns: &str) -> Option<String> {
         params.push(p)
     }
 
-    for p in fun.parameters.iter().filter(|p| out_param(&p.direction)) {
+    for p in lua_params(&fun.parameters, fun.ret.as_ref()).into_iter().filter(|p| out_param(&p.direction)) {
         params.push(p)
     }
 
@@ -586,10 +996,10 @@ impl Function {
         introspectable!(self);
         self.info.gen(w)?;
         self.doc.gen(w)?;
-        let skip = self.typ == FunctionType::Method;
-        gen_doc_params(&self.parameters, root_ns, skip, w)?;
-        let ret = gen_doc_return(self, root_ns,  w)?;
-        let param_names = gen_param_names(&self.parameters, skip);
+        let params = lua_params(&self.parameters, self.ret.as_ref());
+        gen_doc_params(&params, root_ns, w)?;
+        let ret = return_types(self, root_ns);
+        let param_names = gen_param_names(&params);
         match self.typ {
             FunctionType::Callback => panic!("Use gen_callback for callbacks!"),
             FunctionType::Method => {
@@ -621,12 +1031,7 @@ impl Function {
     }
     pub fn gen_callback_type<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
         introspectable!(self);
-        let param_names = gen_param_names_typed(&self.parameters, ns).join(", ");
-        if let Some(ret) = gen_return_names_typed(self, ns) {
-            writeln!(w, "--- @alias {}.{} fun({}):{}", ns, self.name, param_names, ret)?;
-        } else {
-            writeln!(w, "--- @alias {}.{} fun({})", ns, self.name, param_names)?;
-        }
+        writeln!(w, "--- @alias {}.{} {}", ns, self.name, callback_type(self, ns))?;
         Ok(())
     }
 }
@@ -638,50 +1043,6 @@ impl Member {
     }
 }
 
-impl Record {
-    pub fn gen_type<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
-        introspectable!(self);
-        // Skip these
-        if self.name.ends_with("Class") {
-            return Ok(())
-        }
-
-        if let Some(false) = self.info.introspectable {
-            return Ok(())
-        }
-
-        writeln!(w, "--- @class {}.{}", ns, self.name)?;
-        section!(w, self, ns, fields);
-        Ok(writeln!(w, "local {} = {{}}", self.name)?)
-    }
-    pub fn gen(&self, ns: &str, p: &Path) -> Result<()> {
-    // pub fn gen<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
-        introspectable!(self);
-        if let Some(false) = self.info.introspectable {
-            return Ok(())
-        }
-
-        let mut w = gen_file(&self.name, p)?;
-
-        let record_ns = format!("{}.{}", ns, self.name);
-
-        for constructor in self.constructor.iter() {
-            constructor.gen(&record_ns, ns, &mut w)?;
-        }
-        for method in self.method.iter() {
-            method.gen(&record_ns, ns, &mut w)?;
-        }
-        for func in self.functions.iter() {
-            func.gen(&record_ns, ns, &mut w)?;
-        }
-
-        for unio in self.unions.iter() {
-            unio.gen(&record_ns, &mut w)?;
-        }
-        Ok(())
-    }
-}
-
 impl Constant {
     pub fn gen<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
         introspectable!(self);
@@ -693,38 +1054,6 @@ impl Constant {
     }
 }
 
-impl Enumeration {
-    pub fn gen<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
-        introspectable!(self);
-        writeln!(w, "--- @enum {}.{}", &ns, self.name)?;
-        writeln!(w, "{}.{} = {{", &ns, self.name)?;
-        for mem in self.members.iter() {
-            mem.gen(w)?;
-        }
-        for func in self.functions.iter() {
-            func.gen("", ns, w)?;
-        }
-        Ok(writeln!(w, "}}")?)
-    }
-}
-
-impl Bitfield {
-    pub fn gen<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
-        introspectable!(self);
-        writeln!(w, "--- @enum {}.{}", &ns, self.name)?;
-        writeln!(w, "--- @overload fun({{any}}): {}.{}", &ns, self.name)?;
-        writeln!(w, "{}.{} = {{", &ns, self.name)?;
-        for mem in self.members.iter() {
-            mem.gen(w)?;
-        }
-        for func in self.functions.iter() {
-            func.gen("", ns, w)?;
-        }
-        writeln!(w, "}}", )?;
-        Ok(())
-    }
-}
-
 // #[cfg(test)]
 // mod test {
 //
@@ -953,3 +1282,218 @@ impl Bitfield {
 //     pub fn test_interfaces() {
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use anyhow::{bail, Result};
+    use simple_xml_builder::XMLElement;
+    use super::LuaCodegen;
+    use crate::lang::Generator;
+    use crate::lang::augment::Augment;
+    use crate::parse::parse_gir;
+    use crate::resolve::Resolver;
+    use crate::sink::{MemorySink, Sink};
+
+    /// Generate the stubs of a Test-1.0 namespace with `children`, the
+    /// files are relative to Test_1_0/Test_1_0. `includes` are looked up
+    /// in testdata/girs.
+    fn generate(cg: LuaCodegen, includes: &[(&str, &str)], children: Vec<XMLElement>) -> BTreeMap<PathBuf, String> {
+        let mut repo = XMLElement::new("repository");
+        repo.add_attribute("version", "1.2");
+        repo.add_attribute("xmlns", "http://www.gtk.org/introspection/core/1.0");
+        repo.add_attribute("xmlns:c", "http://www.gtk.org/introspection/c/1.0");
+        repo.add_attribute("xmlns:glib", "http://www.gtk.org/introspection/glib/1.0");
+        for (name, version) in includes {
+            repo.add_child(element("include", &[("name", name), ("version", version)]));
+        }
+        let mut ns = XMLElement::new("namespace");
+        ns.add_attribute("name", "Test");
+        ns.add_attribute("version", "1.0");
+        for child in children {
+            ns.add_child(child);
+        }
+        repo.add_child(ns);
+        let mut gir = vec![];
+        repo.write(&mut gir).unwrap();
+
+        let repo = Arc::new(parse_gir(gir.as_slice()).unwrap());
+        let sink = MemorySink::new();
+        let resolver = Arc::new(Resolver::new(vec![PathBuf::from("testdata/girs")]));
+        resolver.load_includes(&repo);
+        cg.resolver(resolver).generate(&repo, &sink).unwrap();
+        sink.into_files().into_iter()
+            .map(|(path, data)| {
+                let path = path.strip_prefix("Test_1_0/Test_1_0").unwrap().to_path_buf();
+                (path, String::from_utf8(data).unwrap())
+            })
+            .collect()
+    }
+
+    fn snapshot(name: &str, contents: &str) {
+        let mut settings = insta::Settings::clone_current();
+        settings.set_snapshot_path("../../testdata/lua/output/");
+        settings.set_prepend_module_to_snapshot(false);
+        settings.bind(|| insta::assert_snapshot!(name, contents));
+    }
+
+    fn element(name: &str, attributes: &[(&str, &str)]) -> XMLElement {
+        let mut element = XMLElement::new(name);
+        for (key, value) in attributes {
+            element.add_attribute(key, value);
+        }
+        element
+    }
+
+    #[test]
+    fn enum_keys_works() {
+        let mut enumeration = element("enumeration", &[("name", "Mode"), ("c:type", "TestMode")]);
+        enumeration.add_child(element("member", &[("name", "read_only"), ("value", "0")]));
+        enumeration.add_child(element("member", &[("name", "write"), ("value", "1")]));
+        let mut bitfield = element("bitfield", &[("name", "Flags"), ("c:type", "TestFlags")]);
+        bitfield.add_child(element("member", &[("name", "none"), ("value", "0")]));
+        bitfield.add_child(element("member", &[("name", "is_set"), ("value", "2")]));
+
+        let files = generate(LuaCodegen::new(), &[], vec![enumeration, bitfield]);
+        snapshot("enum_keys", &files[&PathBuf::from("init.lua")]);
+    }
+
+    fn signal(name: &str, attributes: &[(&str, &str)], params: &[(&str, &str)]) -> XMLElement {
+        let mut signal = element("glib:signal", &[("name", name)]);
+        for (key, value) in attributes {
+            signal.add_attribute(key, value);
+        }
+        let mut ret = element("return-value", &[("transfer-ownership", "none")]);
+        ret.add_child(element("type", &[("name", "none")]));
+        signal.add_child(ret);
+        let mut parameters = element("parameters", &[]);
+        for (name, typ) in params {
+            let mut param = element("parameter", &[("name", name)]);
+            param.add_child(element("type", &[("name", typ)]));
+            parameters.add_child(param);
+        }
+        signal.add_child(parameters);
+        signal
+    }
+
+    fn property(name: &str, attributes: &[(&str, &str)]) -> XMLElement {
+        let mut property = element("property", &[("name", name)]);
+        for (key, value) in attributes {
+            property.add_attribute(key, value);
+        }
+        property.add_child(element("type", &[("name", "utf8")]));
+        property
+    }
+
+    /// A `method` or `constructor` without parameters
+    fn function(kind: &str, name: &str, ret: &str) -> XMLElement {
+        let mut function = element(kind, &[("name", name), ("c:identifier", &format!("test_{}", name))]);
+        let mut value = element("return-value", &[("transfer-ownership", "none")]);
+        value.add_child(element("type", &[("name", ret)]));
+        function.add_child(value);
+        function
+    }
+
+    #[test]
+    fn signals_works() {
+        // connect and emit include what is inherited from other namespaces,
+        // with the types as seen from there
+        let mut checkable = element("interface", &[("name", "Checkable"), ("glib:type-name", "TestCheckable"), ("glib:get-type", "test_checkable_get_type")]);
+        checkable.add_child(property("checked", &[("writable", "1")]));
+        checkable.add_child(signal("toggled", &[("detailed", "1"), ("action", "1")], &[("value", "Mode")]));
+        let mut mode = element("enumeration", &[("name", "Mode"), ("c:type", "TestMode")]);
+        mode.add_child(element("member", &[("name", "on"), ("value", "0")]));
+        let mut toggle = element("class", &[("name", "Toggle"), ("parent", "Toolkit.Button"), ("glib:type-name", "TestToggle"), ("glib:get-type", "test_toggle_get_type")]);
+        toggle.add_child(element("implements", &[("name", "Checkable")]));
+        toggle.add_child(property("label", &[("writable", "1")]));
+        toggle.add_child(signal("clicked", &[("action", "1")], &[]));
+
+        let files = generate(LuaCodegen::new(), &[("Toolkit", "4.0")], vec![mode, checkable, toggle]);
+        let toggle = &files[&PathBuf::from("Toggle.lua")];
+        let start = toggle.find("--- @overload fun(self: Test.Toggle").unwrap();
+        let end = toggle.find("function Toggle:emit").unwrap();
+        snapshot("signals", &toggle[start..end]);
+    }
+
+    #[test]
+    fn augment_works() {
+        let dir = std::env::temp_dir().join(format!("gir-to-stub-augment-{}", std::process::id()));
+        fs::create_dir_all(dir.join("Test")).unwrap();
+        fs::write(dir.join("Test/Box.class.lua"), "--- @field children Test.Box[]").unwrap();
+        fs::write(dir.join("Test/Box.lua"), "function Box:append(child) end\n").unwrap();
+        fs::write(dir.join("Test/init.lua"), "Test.VERSION = 1\n").unwrap();
+        let augment = Augment::load(&dir, "lua").unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut class = element("class", &[("name", "Box"), ("glib:type-name", "TestBox"), ("glib:get-type", "test_box_get_type")]);
+        class.add_child(property("spacing", &[("writable", "1")]));
+        let files = generate(LuaCodegen::new().augment(augment), &[], vec![class]);
+        snapshot("augment_class", &files[&PathBuf::from("Box.lua")]);
+        snapshot("augment_init", &files[&PathBuf::from("init.lua")]);
+    }
+
+    #[test]
+    fn property_access_works() {
+        let mut class = element("class", &[("name", "Window"), ("glib:type-name", "TestWindow"), ("glib:get-type", "test_window_get_type")]);
+        class.add_child(property("title", &[("writable", "1"), ("getter", "get_title"), ("setter", "set_title")]));
+        class.add_child(property("is-active", &[("writable", "0"), ("getter", "is_active")]));
+        class.add_child(property("secret", &[("readable", "0"), ("writable", "1")]));
+        class.add_child(property("role", &[("writable", "1"), ("construct-only", "1")]));
+        class.add_child(function("constructor", "new", "Window"));
+        class.add_child(function("method", "get_title", "utf8"));
+        class.add_child(function("method", "set_title", "none"));
+        class.add_child(function("method", "is_active", "gboolean"));
+
+        let files = generate(LuaCodegen::new(), &[], vec![class]);
+        let window = &files[&PathBuf::from("Window.lua")];
+        let end = window.find("--- @overload fun(self: Test.Window, signal").unwrap();
+        snapshot("property_access", &window[..end]);
+    }
+
+    #[test]
+    fn construct_params_works() {
+        // no Params without a `new` constructor or properties to set
+        let mut label = element("class", &[("name", "Label"), ("glib:type-name", "TestLabel"), ("glib:get-type", "test_label_get_type")]);
+        label.add_child(property("text", &[("writable", "1")]));
+        let mut clock = element("class", &[("name", "Clock"), ("glib:type-name", "TestClock"), ("glib:get-type", "test_clock_get_type")]);
+        clock.add_child(property("time", &[("writable", "0")]));
+        clock.add_child(function("constructor", "new", "Clock"));
+
+        let files = generate(LuaCodegen::new(), &[], vec![label, clock]);
+        let types: Vec<&str> = ["Label.lua", "Clock.lua"].iter()
+            .map(|f| {
+                let stub = &files[&PathBuf::from(f)];
+                &stub[..stub.find("local ").unwrap()]
+            })
+            .collect();
+        snapshot("construct_params", &types.join("\n"));
+    }
+
+    /// Fails when a file is written twice, the second write would replace
+    /// the first in a directory
+    #[derive(Default)]
+    struct OnceSink(Mutex<Vec<PathBuf>>);
+
+    impl Sink for OnceSink {
+        fn write(&self, path: &Path, _contents: &[u8]) -> Result<()> {
+            let mut paths = self.0.lock().unwrap();
+            if paths.iter().any(|p| p == path) {
+                bail!("{} written twice", path.display());
+            }
+            paths.push(path.to_path_buf());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn written_once_works() {
+        let resolver = Arc::new(Resolver::new(vec![PathBuf::from("testdata/girs")]));
+        let repo = resolver.load_file("testdata/girs/GMime-3.0.gir").unwrap();
+        let sink = OnceSink::default();
+        LuaCodegen::new().resolver(resolver).generate(&repo, &sink).unwrap();
+        assert!(sink.0.lock().unwrap().contains(&PathBuf::from("GMime_3_0/GMime_3_0/init.lua")));
+    }
+}
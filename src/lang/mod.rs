@@ -1,6 +1,10 @@
-use std::{str::FromStr, path::Path, fs::{self, File}, io::BufReader};
+use std::{str::FromStr, path::Path, fs::{self, File}, io::BufReader, sync::Arc};
 use anyhow::Result;
 
+use crate::library::Repository;
+use crate::sink::Sink;
+
+pub mod augment;
 pub mod lua;
 // pub mod python;
 
@@ -37,21 +41,25 @@ impl FromStr for Level {
     }
 }
 
-pub trait Generator {
-    fn genfile(&self, filename: &str, output_dir: Option<&str>) -> Result<()>;
-    fn generate(&self, filename: Option<&str>, output_dir: Option<&str>) -> Result<()> {
-        if let Some(filename) = filename {
-            self.genfile(filename, output_dir)
-        } else {
-            let paths = fs::read_dir("/usr/share/gir-1.0/")?;
-            for path in paths {
-                let osstr = path.expect("Couldn't read filename").file_name();
-                let filename = osstr.to_str().expect("Couldn't read filename");
-                println!("Generating file {}", filename);
-                self.genfile(filename, output_dir)?;
-            }
-            Ok(())
-        }
+/// Generates output, like stubs or documentation, from repositories
+pub trait Generator: Send + Sync {
+    /// The directory the output is written to when none is given
+    fn default_dir(&self) -> &'static str;
+    /// Generate the output for the namespaces in `repo` into `sink`
+    fn generate(&self, repo: &Arc<Repository>, sink: &dyn Sink) -> Result<()>;
+    /// Called with the repositories of a run before any is generated, for
+    /// output that refers to the others, like links between pages.
+    fn prepare(&self, _repos: &[Arc<Repository>]) {}
+    /// Called when all repositories have been generated, for output that
+    /// covers every repository, like an index.
+    fn finish(&self, _sink: &dyn Sink) -> Result<()> {
+        Ok(())
+    }
+    /// Whether a repository can be generated without the others. When
+    /// `finish` needs every repository, like for an index, everything is
+    /// generated again if one input changed.
+    fn incremental(&self) -> bool {
+        true
     }
 }
 
@@ -64,7 +72,7 @@ pub trait Generator {
 
 /// Try to open the file at path, if it fails,
 /// it will try to search for the path in gir directory
-fn open_gir<P: AsRef<Path>>(filename: P) -> Result<BufReader<File>> {
+pub(crate) fn open_gir<P: AsRef<Path>>(filename: P) -> Result<BufReader<File>> {
     match fs::File::open(&filename) {
         Ok(f) => Ok(BufReader::new(f)),
         Err(_) => {
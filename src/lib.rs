@@ -1,5 +1,18 @@
-// mod vimdoc;
+pub mod check;
+pub mod coverage;
+pub mod diff;
 pub mod lang;
+pub mod output;
 pub mod parse;
+pub mod query;
 pub mod library;
+pub mod lint;
+pub mod manifest;
+pub mod generate;
+pub mod graph;
+pub mod resolve;
+pub mod sink;
+pub mod typelib;
 pub mod version;
+pub mod visit;
+pub mod write;
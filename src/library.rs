@@ -1,9 +1,11 @@
+use serde::{Serialize, Deserialize};
+
 // TODO Fix version to Version in structs
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Version(pub u16, pub u16, pub u16);
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Repository {
     pub version: Option<Version>,
     pub xmlns: Option<String>,
@@ -15,23 +17,23 @@ pub struct Repository {
     pub namespace: Vec<Namespace>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Include {
     pub name: String,
     pub version: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CInclude {
     pub name: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Package {
     pub name: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Namespace {
     pub name: Option<String>,
 
@@ -56,9 +58,10 @@ pub struct Namespace {
     pub alias: Vec<Alias>,
     pub unions: Vec<Union>,
     pub boxed: Vec<Boxed>,
+    pub docsections: Vec<DocSection>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct InfoAttrs {
     pub introspectable: Option<bool>,
     // should be a bool
@@ -68,7 +71,14 @@ pub struct InfoAttrs {
     pub stability: Option<String>,
 }
 
-#[derive(Debug)]
+impl InfoAttrs {
+    /// Whether bindings have the element, `introspectable` defaults to true
+    pub fn is_introspectable(&self) -> bool {
+        self.introspectable != Some(false)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct InfoElements {
     pub doc: Option<Doc>,
     pub doc_stability: Option<DocVersioned>,
@@ -77,7 +87,7 @@ pub struct InfoElements {
     pub doc_pos: Option<DocPosition>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Doc {
     pub preserve_space: Option<String>, // bools? default false?
     pub preserve_white: Option<String>, // bools? default false?
@@ -87,21 +97,21 @@ pub struct Doc {
     pub content: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DocVersioned {
     pub preserve_space: Option<String>, // bools? default false?
     pub preserve_white: Option<String>, // bools? default false?
     pub content: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DocPosition {
     pub filename: String,
     pub line: String,
     pub column: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Class {
     pub info: InfoAttrs,
     pub doc: InfoElements,
@@ -143,7 +153,7 @@ pub struct Class {
     pub implements: Vec<Implement>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Record {
     pub name: String,
     pub info: InfoAttrs,
@@ -165,7 +175,7 @@ pub struct Record {
     pub method: Vec<Function>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Interface {
     pub name: String,
     pub info: InfoAttrs,
@@ -191,7 +201,7 @@ pub struct Interface {
     pub constant: Vec<Constant>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Constant {
     pub info: InfoAttrs,
     pub doc: InfoElements,
@@ -204,7 +214,7 @@ pub struct Constant {
     pub typ: Option<AnyType>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Bitfield {
     pub info: InfoAttrs,
     pub doc: InfoElements,
@@ -218,7 +228,7 @@ pub struct Bitfield {
     pub functions: Vec<Function>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Enumeration {
     pub info: InfoAttrs,
     pub doc: InfoElements,
@@ -234,7 +244,7 @@ pub struct Enumeration {
     pub functions: Vec<Function>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Macro {
     pub info: InfoAttrs,
     pub doc: InfoElements,
@@ -245,7 +255,7 @@ pub struct Macro {
     pub parameters: Vec<MacroParam>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum FunctionType {
     Function,
     Callback,
@@ -255,7 +265,7 @@ pub enum FunctionType {
     Member,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Function {
     pub info: InfoAttrs,
     pub doc: InfoElements,
@@ -269,12 +279,29 @@ pub struct Function {
     pub shadows: Option<String>,
     pub throws: Option<bool>,
     pub moved_to: Option<String>,
+    /// The c type of a callback
+    pub c_type: Option<String>,
+    /// The method that invokes a virtual method
+    pub invoker: Option<String>,
 
     pub parameters: Vec<Parameter>,
     pub ret: Option<Parameter>,
 }
 
-#[derive(Debug)]
+impl Function {
+    /// What the function is, like `method` or `virtual method`
+    pub fn kind(&self) -> &'static str {
+        match self.typ {
+            FunctionType::Constructor => "constructor",
+            FunctionType::Method => "method",
+            FunctionType::Callback => "callback",
+            FunctionType::Virtual => "virtual method",
+            FunctionType::Function | FunctionType::Member => "function",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Union {
     pub name: Option<String>,
     pub info: InfoAttrs,
@@ -292,7 +319,7 @@ pub struct Union {
     pub record: Vec<Record>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Signal {
     pub name: String,
     pub info: InfoAttrs,
@@ -309,7 +336,7 @@ pub struct Signal {
     pub ret: Option<Parameter>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Boxed {
     pub glib_name: String,
     pub info: InfoAttrs,
@@ -322,12 +349,19 @@ pub struct Boxed {
     pub functions: Vec<Function>,
 }
 
-#[derive(Debug)]
+/// Documentation that isn't attached to a type, like an overview
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocSection {
+    pub name: String,
+    pub doc: InfoElements,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Implement {
     pub name: String
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Alias {
     pub name: String,
     pub info: InfoAttrs,
@@ -337,7 +371,7 @@ pub struct Alias {
 }
 
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ParameterDirection {
     None,
     In, // default
@@ -346,14 +380,14 @@ pub enum ParameterDirection {
     Return,
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Transfer {
     None,
     Container,
     Full,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ParameterScope {
     None,
     Call,
@@ -362,7 +396,7 @@ pub enum ParameterScope {
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Member {
     pub info: InfoAttrs,
     pub doc: InfoElements,
@@ -373,15 +407,17 @@ pub struct Member {
     // pub glib_name: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct MacroParam {
     pub name: String,
     pub doc: InfoElements,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Parameter {
     pub name: String,
+    /// The instance-parameter of a method
+    pub instance: bool,
     pub nullable: bool, // default false
     pub allow_none: bool, // default false
     pub introspectable: Option<bool>,
@@ -402,26 +438,29 @@ pub struct Parameter {
 // only doing anotations and anytype is used for type referencing so specifics are not important. 
 // Calling a class method or a function might have different syntax but we don't do the semantics, 
 // that is why we can get away with this simplified version of anytype. 
-#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum AnyType {
     Array(Array),
     Type(Type),
+    /// The type of a field holding a function pointer, like a virtual
+    /// method in a class struct
+    Callback(Box<Function>),
     VarArg,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Array {
     pub name: Option<String>,
     pub zero_terminated: Option<bool>,
-    pub fixed_size: Option<bool>,
+    pub fixed_size: Option<usize>,
     pub introspectable: Option<bool>,
     pub length: Option<usize>,
     pub ctype: Option<String>,
-    pub typ: String,
-    // pub typ: Box<AnyType>,
+    pub typ: Box<AnyType>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Type {
     pub name: Option<String>,
     pub ctype: Option<String>,
@@ -431,7 +470,7 @@ pub struct Type {
     pub children: Vec<AnyType>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Field {
     pub name: String,
     pub info: InfoAttrs,
@@ -444,7 +483,7 @@ pub struct Field {
     pub bits: Option<u32>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Property {
     pub name: String,
     pub info: InfoAttrs,
@@ -0,0 +1,288 @@
+//! Checking a gir for annotation problems, for libraries that are written
+//! alongside their bindings. Diagnostics point at the source position the
+//! scanner recorded, or the position of the enclosing symbol.
+use std::fmt;
+use std::sync::Arc;
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::library::*;
+use crate::resolve::{Resolver, Scope, split_name};
+use crate::visit::{self, Context, Visitor};
+
+/// Types that are built into gobject-introspection
+const BASIC_TYPES: &[&str] = &[
+    "none", "gboolean", "gchar", "guchar", "gshort", "gushort", "gint", "guint",
+    "glong", "gulong", "gint8", "guint8", "gint16", "guint16", "gint32", "guint32",
+    "gint64", "guint64", "gfloat", "gdouble", "gsize", "gssize", "goffset",
+    "gintptr", "guintptr", "gpointer", "gconstpointer", "gunichar", "gunichar2",
+    "utf8", "filename", "GType", "va_list", "long double", "time_t", "off_t",
+    "pid_t", "uid_t", "gid_t", "dev_t", "socklen_t",
+];
+
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub line: Option<String>,
+    /// The symbol, like `Gtk.Widget.show`
+    pub path: String,
+    /// The name of the check, like `missing-doc`
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, &self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}: ", file, line)?,
+            (Some(file), None) => write!(f, "{}: ", file)?,
+            _ => {}
+        }
+        write!(f, "{}: {} [{}]", self.path, self.message, self.rule)
+    }
+}
+
+/// Check every namespace of `repo`, types are looked up in `resolver`.
+/// The includes of `repo` should have been loaded.
+pub fn lint(repo: &Arc<Repository>, resolver: &Resolver) -> Result<Vec<Diagnostic>> {
+    let mut lint = Lint { scope: resolver.scope(repo), diagnostics: vec![] };
+    let ns = repo.namespace.first().and_then(|ns| ns.name.as_deref()).unwrap_or("");
+    for include in repo.include.iter() {
+        if lint.scope.namespace(&include.name).is_none() {
+            lint.diagnostics.push(Diagnostic {
+                file: None,
+                line: None,
+                path: ns.to_string(),
+                rule: "missing-include",
+                message: format!("include {}-{} can't be found, its types aren't checked",
+                    include.name, include.version.as_deref().unwrap_or("?")),
+            });
+        }
+    }
+    visit::walk_repository(&mut lint, repo)?;
+    Ok(lint.diagnostics)
+}
+
+/// Where the scanner found the element
+fn position(doc: &InfoElements) -> (Option<String>, Option<String>) {
+    if let Some(ref pos) = doc.doc_pos {
+        (Some(pos.filename.clone()), Some(pos.line.clone()))
+    } else if let Some(ref doc) = doc.doc {
+        (Some(doc.filename.clone()), Some(doc.line.clone()))
+    } else {
+        (None, None)
+    }
+}
+
+fn documented(doc: &InfoElements) -> bool {
+    doc.doc.as_ref().is_some_and(|d| !d.content.trim().is_empty())
+}
+
+/// Whether the doc says the value can be %NULL, not counting %NULL-terminated
+fn mentions_null(doc: &InfoElements) -> bool {
+    doc.doc.as_ref().is_some_and(|d| {
+        d.content.match_indices("%NULL").any(|(i, m)| !d.content[i + m.len()..].starts_with('-'))
+    })
+}
+
+fn nullable(p: &Parameter) -> bool {
+    p.nullable || p.allow_none || p.optional
+}
+
+/// Whether the value is a pointer that needs to say who owns it
+fn is_pointer(typ: &AnyType) -> bool {
+    match typ {
+        AnyType::Array(_) => true,
+        AnyType::Type(t) => t.ctype.as_deref().is_some_and(|c| c.ends_with('*'))
+            || matches!(t.name.as_deref(), Some("utf8") | Some("filename")),
+        AnyType::Callback(_) | AnyType::VarArg => false,
+    }
+}
+
+struct Lint {
+    scope: Scope,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lint {
+    fn report(&mut self, doc: &InfoElements, path: &str, rule: &'static str, message: String) {
+        let (file, line) = position(doc);
+        self.diagnostics.push(Diagnostic { file, line, path: path.to_string(), rule, message });
+    }
+
+    fn doc(&mut self, doc: &InfoElements, path: &str, what: &str) {
+        if !documented(doc) {
+            self.report(doc, path, "missing-doc", format!("{} has no documentation", what));
+        }
+    }
+
+    /// Array and type problems of `typ`, reported at `doc`
+    fn typ(&mut self, typ: &AnyType, doc: &InfoElements, path: &str, ns: &str) {
+        match typ {
+            AnyType::Array(array) => {
+                if array.name.is_none() && array.length.is_none() && array.fixed_size.is_none()
+                    && array.zero_terminated == Some(false) {
+                    self.report(doc, path, "array-length",
+                        "array has no length, fixed-size or zero-terminated".to_string());
+                }
+                self.typ(&array.typ, doc, path, ns);
+            }
+            AnyType::Type(t) => {
+                if let Some(ref name) = t.name {
+                    let (tns, _) = split_name(name, ns);
+                    // the types of missing includes are reported once, as the include
+                    if !BASIC_TYPES.contains(&name.as_str())
+                        && self.scope.namespace(tns).is_some()
+                        && !self.scope.is_type(name, ns) {
+                        self.report(doc, path, "unresolved-type",
+                            format!("type {} can't be found in the namespace or its includes", name));
+                    }
+                }
+                for child in t.children.iter() {
+                    self.typ(child, doc, path, ns);
+                }
+            }
+            AnyType::Callback(_) | AnyType::VarArg => {}
+        }
+    }
+
+    /// The parameters and return value of a function or signal. Parameters
+    /// rarely have a position, so problems are reported at `doc`.
+    fn params(&mut self, params: &[Parameter], ret: Option<&Parameter>, doc: &InfoElements, path: &str, ns: &str) {
+        let params: Vec<&Parameter> = params.iter().filter(|p| !p.instance).collect();
+        // destroy notifies are called once and don't need a scope
+        let destroys: Vec<usize> = params.iter()
+            .filter_map(|p| p.destroy.as_deref().and_then(|d| d.parse().ok()))
+            .collect();
+        for (i, param) in params.iter().enumerate() {
+            let ppath = format!("{}({})", path, param.name);
+            if !documented(&param.doc) {
+                self.report(doc, &ppath, "undocumented-parameter",
+                    format!("parameter {} has no documentation", param.name));
+            }
+            if param.allow_none && !param.nullable && !param.optional {
+                self.report(doc, &ppath, "allow-none",
+                    "allow-none is deprecated, use nullable or optional".to_string());
+            }
+            if mentions_null(&param.doc) && !nullable(param) {
+                self.report(doc, &ppath, "nullable",
+                    "documented as %NULL but not nullable or optional".to_string());
+            }
+            let callback = match param.typ {
+                AnyType::Callback(_) => true,
+                AnyType::Type(ref t) => t.name.as_deref().is_some_and(|n| self.scope.is_callback(n, ns)),
+                _ => false,
+            };
+            if callback && param.scope.is_none() && !destroys.contains(&i) {
+                self.report(doc, &ppath, "callback-scope",
+                    format!("callback parameter {} has no scope", param.name));
+            }
+            self.typ(&param.typ, doc, &ppath, ns);
+        }
+        if let Some(ret) = ret {
+            if is_pointer(&ret.typ) {
+                if ret.transfer.is_none() {
+                    self.report(doc, path, "missing-transfer",
+                        "pointer return value has no transfer-ownership".to_string());
+                }
+                if mentions_null(&ret.doc) && !ret.nullable {
+                    self.report(doc, path, "nullable",
+                        "return value documented as %NULL but not nullable".to_string());
+                }
+            }
+            self.typ(&ret.typ, doc, path, ns);
+        }
+    }
+}
+
+impl Visitor for Lint {
+    fn visit_class<'a>(&mut self, class: &'a Class, cx: Context<'a>) -> Result<()> {
+        self.doc(&class.doc, &cx.path(&class.name), "class");
+        visit::walk_class(self, class, cx)
+    }
+
+    fn visit_interface<'a>(&mut self, interface: &'a Interface, cx: Context<'a>) -> Result<()> {
+        self.doc(&interface.doc, &cx.path(&interface.name), "interface");
+        visit::walk_interface(self, interface, cx)
+    }
+
+    fn visit_record<'a>(&mut self, record: &'a Record, cx: Context<'a>) -> Result<()> {
+        self.doc(&record.doc, &cx.path(&record.name), "record");
+        visit::walk_record(self, record, cx)
+    }
+
+    fn visit_union<'a>(&mut self, union: &'a Union, cx: Context<'a>) -> Result<()> {
+        if let Some(ref n) = union.name {
+            self.doc(&union.doc, &cx.path(n), "union");
+        }
+        visit::walk_union(self, union, cx)
+    }
+
+    fn visit_enum<'a>(&mut self, e: &'a Enumeration, cx: Context<'a>) -> Result<()> {
+        self.doc(&e.doc, &cx.path(&e.name), "enum");
+        visit::walk_enum(self, e, cx)
+    }
+
+    fn visit_bitfield<'a>(&mut self, b: &'a Bitfield, cx: Context<'a>) -> Result<()> {
+        self.doc(&b.doc, &cx.path(&b.name), "bitfield");
+        visit::walk_bitfield(self, b, cx)
+    }
+
+    fn visit_function<'a>(&mut self, fun: &'a Function, cx: Context<'a>) -> Result<()> {
+        let path = cx.path(&fun.name);
+        self.doc(&fun.doc, &path, "function");
+        self.params(&fun.parameters, fun.ret.as_ref(), &fun.doc, &path, cx.ns);
+        Ok(())
+    }
+
+    fn visit_signal<'a>(&mut self, signal: &'a Signal, cx: Context<'a>) -> Result<()> {
+        let path = format!("{}::{}", cx.qualified(), signal.name);
+        self.doc(&signal.doc, &path, "signal");
+        self.params(&signal.parameters, signal.ret.as_ref(), &signal.doc, &path, cx.ns);
+        Ok(())
+    }
+
+    fn visit_property<'a>(&mut self, prop: &'a Property, cx: Context<'a>) -> Result<()> {
+        let path = cx.path(&prop.name);
+        self.doc(&prop.doc, &path, "property");
+        self.typ(&prop.typ, &prop.doc, &path, cx.ns);
+        Ok(())
+    }
+
+    fn visit_field<'a>(&mut self, field: &'a Field, cx: Context<'a>) -> Result<()> {
+        self.typ(&field.typ, &field.doc, &cx.path(&field.name), cx.ns);
+        Ok(())
+    }
+
+    fn visit_constant<'a>(&mut self, constant: &'a Constant, cx: Context<'a>) -> Result<()> {
+        let path = cx.path(&constant.name);
+        self.doc(&constant.doc, &path, "constant");
+        if let Some(ref typ) = constant.typ {
+            self.typ(typ, &constant.doc, &path, cx.ns);
+        }
+        Ok(())
+    }
+
+    fn visit_alias<'a>(&mut self, alias: &'a Alias, cx: Context<'a>) -> Result<()> {
+        let path = cx.path(&alias.name);
+        self.doc(&alias.doc, &path, "alias");
+        self.typ(&alias.typ, &alias.doc, &path, cx.ns);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint;
+    use crate::resolve::Resolver;
+
+    #[test]
+    fn lint_works() {
+        let resolver = Resolver::new(vec![]);
+        let repo = resolver.load_file("testdata/girs/GMime-3.0.gir").unwrap();
+        let diagnostics = lint(&repo, &resolver).unwrap();
+        assert!(diagnostics.iter().any(|d| d.rule == "missing-include"));
+        // the Message class is documented
+        assert!(!diagnostics.iter().any(|d| d.rule == "missing-doc" && d.path == "GMime.Message"));
+    }
+}
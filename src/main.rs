@@ -1,38 +1,27 @@
-use std::str::FromStr;
+use std::io::Write;
+use std::path::PathBuf;
 
-use gir_to_stub::lang;
+use gir_to_stub::{coverage, diff, lang, lint, resolve};
+use gir_to_stub::graph::{Graph, GraphFormat};
+use gir_to_stub::query::Query;
+use gir_to_stub::resolve::Resolver;
+use gir_to_stub::generate::{Format, Lang, Options};
 
 use anyhow::Result;
 
-use clap::Parser;
-
-#[derive(Clone)]
-enum Lang {
-    // Python,
-    Lua,
-}
-
-impl FromStr for Lang {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "lua" => Ok(Lang::Lua),
-            // "python" => Ok(Lang::Python),
-            lang => {
-                let ret = format!("{} not supported", lang);
-                Err(ret)
-            }
-        }
-    }
-}
-
+use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    #[clap(required = true)]
-    #[clap(short, long, value_name = "lua|python")]
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    // the language of the stubs, only lua for now; python stubs aren't
+    // generated yet
+    #[clap(short, long, value_name = "lua")]
+    #[clap(default_value = "lua")]
     lang: Lang,
 
     // #[clap(long, value_name = "Code|CodeDoc|Full")]
@@ -40,6 +29,11 @@ struct Cli {
     #[clap(default_value_t = lang::Level::Code)]
     level: lang::Level,
 
+    // what to generate, stubs for the language or documentation
+    #[clap(short, long, value_name = "stub|vimdoc|markdown|html|man|docset|json|gir")]
+    #[clap(default_value_t = Format::Stub)]
+    format: Format,
+
     // generates all files found in "/usr/share/gir-1.0/" but overrides if file is 
     // found locally.
     #[clap(long)]
@@ -49,31 +43,273 @@ struct Cli {
     #[clap(short, long)]
     output: Option<String>,
 
-    // #[clap(required = true)]
+    // generate every file, even the ones the manifest in the output
+    // directory says haven't changed
+    #[clap(long)]
+    force: bool,
+
+    // generate in memory and compare with the output directory instead of
+    // writing to it, fails with a diff if anything is out of date
+    #[clap(long)]
+    check: bool,
+
+    // print the generated files instead of writing them, only the ones of
+    // the type (or namespace) if one is given after the filename
+    #[clap(long, conflicts_with_all = ["gen_all", "check"])]
+    stdout: bool,
+
+    // list the files that would be written without writing them
+    #[clap(long, conflicts_with_all = ["check", "stdout"])]
+    dry_run: bool,
+
+    // the number of files generated at the same time, 0 for one per cpu
+    #[clap(short, long, value_name = "N")]
+    #[clap(default_value_t = 0)]
+    jobs: usize,
+
+    // directory with hand-written snippets, one directory per namespace,
+    // that are merged into the generated files.
+    #[clap(long, value_name = "DIR")]
+    augment: Option<String>,
+
+    // a gir or typelib file, every installed one with --gen-all
+    #[clap(required_unless_present = "gen_all")]
     filename: Option<String>,
 
+    // a type like Gio.File or a namespace, with --stdout
+    #[clap(requires = "stdout")]
+    name: Option<String>,
+
 }
 
-fn get_lang(lang: Lang) -> Box<dyn lang::Generator> {
-    match lang {
-        // Lang::Python => {
-        //     Box::new(lang::python::PythonCodeGen::new())
-        // }
-        Lang::Lua => {
-            Box::new(lang::lua::LuaCodegen::new())
-        },
+#[derive(Subcommand)]
+enum Command {
+    /// Compare two versions of a gir or typelib file
+    Diff {
+        old: String,
+        new: String,
+
+        // print the changes as json
+        #[clap(long)]
+        json: bool,
+
+        // only print breaking changes and fail if there are any
+        #[clap(long)]
+        breaking: bool,
+    },
+    /// List the symbols left out of the stubs and why
+    Coverage {
+        #[clap(required = true)]
+        files: Vec<String>,
+
+        // print the report as json
+        #[clap(long)]
+        json: bool,
+    },
+    /// Check gir files for annotation problems
+    Lint {
+        #[clap(required = true)]
+        files: Vec<String>,
+    },
+    /// Search the installed gir and typelib files
+    Query {
+        // a part of the lua name or the C identifier
+        name: Option<String>,
+
+        // only functions returning this type
+        #[clap(long, value_name = "TYPE")]
+        returns: Option<String>,
+
+        // only functions taking a parameter of this type
+        #[clap(long, value_name = "TYPE")]
+        takes: Option<String>,
+
+        // search these files instead of the installed ones
+        #[clap(long = "file", value_name = "FILE")]
+        files: Vec<String>,
+
+        // print the results as json
+        #[clap(long)]
+        json: bool,
+    },
+    /// Print the class hierarchy or the namespace includes as a graph
+    Graph {
+        #[clap(required = true)]
+        files: Vec<String>,
+
+        #[clap(short, long, value_name = "dot|mermaid")]
+        #[clap(default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+
+        // only this type (or namespace) with its ancestors and descendants
+        #[clap(long, value_name = "NAME")]
+        root: Option<String>,
+
+        // only this many edges from the root
+        #[clap(long)]
+        depth: Option<usize>,
+
+        // the includes of the namespaces instead of the types
+        #[clap(long)]
+        namespaces: bool,
+    },
+}
+
+fn run_diff(old: &str, new: &str, json: bool, breaking: bool) -> Result<()> {
+    let old = resolve::read_repository(old)?;
+    let new = resolve::read_repository(new)?;
+    let mut changes = diff::diff(&old, &new);
+    if breaking {
+        changes.retain(|c| c.breaking);
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+    } else {
+        for change in changes.iter() {
+            println!("{}", change);
+        }
     }
+    if breaking && !changes.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_coverage(files: &[String], json: bool) -> Result<()> {
+    let mut report = vec![];
+    for file in files.iter() {
+        report.extend(coverage::read_coverage(file)?);
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", coverage::report(&report));
+    }
+    Ok(())
+}
+
+fn run_lint(files: &[String]) -> Result<()> {
+    let resolver = Resolver::default();
+    let mut found = false;
+    for file in files.iter() {
+        let repo = resolver.load_file(file)?;
+        for diagnostic in lint::lint(&repo, &resolver)? {
+            println!("{}", diagnostic);
+            found = true;
+        }
+    }
+    if found {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_query(query: Query, files: Vec<String>, json: bool) -> Result<()> {
+    let files = if files.is_empty() {
+        Resolver::default().available()
+    } else {
+        files.into_iter().map(PathBuf::from).collect()
+    };
+    let mut hits = vec![];
+    for file in files.iter() {
+        match resolve::read_repository(file) {
+            Ok(repo) => hits.extend(query.search(&repo)?),
+            Err(err) => eprintln!("Skipping {}: {}", file.display(), err),
+        }
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&hits)?);
+    } else {
+        for hit in hits.iter() {
+            match hit.c_identifier {
+                Some(ref c) => println!("{}  -- {}", hit.signature, c),
+                None => println!("{}", hit.signature),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_graph(files: &[String], format: GraphFormat, root: Option<&str>, depth: Option<usize>, namespaces: bool) -> Result<()> {
+    let resolver = Resolver::default();
+    let mut graph = Graph::default();
+    for file in files.iter() {
+        let repo = resolver.load_file(file)?;
+        if namespaces {
+            graph.namespaces(&repo, &resolver);
+        } else {
+            graph.types(&repo);
+        }
+    }
+    if root.is_some() || depth.is_some() {
+        graph = graph.limit(root, depth)?;
+    }
+    print!("{}", graph.render(format));
+    Ok(())
+}
+
+fn run_check(options: &Options) -> Result<()> {
+    let stale = options.check()?;
+    for file in stale.iter() {
+        print!("{}", file);
+    }
+    if !stale.is_empty() {
+        eprintln!("{} generated files are out of date", stale.len());
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_stdout(options: &Options, name: Option<&str>) -> Result<()> {
+    let files = options.files(name)?;
+    let mut out = std::io::stdout().lock();
+    for (path, contents) in files.iter() {
+        // like head, the path before every file if there is more than one
+        if files.len() > 1 {
+            writeln!(out, "==> {} <==", path.display())?;
+        }
+        out.write_all(contents)?;
+    }
+    Ok(())
 }
 
-// a bit much copy-pasty
 fn main() -> Result<()>{
     let args = Cli::parse();
-    let cg = get_lang(args.lang);
-    if args.gen_all {
-        cg.generate(None, args.output.as_deref())?;
+    match args.command {
+        Some(Command::Diff { old, new, json, breaking }) => return run_diff(&old, &new, json, breaking),
+        Some(Command::Coverage { files, json }) => return run_coverage(&files, json),
+        Some(Command::Lint { files }) => return run_lint(&files),
+        Some(Command::Query { name, returns, takes, files, json }) =>
+            return run_query(Query { name, returns, takes }, files, json),
+        Some(Command::Graph { files, format, root, depth, namespaces }) =>
+            return run_graph(&files, format, root.as_deref(), depth, namespaces),
+        None => {}
+    }
+    let files = if args.gen_all {
+        vec![]
     } else {
-        let filename = args.filename.expect("Missing filename");
-        cg.generate(Some(&filename), args.output.as_deref())?;
+        vec![args.filename.expect("Missing filename")]
+    };
+    let options = Options {
+        format: args.format,
+        lang: args.lang,
+        files,
+        output_dir: args.output,
+        augment: args.augment,
+        force: args.force,
+        jobs: args.jobs,
+    };
+    if args.check {
+        return run_check(&options)
     }
-    Ok(())
+    if args.stdout {
+        return run_stdout(&options, args.name.as_deref())
+    }
+    if args.dry_run {
+        for path in options.dry_run()? {
+            println!("{}", path.display());
+        }
+        return Ok(())
+    }
+    options.run()
 }
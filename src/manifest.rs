@@ -0,0 +1,193 @@
+//! What was generated into an output directory and from what, so a run
+//! can skip the inputs that haven't changed and delete the files that
+//! are no longer generated.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// The name of the manifest in the output directory
+pub const MANIFEST: &str = ".gir-to-stub.json";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Input {
+    pub hash: String,
+    /// The files of the includes, and their includes, with their hashes
+    pub includes: BTreeMap<PathBuf, String>,
+    /// The files generated from the input, relative to the output directory,
+    /// with the hash of their contents
+    pub outputs: BTreeMap<PathBuf, String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The version of gir-to-stub that generated the output
+    pub version: String,
+    /// The format and the options of the generator
+    pub options: String,
+    /// Keyed by the input file as given
+    pub inputs: BTreeMap<String, Input>,
+    /// The files written after every input was generated, like an index
+    pub finished: BTreeMap<PathBuf, String>,
+}
+
+impl Manifest {
+    pub fn new(options: String) -> Manifest {
+        Manifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            options,
+            ..Manifest::default()
+        }
+    }
+
+    /// The manifest in `dir`, an empty one if there is none or it
+    /// can't be read
+    pub fn load<P: AsRef<Path>>(dir: P) -> Manifest {
+        fs::read(dir.as_ref().join(MANIFEST)).ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(MANIFEST), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Whether the output was generated by this version with the same options
+    pub fn compatible(&self, other: &Manifest) -> bool {
+        self.version == other.version && self.options == other.options
+    }
+
+    /// Every file generated
+    pub fn outputs(&self) -> BTreeSet<&Path> {
+        self.inputs.values()
+            .flat_map(|i| i.outputs.keys())
+            .chain(self.finished.keys())
+            .map(PathBuf::as_path)
+            .collect()
+    }
+
+    /// Delete the files of `old` that aren't generated anymore, and the
+    /// directories that are left empty
+    pub fn remove_stale<P: AsRef<Path>>(&self, old: &Manifest, dir: P) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        let outputs = self.outputs();
+        let mut removed = vec![];
+        for path in old.outputs().difference(&outputs) {
+            let full = dir.join(path);
+            match fs::remove_file(&full) {
+                Ok(()) => removed.push(path.to_path_buf()),
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+            for parent in full.ancestors().skip(1) {
+                if parent == dir || fs::remove_dir(parent).is_err() {
+                    break;
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// FNV-1a of `data`. Not for security, only to notice changed files,
+/// and the same on every platform and rust version.
+pub fn hash(data: &[u8]) -> String {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in data {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", h)
+}
+
+/// Hashes files, each only once per run. Files that can't be read
+/// have no hash.
+#[derive(Default)]
+pub struct Hashes {
+    files: HashMap<PathBuf, Option<String>>,
+}
+
+impl Hashes {
+    pub fn file<P: AsRef<Path>>(&mut self, path: P) -> Option<String> {
+        let path = path.as_ref();
+        self.files.entry(path.to_path_buf())
+            .or_insert_with(|| fs::read(path).ok().map(|data| hash(&data)))
+            .clone()
+    }
+
+    /// Whether `input` was generated from the files as they are now, and
+    /// its outputs in `dir` are still as they were written
+    pub fn unchanged(&mut self, input: &Input, path: &Path, dir: &Path) -> bool {
+        self.file(path).as_ref() == Some(&input.hash)
+            && input.includes.iter().all(|(inc, hash)| self.file(inc).as_ref() == Some(hash))
+            && self.written(&input.outputs, dir)
+    }
+
+    /// Whether the files in `dir` still have the hashes in `outputs`, none
+    /// were deleted or edited
+    pub fn written(&mut self, outputs: &BTreeMap<PathBuf, String>, dir: &Path) -> bool {
+        outputs.iter().all(|(out, hash)| self.file(dir.join(out)).as_ref() == Some(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use super::{hash, Hashes, Input, Manifest};
+
+    #[test]
+    fn remove_stale_works() {
+        let dir = std::env::temp_dir().join(format!("gir-to-stub-manifest-{}", std::process::id()));
+        fs::create_dir_all(dir.join("GMime")).unwrap();
+        fs::write(dir.join("GMime/init.lua"), "").unwrap();
+        fs::write(dir.join("GMime/Gone.lua"), "").unwrap();
+
+        let mut old = Manifest::new(String::new());
+        old.inputs.insert("GMime-3.0.gir".to_string(), Input {
+            outputs: ["GMime/init.lua", "GMime/Gone.lua"].iter().map(|p| (PathBuf::from(p), hash(b""))).collect(),
+            ..Input::default()
+        });
+        let mut new = Manifest::new(String::new());
+        new.inputs.insert("GMime-3.0.gir".to_string(), Input {
+            outputs: [(PathBuf::from("GMime/init.lua"), hash(b""))].into_iter().collect(),
+            ..Input::default()
+        });
+        let removed = new.remove_stale(&old, &dir).unwrap();
+        assert_eq!(removed, vec![PathBuf::from("GMime/Gone.lua")]);
+        assert!(dir.join("GMime/init.lua").exists());
+
+        // nothing is generated from GMime anymore, so the directory goes too
+        let removed = Manifest::new(String::new()).remove_stale(&new, &dir).unwrap();
+        assert_eq!(removed, vec![PathBuf::from("GMime/init.lua")]);
+        assert!(!dir.join("GMime").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unchanged_works() {
+        let dir = std::env::temp_dir().join(format!("gir-to-stub-unchanged-{}", std::process::id()));
+        fs::create_dir_all(dir.join("GMime")).unwrap();
+        fs::write(dir.join("GMime-3.0.gir"), "<repository/>").unwrap();
+        fs::write(dir.join("GMime/init.lua"), "return {}\n").unwrap();
+        let input = Input {
+            hash: hash(b"<repository/>"),
+            outputs: [(PathBuf::from("GMime/init.lua"), hash(b"return {}\n"))].into_iter().collect(),
+            ..Input::default()
+        };
+        let gir = dir.join("GMime-3.0.gir");
+        assert!(Hashes::default().unchanged(&input, &gir, &dir));
+
+        // an output that was edited or deleted is generated again
+        fs::write(dir.join("GMime/init.lua"), "return nil\n").unwrap();
+        assert!(!Hashes::default().unchanged(&input, &gir, &dir));
+        fs::remove_file(dir.join("GMime/init.lua")).unwrap();
+        assert!(!Hashes::default().unchanged(&input, &gir, &dir));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,210 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+use rusqlite::{Connection, DatabaseName, params};
+
+use crate::lang::Generator;
+use crate::library::Repository;
+use crate::resolve::Resolver;
+use crate::sink::{MemorySink, Sink};
+use super::html::Html;
+
+/// Packages the html site as a Dash/Zeal docset. Every namespace generated
+/// in one run goes into the same docset, named after the namespaces in it,
+/// like `GLib+Gio`:
+///
+/// ```text
+/// <Name>.docset/Contents/Info.plist
+/// <Name>.docset/Contents/Resources/docSet.dsidx    the searchIndex table
+/// <Name>.docset/Contents/Resources/Documents/      the html site
+/// ```
+pub struct Docset {
+    html: Html,
+    // the html site, it's only known where it goes once all files are generated
+    site: MemorySink,
+    namespaces: Mutex<Vec<String>>,
+}
+
+impl Docset {
+    pub fn new() -> Docset {
+        Docset {
+            html: Html::new(),
+            site: MemorySink::new(),
+            namespaces: Mutex::new(vec![]),
+        }
+    }
+
+    /// Look up types from other namespaces in `resolver`
+    pub fn resolver(mut self, resolver: Arc<Resolver>) -> Docset {
+        self.html = self.html.resolver(resolver);
+        self
+    }
+
+    /// The namespaces of the docset sorted, so the name doesn't depend on
+    /// the order the inputs were generated in
+    fn name(&self) -> String {
+        let mut namespaces = self.namespaces.lock().unwrap().clone();
+        namespaces.sort();
+        namespaces.join("+")
+    }
+}
+
+impl Default for Docset {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The dash entry type for a kind of the search index
+fn entry_type(kind: &str) -> &'static str {
+    match kind {
+        "namespace" => "Namespace",
+        "class" => "Class",
+        "interface" => "Interface",
+        "record" => "Struct",
+        "constructor" => "Constructor",
+        "method" => "Method",
+        "function" => "Function",
+        "property" => "Property",
+        "signal" => "Event",
+        "enum" | "bitfield" => "Enum",
+        "constant" => "Constant",
+        _ => "Entry",
+    }
+}
+
+impl Generator for Docset {
+    fn default_dir(&self) -> &'static str {
+        "docset"
+    }
+
+    fn incremental(&self) -> bool {
+        false
+    }
+
+    fn prepare(&self, repos: &[Arc<Repository>]) {
+        self.html.prepare(repos)
+    }
+
+    fn generate(&self, repo: &Arc<Repository>, _sink: &dyn Sink) -> Result<()> {
+        self.html.generate(repo, &self.site)?;
+        let mut namespaces = self.namespaces.lock().unwrap();
+        for name in repo.namespace.iter().filter_map(|ns| ns.name.as_ref()) {
+            if !namespaces.contains(name) {
+                namespaces.push(name.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&self, sink: &dyn Sink) -> Result<()> {
+        self.html.finish(&self.site)?;
+
+        let name = self.name();
+        let contents = PathBuf::from(format!("{}.docset", name)).join("Contents");
+        let resources = contents.join("Resources");
+        let documents = resources.join("Documents");
+        for path in self.site.paths() {
+            if let Some(file) = self.site.get(&path) {
+                sink.write(&documents.join(path), &file)?;
+            }
+        }
+
+        sink.write(&contents.join("Info.plist"), format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>CFBundleIdentifier</key>
+	<string>{id}</string>
+	<key>CFBundleName</key>
+	<string>{name}</string>
+	<key>DocSetPlatformFamily</key>
+	<string>{id}</string>
+	<key>isDashDocset</key>
+	<true/>
+	<key>isJavaScriptEnabled</key>
+	<true/>
+	<key>dashIndexFilePath</key>
+	<string>index.html</string>
+</dict>
+</plist>
+"#, id = name.to_lowercase(), name = name).as_bytes())?;
+
+        // the index is built in memory, so it can go to any sink
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT);
+             CREATE UNIQUE INDEX anchor ON searchIndex (name, type, path);")?;
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR IGNORE INTO searchIndex(name, type, path) VALUES (?1, ?2, ?3)")?;
+            for entry in self.html.search_index() {
+                insert.execute(params![entry.name, entry_type(entry.kind), entry.path])?;
+            }
+        }
+        tx.commit()?;
+        let db = conn.serialize(DatabaseName::Main)?;
+        sink.write(&resources.join("docSet.dsidx"), &db)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::fs;
+    use rusqlite::Connection;
+    use super::Docset;
+    use crate::lang::Generator;
+    use crate::resolve::Resolver;
+    use crate::sink::MemorySink;
+
+    fn generate(files: &[&str]) -> MemorySink {
+        let resolver = Arc::new(Resolver::new(vec![PathBuf::from("testdata/girs")]));
+        let docset = Docset::new().resolver(resolver.clone());
+        let repos: Vec<_> = files.iter()
+            .map(|f| resolver.load_file(&format!("testdata/girs/{}", f)).unwrap())
+            .collect();
+        docset.prepare(&repos);
+        let sink = MemorySink::new();
+        for repo in repos.iter() {
+            docset.generate(repo, &sink).unwrap();
+        }
+        docset.finish(&sink).unwrap();
+        sink
+    }
+
+    #[test]
+    fn docset_works() {
+        let sink = generate(&["GMime-3.0.gir"]);
+        let db = sink.get(Path::new("GMime.docset/Contents/Resources/docSet.dsidx")).unwrap();
+        // the index is opened like dash does, from a file
+        let path = std::env::temp_dir().join(format!("gir-to-stub-docset-{}.dsidx", std::process::id()));
+        fs::write(&path, db).unwrap();
+        let conn = Connection::open(&path).unwrap();
+        let mut query = conn.prepare(
+            "SELECT name, type, path FROM searchIndex
+             WHERE name = 'GMime.Message' OR name GLOB 'GMime.Message[.:]*'").unwrap();
+        let rows: Vec<(String, String, String)> = query
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        fs::remove_file(&path).unwrap();
+
+        let row = |name: &str, typ: &str, path: &str| (name.to_string(), typ.to_string(), path.to_string());
+        assert!(rows.contains(&row("GMime.Message", "Class", "GMime-3.0/Message.html")));
+        assert!(rows.contains(&row("GMime.Message.new", "Constructor", "GMime-3.0/Message.html#new")));
+        assert!(rows.contains(&row("GMime.Message:set_subject", "Method", "GMime-3.0/Message.html#set_subject")));
+        assert!(rows.iter().all(|(_, _, path)| path.starts_with("GMime-3.0/Message.html")));
+        assert_eq!(rows.len(), 25);
+    }
+
+    #[test]
+    fn name_works() {
+        let sink = generate(&["Toolkit-4.0.gir", "GMime-3.0.gir"]);
+        assert!(sink.get(Path::new("GMime+Toolkit.docset/Contents/Info.plist")).is_some());
+    }
+}
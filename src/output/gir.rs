@@ -0,0 +1,41 @@
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+
+use crate::lang::Generator;
+use crate::library::Repository;
+use crate::sink::Sink;
+use crate::write::write_gir;
+
+/// Writes the parsed repository back out as `<Namespace>-<version>.gir`
+pub struct Gir {}
+
+impl Gir {
+    pub fn new() -> Gir {
+        Gir {}
+    }
+}
+
+impl Default for Gir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator for Gir {
+    fn default_dir(&self) -> &'static str {
+        "gir"
+    }
+
+    fn generate(&self, repo: &Arc<Repository>, sink: &dyn Sink) -> Result<()> {
+        let ns = repo.namespace.first().context("Repository has no namespace")?;
+        let name = ns.name.as_ref().context("Failed to read name")?;
+        let filename = match ns.version {
+            Some(ref version) => format!("{}-{}.gir", name, version),
+            None => format!("{}.gir", name),
+        };
+        let mut w = vec![];
+        write_gir(repo, &mut w)?;
+        sink.write(Path::new(&filename), &w)
+    }
+}
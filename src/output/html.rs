@@ -0,0 +1,739 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::library::*;
+use crate::lang::Generator;
+use crate::resolve::Resolver;
+use crate::visit::*;
+use crate::sink::Sink;
+use super::markdown::{Markdown, Page, anchor, namespace_dir};
+use super::summary;
+
+/// An entry of the client-side search index
+#[derive(Clone, Serialize)]
+pub struct SearchEntry {
+    /// The name as written in lua, like `Gtk.Widget:show`
+    pub name: String,
+    pub kind: &'static str,
+    /// Path of the page relative to the root of the site, with an anchor
+    pub path: String,
+    pub summary: String,
+}
+
+#[derive(Default)]
+struct Site {
+    // namespace directory and title of its index page
+    namespaces: BTreeMap<String, String>,
+    // keyed by namespace, so the index doesn't depend on the order
+    // repositories are generated in
+    search: BTreeMap<String, Vec<SearchEntry>>,
+}
+
+impl Site {
+    fn search(&self) -> Vec<SearchEntry> {
+        self.search.values().flatten().cloned().collect()
+    }
+}
+
+/// Generates a static html site from the markdown pages: an index of
+/// namespaces, a page per type and a search index (search-index.js, and
+/// search-index.json for other tools) that is queried in the browser. The site has no external dependencies.
+pub struct Html {
+    markdown: Markdown,
+    site: Mutex<Site>,
+}
+
+impl Html {
+    pub fn new() -> Html {
+        Html {
+            markdown: Markdown::new(),
+            site: Mutex::new(Site::default()),
+        }
+    }
+
+    /// Look up types from other namespaces in `resolver`
+    pub fn resolver(mut self, resolver: Arc<Resolver>) -> Html {
+        self.markdown = self.markdown.resolver(resolver);
+        self
+    }
+
+    /// The search entries of everything generated so far
+    pub fn search_index(&self) -> Vec<SearchEntry> {
+        self.site.lock().unwrap().search()
+    }
+}
+
+impl Default for Html {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator for Html {
+    fn default_dir(&self) -> &'static str {
+        "html"
+    }
+
+    fn incremental(&self) -> bool {
+        false
+    }
+
+    fn prepare(&self, repos: &[Arc<Repository>]) {
+        self.markdown.prepare(repos)
+    }
+
+    fn generate(&self, repo: &Arc<Repository>, sink: &dyn Sink) -> Result<()> {
+        let pages = self.markdown.pages(repo)?;
+        for page in pages.iter() {
+            sink.write(&page.path.with_extension("html"), render_page(page).as_bytes())?;
+        }
+
+        let mut site = self.site.lock().unwrap();
+        for page in pages.iter().filter(|p| p.path.ends_with("index.md")) {
+            let ns = page.path.parent()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            site.namespaces.insert(ns, page.title.clone());
+        }
+        let dir = repo.namespace.first().and_then(namespace_dir).unwrap_or_default();
+        let entries = site.search.entry(dir.clone()).or_default();
+        walk_repository(&mut Search { dir, entries }, repo)?;
+        Ok(())
+    }
+
+    fn finish(&self, sink: &dyn Sink) -> Result<()> {
+        let site = self.site.lock().unwrap();
+
+        let mut body = String::from("<h1>Namespaces</h1>\n<ul class=\"namespaces\">\n");
+        for (ns, title) in site.namespaces.iter() {
+            body.push_str(&format!("<li><a href=\"{}/index.html\">{}</a></li>\n",
+                escape(ns), escape(title)));
+        }
+        body.push_str("</ul>\n");
+        sink.write(Path::new("index.html"), template("API reference", "", &body).as_bytes())?;
+
+        // the page loads a script, browsers don't fetch json from file:// urls.
+        // The json is for other tools.
+        let json = serde_json::to_string(&site.search())?;
+        sink.write(Path::new("search-index.json"), json.as_bytes())?;
+        let index = format!("window.searchIndex = {};\n", json);
+        sink.write(Path::new("search-index.js"), index.as_bytes())?;
+        sink.write(Path::new("style.css"), STYLE.as_bytes())?;
+        sink.write(Path::new("search.js"), SEARCH.as_bytes())
+    }
+}
+
+fn render_page(page: &Page) -> String {
+    // pages are one directory below the root
+    let root = "../".repeat(page.path.components().count() - 1);
+    template(&page.title, &root, &render(&page.markdown))
+}
+
+fn template(title: &str, root: &str, body: &str) -> String {
+    format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<link rel="stylesheet" href="{root}style.css">
+</head>
+<body>
+<header>
+<a class="home" href="{root}index.html">Index</a>
+<div class="search">
+<input id="search" type="search" placeholder="Search…" autocomplete="off">
+<ul id="results"></ul>
+</div>
+</header>
+<main>
+{body}</main>
+<script src="{root}search.js" data-root="{root}"></script>
+</body>
+</html>
+"#, title = escape(title), root = root, body = body)
+}
+
+/// Collects everything in a namespace that has a page or a heading
+struct Search<'s> {
+    /// The directory of the namespace, like `Gtk-4.0`
+    dir: String,
+    entries: &'s mut Vec<SearchEntry>,
+}
+
+impl Search<'_> {
+    /// The page of the type the element in `cx` is declared in,
+    /// the namespace index for the namespace
+    fn page(&self, cx: &Context) -> String {
+        match cx.parent.and_then(|p| p.name()) {
+            Some(name) => self.type_page(name),
+            None => format!("{}/index.html", self.dir),
+        }
+    }
+
+    fn type_page(&self, name: &str) -> String {
+        format!("{}/{}.html", self.dir, name)
+    }
+
+    fn push(&mut self, name: String, kind: &'static str, path: String, doc: &InfoElements) {
+        self.entries.push(SearchEntry { name, kind, path, summary: summary(doc) });
+    }
+}
+
+impl Visitor for Search<'_> {
+    fn visit_namespace<'a>(&mut self, ns: &'a Namespace, cx: Context<'a>) -> Result<()> {
+        self.entries.push(SearchEntry {
+            name: cx.ns.to_string(),
+            kind: "namespace",
+            path: self.page(&cx),
+            summary: String::new(),
+        });
+        walk_namespace(self, ns, cx)
+    }
+
+    fn visit_class<'a>(&mut self, class: &'a Class, cx: Context<'a>) -> Result<()> {
+        if class.info.is_introspectable() {
+            self.push(format!("{}.{}", cx.ns, class.name), "class",
+                self.type_page(&class.name), &class.doc);
+            walk_class(self, class, cx)?;
+        }
+        Ok(())
+    }
+
+    fn visit_interface<'a>(&mut self, interface: &'a Interface, cx: Context<'a>) -> Result<()> {
+        if interface.info.is_introspectable() {
+            self.push(format!("{}.{}", cx.ns, interface.name), "interface",
+                self.type_page(&interface.name), &interface.doc);
+            walk_interface(self, interface, cx)?;
+        }
+        Ok(())
+    }
+
+    fn visit_record<'a>(&mut self, record: &'a Record, cx: Context<'a>) -> Result<()> {
+        // class structs and nested records don't get a page
+        if cx.parent.is_none() && record.info.is_introspectable()
+            && record.glib_is_gtype_struct_for.is_none() {
+            self.push(format!("{}.{}", cx.ns, record.name), "record",
+                self.type_page(&record.name), &record.doc);
+            walk_record(self, record, cx)?;
+        }
+        Ok(())
+    }
+
+    fn visit_union<'a>(&mut self, _union: &'a Union, _cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_boxed<'a>(&mut self, _boxed: &'a Boxed, _cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_enum<'a>(&mut self, e: &'a Enumeration, cx: Context<'a>) -> Result<()> {
+        if e.info.is_introspectable() {
+            let qualified = format!("{}.{}", cx.ns, e.name);
+            let path = format!("{}#{}", self.page(&cx), heading_id(&qualified));
+            self.push(qualified, "enum", path, &e.doc);
+        }
+        Ok(())
+    }
+
+    fn visit_bitfield<'a>(&mut self, b: &'a Bitfield, cx: Context<'a>) -> Result<()> {
+        if b.info.is_introspectable() {
+            let qualified = format!("{}.{}", cx.ns, b.name);
+            let path = format!("{}#{}", self.page(&cx), heading_id(&qualified));
+            self.push(qualified, "bitfield", path, &b.doc);
+        }
+        Ok(())
+    }
+
+    fn visit_function<'a>(&mut self, fun: &'a Function, cx: Context<'a>) -> Result<()> {
+        let kind = match (&fun.typ, cx.parent) {
+            (FunctionType::Constructor, Some(Parent::Class(_) | Parent::Record(_))) => "constructor",
+            (FunctionType::Method, _) => "method",
+            (FunctionType::Function, _) => "function",
+            _ => return Ok(()),
+        };
+        if fun.info.is_introspectable() {
+            let sep = if fun.typ == FunctionType::Method { ":" } else { "." };
+            self.push(format!("{}{}{}", cx.qualified(), sep, fun.name), kind,
+                format!("{}#{}", self.page(&cx), anchor(&fun.name)), &fun.doc);
+        }
+        Ok(())
+    }
+
+    fn visit_property<'a>(&mut self, prop: &'a Property, cx: Context<'a>) -> Result<()> {
+        if prop.info.is_introspectable() {
+            self.push(cx.path(&prop.name.replace('-', "_")),
+                "property", format!("{}#properties", self.page(&cx)), &prop.doc);
+        }
+        Ok(())
+    }
+
+    fn visit_signal<'a>(&mut self, signal: &'a Signal, cx: Context<'a>) -> Result<()> {
+        if signal.info.is_introspectable() {
+            self.push(format!("{}::{}", cx.qualified(), signal.name),
+                "signal", format!("{}#signals", self.page(&cx)), &signal.doc);
+        }
+        Ok(())
+    }
+
+    fn visit_constant<'a>(&mut self, constant: &'a Constant, cx: Context<'a>) -> Result<()> {
+        if cx.parent.is_none() && constant.info.is_introspectable() {
+            self.push(format!("{}.{}", cx.ns, constant.name), "constant",
+                format!("{}#constants", self.page(&cx)), &constant.doc);
+        }
+        Ok(())
+    }
+}
+
+fn escape(str: &str) -> String {
+    let mut out = String::with_capacity(str.len());
+    for c in str.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The id of a heading, same as the anchors used in the markdown pages
+fn heading_id(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Point links to other pages at their html version
+fn rewrite_link(url: &str) -> String {
+    if url.contains("://") {
+        return url.to_string();
+    }
+    match url.split_once('#') {
+        Some((page, anchor)) if page.ends_with(".md") => {
+            format!("{}#{}", PathBuf::from(page).with_extension("html").display(), anchor)
+        }
+        None if url.ends_with(".md") => {
+            PathBuf::from(url).with_extension("html").display().to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Render the subset of markdown written by the markdown output (and found
+/// in gir docs) to html: headings, paragraphs, tables, lists, block quotes,
+/// fenced code, code spans, links and emphasis.
+fn render(markdown: &str) -> String {
+    let mut out = String::new();
+    let lines: Vec<&str> = markdown.lines().collect();
+    let mut para: Vec<&str> = vec![];
+    let mut i = 0;
+
+    fn flush(para: &mut Vec<&str>, out: &mut String) {
+        if !para.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", inline(&para.join("\n"))));
+            para.clear();
+        }
+    }
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            flush(&mut para, &mut out);
+            let mut code = vec![];
+            i += 1;
+            while i < lines.len() && !lines[i].trim_start().starts_with("```") {
+                code.push(lines[i]);
+                i += 1;
+            }
+            let code = code.join("\n");
+            if lang.trim() == "lua" {
+                out.push_str(&format!("<pre><code class=\"language-lua\">{}</code></pre>\n", highlight(&code)));
+            } else {
+                out.push_str(&format!("<pre><code>{}</code></pre>\n", escape(&code)));
+            }
+        } else if trimmed.starts_with('#') && trimmed.trim_start_matches('#').starts_with(' ') {
+            flush(&mut para, &mut out);
+            let level = trimmed.len() - trimmed.trim_start_matches('#').len();
+            let text = trimmed[level..].trim();
+            out.push_str(&format!("<h{l} id=\"{}\">{}</h{l}>\n", heading_id(text), inline(text),
+                l = level.min(6)));
+        } else if trimmed.starts_with('|')
+            && lines.get(i + 1).is_some_and(|l| l.trim_start().starts_with("|-")) {
+            flush(&mut para, &mut out);
+            out.push_str("<table>\n<thead><tr>");
+            for cell in cells(trimmed) {
+                out.push_str(&format!("<th>{}</th>", inline(&cell)));
+            }
+            out.push_str("</tr></thead>\n<tbody>\n");
+            i += 2;
+            while i < lines.len() && lines[i].trim_start().starts_with('|') {
+                out.push_str("<tr>");
+                for cell in cells(lines[i].trim_start()) {
+                    out.push_str(&format!("<td>{}</td>", inline(&cell)));
+                }
+                out.push_str("</tr>\n");
+                i += 1;
+            }
+            out.push_str("</tbody>\n</table>\n");
+            continue;
+        } else if let Some(quote) = trimmed.strip_prefix("> ") {
+            flush(&mut para, &mut out);
+            out.push_str(&format!("<blockquote><p>{}</p></blockquote>\n", inline(quote)));
+        } else if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
+            flush(&mut para, &mut out);
+            out.push_str("<ul>\n");
+            while i < lines.len() {
+                let item = lines[i].trim_start();
+                match item.strip_prefix("- ").or_else(|| item.strip_prefix("* ")) {
+                    Some(item) => out.push_str(&format!("<li>{}</li>\n", inline(item))),
+                    None => break,
+                }
+                i += 1;
+            }
+            out.push_str("</ul>\n");
+            continue;
+        } else if trimmed.is_empty() {
+            flush(&mut para, &mut out);
+        } else {
+            para.push(line);
+        }
+        i += 1;
+    }
+    flush(&mut para, &mut out);
+    out
+}
+
+/// The cells of a table row, `\|` doesn't end a cell
+fn cells(row: &str) -> Vec<String> {
+    let row = row.trim().trim_start_matches('|');
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            }
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            c => cell.push(c),
+        }
+    }
+    if !cell.trim().is_empty() {
+        cells.push(cell.trim().to_string());
+    }
+    cells
+}
+
+/// Render the inline markup of a line of text
+fn inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            if let Some(end) = rest[1..].find('`') {
+                out.push_str(&format!("<code>{}</code>", highlight(&rest[1..end + 1])));
+                rest = &rest[end + 2..];
+                continue;
+            }
+        } else if c == '[' {
+            if let Some(link) = parse_link(rest) {
+                let (label, url, len) = link;
+                out.push_str(&format!("<a href=\"{}\">{}</a>", escape(&rewrite_link(url)), inline(label)));
+                rest = &rest[len..];
+                continue;
+            }
+        } else if c == '*' {
+            let (marker, tag) = if rest.starts_with("**") { ("**", "strong") } else { ("*", "em") };
+            let after = &rest[marker.len()..];
+            if !after.starts_with(char::is_whitespace) && !after.is_empty() {
+                if let Some(end) = after.find(marker) {
+                    if end > 0 {
+                        out.push_str(&format!("<{t}>{}</{t}>", inline(&after[..end]), t = tag));
+                        rest = &after[end + marker.len()..];
+                        continue;
+                    }
+                }
+            }
+        } else if c == '\\' && rest[1..].starts_with(|c: char| c.is_ascii_punctuation()) {
+            rest = &rest[1..];
+        }
+        let c = rest.chars().next().unwrap();
+        out.push_str(&escape(c.encode_utf8(&mut [0; 4])));
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// Parse `[label](url)` at the start of `text`, returns the label, url and
+/// the length of the link
+fn parse_link(text: &str) -> Option<(&str, &str, usize)> {
+    let close = text.find("](")?;
+    let label = &text[1..close];
+    if label.contains('[') {
+        return None;
+    }
+    let end = close + 2 + text[close + 2..].find(')')?;
+    Some((label, &text[close + 2..end], end + 1))
+}
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "fun", "function",
+    "goto", "if", "in", "local", "nil", "not", "or", "repeat", "return", "self", "then",
+    "true", "until", "while",
+];
+
+/// Highlight lua code (and the type annotations of the signatures)
+fn highlight(code: &str) -> String {
+    let mut out = String::new();
+    let chars: Vec<char> = code.chars().collect();
+    let mut i = 0;
+    let span = |class: &str, text: &str| format!("<span class=\"{}\">{}</span>", class, escape(text));
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c == '-' && chars.get(i + 1) == Some(&'-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            out.push_str(&span("comment", &chars[start..i].iter().collect::<String>()));
+        } else if c == '"' || c == '\'' {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+            out.push_str(&span("string", &chars[start..i].iter().collect::<String>()));
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            out.push_str(&span("number", &chars[start..i].iter().collect::<String>()));
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_'
+                || (chars[i] == '.' && chars.get(i + 1).is_some_and(|c| c.is_alphabetic()))) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if KEYWORDS.contains(&word.as_str()) {
+                out.push_str(&span("keyword", &word));
+            } else if word.starts_with(char::is_uppercase) {
+                out.push_str(&span("type", &word));
+            } else {
+                out.push_str(&escape(&word));
+            }
+        } else {
+            i += 1;
+            out.push_str(&escape(c.encode_utf8(&mut [0; 4])));
+        }
+    }
+    out
+}
+
+const STYLE: &str = r#"body {
+    margin: 0;
+    font-family: sans-serif;
+    line-height: 1.5;
+    color: #222;
+}
+header {
+    display: flex;
+    align-items: center;
+    gap: 1em;
+    padding: 0.5em 1em;
+    background: #2d3748;
+}
+header a.home {
+    color: #fff;
+    font-weight: bold;
+    text-decoration: none;
+}
+.search {
+    position: relative;
+    flex: 1;
+}
+#search {
+    width: 100%;
+    max-width: 30em;
+    padding: 0.3em;
+}
+#results {
+    position: absolute;
+    z-index: 1;
+    margin: 0;
+    padding: 0;
+    list-style: none;
+    background: #fff;
+    box-shadow: 0 2px 6px rgba(0, 0, 0, 0.3);
+    max-height: 70vh;
+    overflow-y: auto;
+}
+#results li {
+    padding: 0.2em 0.5em;
+}
+#results .kind {
+    color: #718096;
+    font-size: 0.8em;
+    margin-left: 0.5em;
+}
+main {
+    max-width: 60em;
+    margin: 0 auto;
+    padding: 1em;
+}
+table {
+    border-collapse: collapse;
+    margin-bottom: 1em;
+}
+th, td {
+    border: 1px solid #cbd5e0;
+    padding: 0.3em 0.6em;
+    text-align: left;
+    vertical-align: top;
+}
+pre {
+    background: #f7fafc;
+    border: 1px solid #e2e8f0;
+    padding: 0.6em;
+    overflow-x: auto;
+}
+code {
+    font-family: monospace;
+}
+blockquote {
+    border-left: 4px solid #e53e3e;
+    margin-left: 0;
+    padding-left: 1em;
+}
+.keyword { color: #805ad5; }
+.type { color: #2b6cb0; }
+.string { color: #2f855a; }
+.number { color: #c05621; }
+.comment { color: #718096; }
+"#;
+
+const SEARCH: &str = r#"(function () {
+    const root = document.currentScript.dataset.root;
+    const input = document.getElementById("search");
+    const results = document.getElementById("results");
+    let index = null;
+
+    function load() {
+        if (index === null) {
+            index = new Promise((resolve, reject) => {
+                const script = document.createElement("script");
+                script.src = root + "search-index.js";
+                script.onload = () => resolve(window.searchIndex);
+                script.onerror = reject;
+                document.head.append(script);
+            });
+        }
+        return index;
+    }
+
+    function show(entries, query) {
+        results.replaceChildren();
+        if (query === "") {
+            return;
+        }
+        const words = query.toLowerCase().split(/\s+/);
+        const matches = entries
+            .filter(e => words.every(w => e.name.toLowerCase().includes(w)))
+            .sort((a, b) => a.name.length - b.name.length)
+            .slice(0, 50);
+        for (const entry of matches) {
+            const li = document.createElement("li");
+            const a = document.createElement("a");
+            a.href = root + entry.path;
+            a.textContent = entry.name;
+            a.title = entry.summary;
+            const kind = document.createElement("span");
+            kind.className = "kind";
+            kind.textContent = entry.kind;
+            li.append(a, kind);
+            results.append(li);
+        }
+    }
+
+    input.addEventListener("input", () => {
+        load().then(entries => show(entries, input.value.trim()));
+    });
+    input.addEventListener("keydown", e => {
+        if (e.key === "Escape") {
+            input.value = "";
+            results.replaceChildren();
+        }
+    });
+})();
+"#;
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use super::Html;
+    use crate::lang::Generator;
+    use crate::resolve::{read_repository, Resolver};
+    use crate::sink::MemorySink;
+
+    #[test]
+    fn search_index_works() {
+        let repo = Arc::new(read_repository("testdata/girs/GMime-3.0.gir").unwrap());
+        let html = Html::new();
+        let sink = MemorySink::new();
+        html.generate(&repo, &sink).unwrap();
+        html.finish(&sink).unwrap();
+
+        // a script, so it can be loaded from file:// urls
+        let index = String::from_utf8(sink.get(Path::new("search-index.js")).unwrap()).unwrap();
+        let json = index.strip_prefix("window.searchIndex = ").unwrap().trim_end().strip_suffix(';').unwrap();
+        let entries: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert!(entries.as_array().unwrap().iter().any(|e| e["name"] == "GMime.Message"));
+
+        // the same entries as json
+        let json: serde_json::Value = serde_json::from_slice(&sink.get(Path::new("search-index.json")).unwrap()).unwrap();
+        assert_eq!(json, entries);
+        let message = json.as_array().unwrap().iter().find(|e| e["name"] == "GMime.Message").unwrap();
+        assert_eq!(message["kind"], "class");
+        assert_eq!(message["path"], "GMime-3.0/Message.html");
+    }
+
+    #[test]
+    fn versions_works() {
+        let resolver = Arc::new(Resolver::new(vec![PathBuf::from("testdata/girs")]));
+        let html = Html::new().resolver(resolver.clone());
+        let repos: Vec<_> = ["Toolkit-3.0.gir", "Toolkit-4.0.gir"].iter()
+            .map(|f| resolver.load_file(&format!("testdata/girs/{}", f)).unwrap())
+            .collect();
+        html.prepare(&repos);
+        let sink = MemorySink::new();
+        for repo in repos.iter() {
+            html.generate(repo, &sink).unwrap();
+        }
+        html.finish(&sink).unwrap();
+
+        // each version has its own pages and search entries
+        let index = String::from_utf8(sink.get(Path::new("index.html")).unwrap()).unwrap();
+        for dir in ["Toolkit-3.0", "Toolkit-4.0"] {
+            assert!(sink.get(Path::new(&format!("{}/Button.html", dir))).is_some());
+            assert!(index.contains(&format!("href=\"{}/index.html\"", dir)));
+            assert!(html.search_index().iter().any(|e| e.path == format!("{}/Button.html", dir)));
+        }
+    }
+}
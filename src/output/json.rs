@@ -0,0 +1,56 @@
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+
+use crate::lang::Generator;
+use crate::library::Repository;
+use crate::sink::Sink;
+
+/// Dumps the parsed repository as json, as `<Namespace>-<version>.json`.
+/// The json has the same shape as the types in `library`, so it can be
+/// read back with serde.
+pub struct Json {}
+
+impl Json {
+    pub fn new() -> Json {
+        Json {}
+    }
+}
+
+impl Default for Json {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator for Json {
+    fn default_dir(&self) -> &'static str {
+        "json"
+    }
+
+    fn generate(&self, repo: &Arc<Repository>, sink: &dyn Sink) -> Result<()> {
+        let ns = repo.namespace.first().context("Repository has no namespace")?;
+        let name = ns.name.as_ref().context("Failed to read name")?;
+        let filename = match ns.version {
+            Some(ref version) => format!("{}-{}.json", name, version),
+            None => format!("{}.json", name),
+        };
+        let json = serde_json::to_vec_pretty(repo.as_ref())?;
+        sink.write(Path::new(&filename), &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::library::Repository;
+    use crate::parse::parse_gir;
+
+    #[test]
+    fn roundtrip_works() {
+        let f = std::fs::File::open("testdata/girs/GMime-3.0.gir").unwrap();
+        let repo = parse_gir(std::io::BufReader::new(f)).unwrap();
+        let json = serde_json::to_string(&repo).unwrap();
+        let back: Repository = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::to_string(&back).unwrap());
+    }
+}
@@ -0,0 +1,506 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+
+use crate::library::*;
+use crate::resolve::{Resolver, Scope, split_name};
+use crate::lang::Generator;
+use crate::sink::Sink;
+use crate::lang::lua::{show_anytyp, in_param, lua_params, signature};
+use super::{luafy, summary, Versions};
+
+const SECTION: &str = "3lua";
+
+/// Generates section 3 man pages in man(7) format, one for every namespace
+/// (`Gtk-4.0.3lua`) and one for every class, interface and record
+/// (`Gtk-4.0.Widget.3lua`). The pages are written to a `man3` directory so the
+/// output directory can be added to `MANPATH`.
+pub struct Man {
+    resolver: Arc<Resolver>,
+}
+
+impl Man {
+    pub fn new() -> Man {
+        Man {
+            resolver: Arc::new(Resolver::default()),
+        }
+    }
+
+    /// Look up types from other namespaces in `resolver`
+    pub fn resolver(mut self, resolver: Arc<Resolver>) -> Man {
+        self.resolver = resolver;
+        self
+    }
+}
+
+impl Default for Man {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator for Man {
+    fn default_dir(&self) -> &'static str {
+        "man"
+    }
+
+    fn generate(&self, repo: &Arc<Repository>, sink: &dyn Sink) -> Result<()> {
+        self.resolver.insert(repo);
+        let scope = self.resolver.scope(repo);
+        let versions = Versions::new(repo);
+        let dir = Path::new("man3");
+        for ns in repo.namespace.iter() {
+            let name = ns.name.as_ref().context("Failed to read name")?;
+            let page = versions.get(name);
+
+            let mut w = vec![];
+            write_namespace(ns, name, &versions, &mut w)?;
+            sink.write(&dir.join(format!("{}.{}", page, SECTION)), &w)?;
+
+            for class in ns.classes.iter().filter(|c| c.info.is_introspectable()) {
+                let mut w = vec![];
+                write_class(class, name, &scope, &versions, &mut w)?;
+                sink.write(&dir.join(format!("{}.{}.{}", page, class.name, SECTION)), &w)?;
+            }
+            for interface in ns.interfaces.iter().filter(|i| i.info.is_introspectable()) {
+                let mut w = vec![];
+                write_interface(interface, name, &versions, &mut w)?;
+                sink.write(&dir.join(format!("{}.{}.{}", page, interface.name, SECTION)), &w)?;
+            }
+            for record in ns.record.iter()
+                .filter(|r| r.info.is_introspectable() && r.glib_is_gtype_struct_for.is_none()) {
+                let mut w = vec![];
+                write_record(record, name, &versions, &mut w)?;
+                sink.write(&dir.join(format!("{}.{}.{}", page, record.name, SECTION)), &w)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escape text so troff doesn't interpret it
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    // a line starting with . or ' would be a request
+    text.lines()
+        .map(|line| {
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{}", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Turn inline markup of the docs into fonts: `code` is bold and
+/// @param is italic
+fn inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut code = false;
+    for (i, part) in text.split('`').enumerate() {
+        if i > 0 {
+            out.push_str(if code { "\\fR" } else { "\\fB" });
+            code = !code;
+        }
+        out.push_str(part);
+    }
+    if code {
+        out.push_str("\\fR");
+    }
+    out
+}
+
+/// Convert the docs of an element to paragraphs, code blocks (``` or |[ ]|)
+/// are kept as they are
+fn write_doc<W: Write>(doc: &InfoElements, w: &mut W) -> Result<()> {
+    let doc = match doc.doc {
+        Some(ref doc) => luafy(&doc.content),
+        None => return Ok(()),
+    };
+    let mut para: Vec<String> = vec![];
+    let mut in_code = false;
+    for line in doc.lines() {
+        let trimmed = line.trim();
+        if !in_code && (trimmed.starts_with("```") || trimmed.starts_with("|[")) {
+            write_para(&mut para, w)?;
+            writeln!(w, ".PP\n.RS\n.nf")?;
+            in_code = true;
+        } else if in_code && (trimmed.starts_with("```") || trimmed.starts_with("]|")) {
+            writeln!(w, ".fi\n.RE")?;
+            in_code = false;
+        } else if in_code {
+            writeln!(w, "{}", escape(line))?;
+        } else if trimmed.is_empty() {
+            write_para(&mut para, w)?;
+        } else {
+            para.push(trimmed.to_string());
+        }
+    }
+    if in_code {
+        writeln!(w, ".fi\n.RE")?;
+    }
+    write_para(&mut para, w)?;
+    Ok(())
+}
+
+fn write_para<W: Write>(para: &mut Vec<String>, w: &mut W) -> Result<()> {
+    if para.is_empty() {
+        return Ok(())
+    }
+    let text = escape(&para.join("\n"));
+    let text = text.split(' ')
+        .map(|word| match word.strip_prefix('@') {
+            Some(param) if !param.is_empty() => format!("\\fI{}\\fR", param),
+            _ => word.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+    writeln!(w, ".PP\n{}", inline(&text))?;
+    para.clear();
+    Ok(())
+}
+
+fn write_info<W: Write>(info: &InfoAttrs, w: &mut W) -> Result<()> {
+    if let Some(true) = info.deprecated {
+        match info.deprecated_version {
+            Some(ref version) => writeln!(w, ".PP\n\\fBDeprecated\\fR since {}.", escape(version))?,
+            None => writeln!(w, ".PP\n\\fBDeprecated\\fR.")?,
+        }
+    }
+    if let Some(ref version) = info.version {
+        writeln!(w, ".PP\nSince {}.", escape(version))?;
+    }
+    Ok(())
+}
+
+/// The title is the name of the page, with the version, the NAME section
+/// has the name as in lua
+fn write_header<W: Write>(title: &str, ns: &str, summary: &str, versions: &Versions, w: &mut W) -> Result<()> {
+    writeln!(w, ".TH \"{}\" \"{}\" \"\" \"gir-to-stub\" \"{} Lua API\"",
+        versions.qualify(title), SECTION, versions.get(ns))?;
+    writeln!(w, ".SH NAME")?;
+    if summary.is_empty() {
+        writeln!(w, "{}", escape(title))?;
+    } else {
+        writeln!(w, "{} \\- {}", escape(title), inline(&escape(summary)))?;
+    }
+    Ok(())
+}
+
+/// The first paragraph of the docs as the body of a tagged paragraph
+fn write_summary<W: Write>(doc: &InfoElements, w: &mut W) -> Result<()> {
+    let summary = summary(doc);
+    if !summary.is_empty() {
+        writeln!(w, "{}", inline(&escape(&summary)))?;
+    }
+    Ok(())
+}
+
+/// A reference to the man page of another type, for SEE ALSO
+fn reference(typ: &str, ns: &str, versions: &Versions) -> String {
+    let (ns, name) = split_name(typ, ns);
+    format!(".BR {}.{} ({})", versions.get(ns), name, SECTION)
+}
+
+fn write_see_also<W: Write>(refs: &[String], w: &mut W) -> Result<()> {
+    if refs.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, ".SH SEE ALSO")?;
+    let last = refs.len() - 1;
+    for (i, r) in refs.iter().enumerate() {
+        if i == last {
+            writeln!(w, "{}", r)?;
+        } else {
+            writeln!(w, "{},", r)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_synopsis<W: Write>(ns: &str, lists: &[&[Function]], static_ns: &str, w: &mut W) -> Result<()> {
+    writeln!(w, ".SH SYNOPSIS")?;
+    writeln!(w, ".nf")?;
+    writeln!(w, "local {} = require('lgi').require('{}')", ns, ns)?;
+    let funs: Vec<&Function> = lists.iter()
+        .flat_map(|l| l.iter())
+        .filter(|f| f.info.is_introspectable())
+        .collect();
+    if !funs.is_empty() {
+        writeln!(w)?;
+    }
+    for fun in funs {
+        writeln!(w, "{}", escape(&signature(fun, static_ns, ns)))?;
+    }
+    writeln!(w, ".fi")?;
+    Ok(())
+}
+
+/// A section with an entry per function: its signature, docs and parameters
+fn write_functions<W: Write>(title: &str, funs: &[Function], static_ns: &str, ns: &str, w: &mut W) -> Result<()> {
+    let funs: Vec<&Function> = funs.iter().filter(|f| f.info.is_introspectable()).collect();
+    if funs.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, ".SH {}", title)?;
+    for fun in funs {
+        writeln!(w, ".TP\n.B \"{}\"", escape(&signature(fun, static_ns, ns)).replace('"', "\"\""))?;
+        let mut body = vec![];
+        write_info(&fun.info, &mut body)?;
+        write_doc(&fun.doc, &mut body)?;
+        for param in lua_params(&fun.parameters, fun.ret.as_ref()).into_iter().filter(|p| in_param(&p.direction)) {
+            let doc = summary(&param.doc);
+            if !doc.is_empty() {
+                writeln!(body, ".PP\n\\fI{}\\fR: {}", escape(&param.name), inline(&escape(&doc)))?;
+            }
+        }
+        // the first paragraph belongs to the tag
+        let body = String::from_utf8(body)?;
+        w.write_all(body.strip_prefix(".PP\n").unwrap_or(&body).as_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_properties<W: Write>(props: &[Property], ns: &str, w: &mut W) -> Result<()> {
+    let props: Vec<&Property> = props.iter().filter(|p| p.info.is_introspectable()).collect();
+    if props.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, ".SH PROPERTIES")?;
+    for prop in props {
+        let mut access = vec![];
+        if prop.readable {
+            access.push("read");
+        }
+        if prop.writable && !prop.construct_only {
+            access.push("write");
+        }
+        if prop.construct || prop.construct_only {
+            access.push("construct");
+        }
+        writeln!(w, ".TP\n.BR {} \" ({}, {})\"", escape(&prop.name.replace('-', "_")),
+            escape(&show_anytyp(&prop.typ, ns)), access.join(", "))?;
+        write_summary(&prop.doc, w)?;
+    }
+    Ok(())
+}
+
+fn write_signals<W: Write>(signals: &[Signal], ns: &str, w: &mut W) -> Result<()> {
+    let signals: Vec<&Signal> = signals.iter().filter(|s| s.info.is_introspectable()).collect();
+    if signals.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, ".SH SIGNALS")?;
+    for signal in signals {
+        let mut params = vec!["self".to_string()];
+        params.extend(lua_params(&signal.parameters, signal.ret.as_ref())
+            .into_iter()
+            .filter(|p| in_param(&p.direction))
+            .map(|p| format!("{}: {}", p.name, show_anytyp(&p.typ, ns))));
+        let mut handler = format!("fun({})", params.join(", "));
+        if let Some(ref ret) = signal.ret {
+            let ret = show_anytyp(&ret.typ, ns);
+            if ret != "nil" {
+                handler = format!("{}: {}", handler, ret);
+            }
+        }
+        writeln!(w, ".TP\n.BR \"{}\" \" {}\"", escape(&signal.name), escape(&handler))?;
+        write_summary(&signal.doc, w)?;
+    }
+    Ok(())
+}
+
+fn write_fields<W: Write>(fields: &[Field], ns: &str, w: &mut W) -> Result<()> {
+    let fields: Vec<&Field> = fields.iter().filter(|f| !f.private).collect();
+    if fields.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, ".SH FIELDS")?;
+    for field in fields {
+        writeln!(w, ".TP\n.BR {} \" ({})\"", escape(&field.name), escape(&show_anytyp(&field.typ, ns)))?;
+        write_summary(&field.doc, w)?;
+    }
+    Ok(())
+}
+
+fn write_namespace<W: Write>(ns: &Namespace, name: &str, versions: &Versions, w: &mut W) -> Result<()> {
+    write_header(name, name, "", versions, w)?;
+    write_synopsis(name, &[&ns.functions], name, w)?;
+
+    writeln!(w, ".SH DESCRIPTION")?;
+    match ns.version {
+        Some(ref version) => writeln!(w, "The {} {} namespace.", name, escape(version))?,
+        None => writeln!(w, "The {} namespace.", name)?,
+    }
+
+    write_functions("FUNCTIONS", &ns.functions, name, name, w)?;
+
+    let enums = ns.enums.iter()
+        .map(|e| (e.name.as_str(), &e.info, &e.doc, e.members.as_slice()))
+        .chain(ns.bitfield.iter().map(|b| (b.name.as_str(), &b.info, &b.doc, b.members.as_slice())))
+        .filter(|e| e.1.is_introspectable())
+        .collect::<Vec<_>>();
+    if !enums.is_empty() {
+        writeln!(w, ".SH ENUMS")?;
+        for (ename, _, doc, members) in enums {
+            writeln!(w, ".SS {}.{}", name, ename)?;
+            write_doc(doc, w)?;
+            for member in members.iter().filter(|m| m.info.is_introspectable()) {
+                writeln!(w, ".TP\n.BR {}.{}.{} \" = {}\"", name, ename,
+                    escape(&member.name.to_uppercase()), escape(&member.value))?;
+                write_summary(&member.doc, w)?;
+            }
+        }
+    }
+
+    let constants: Vec<&Constant> = ns.constant.iter().filter(|c| c.info.is_introspectable()).collect();
+    if !constants.is_empty() {
+        writeln!(w, ".SH CONSTANTS")?;
+        for constant in constants {
+            writeln!(w, ".TP\n.BR {}.{} \" = {}\"", name, escape(&constant.name), escape(&constant.value))?;
+            write_summary(&constant.doc, w)?;
+        }
+    }
+
+    let mut refs = vec![];
+    refs.extend(ns.classes.iter().filter(|c| c.info.is_introspectable()).map(|c| reference(&c.name, name, versions)));
+    refs.extend(ns.interfaces.iter().filter(|i| i.info.is_introspectable()).map(|i| reference(&i.name, name, versions)));
+    refs.extend(ns.record.iter()
+        .filter(|r| r.info.is_introspectable() && r.glib_is_gtype_struct_for.is_none())
+        .map(|r| reference(&r.name, name, versions)));
+    write_see_also(&refs, w)?;
+    Ok(())
+}
+
+fn write_class<W: Write>(class: &Class, ns: &str, scope: &Scope, versions: &Versions, w: &mut W) -> Result<()> {
+    let static_ns = format!("{}.{}", ns, class.name);
+    write_header(&static_ns, ns, &summary(&class.doc), versions, w)?;
+    write_synopsis(ns, &[&class.constructor, &class.method, &class.functions], &static_ns, w)?;
+
+    writeln!(w, ".SH DESCRIPTION")?;
+    let ancestors = scope.ancestors(&class.name, ns);
+    if let Some(ref parent) = class.parent {
+        let (pns, pname) = split_name(parent, ns);
+        writeln!(w, ".PP\nInherits from \\fB{}.{}\\fR.", pns, pname)?;
+    }
+    write_info(&class.info, w)?;
+    write_doc(&class.doc, w)?;
+
+    write_functions("CONSTRUCTORS", &class.constructor, &static_ns, ns, w)?;
+    write_functions("METHODS", &class.method, &static_ns, ns, w)?;
+    write_functions("FUNCTIONS", &class.functions, &static_ns, ns, w)?;
+    write_properties(&class.properties, ns, w)?;
+    write_signals(&class.signals, ns, w)?;
+    write_fields(&class.fields, ns, w)?;
+
+    // the parent might be in a namespace that couldn't be loaded
+    let mut refs: Vec<String> = ancestors.iter().map(|a| reference(a, ns, versions)).collect();
+    if refs.is_empty() {
+        if let Some(ref parent) = class.parent {
+            refs.push(reference(parent, ns, versions));
+        }
+    }
+    refs.extend(class.implements.iter().map(|i| reference(&i.name, ns, versions)));
+    refs.push(format!(".BR {} ({})", versions.get(ns), SECTION));
+    write_see_also(&refs, w)?;
+    Ok(())
+}
+
+fn write_interface<W: Write>(interface: &Interface, ns: &str, versions: &Versions, w: &mut W) -> Result<()> {
+    let static_ns = format!("{}.{}", ns, interface.name);
+    write_header(&static_ns, ns, &summary(&interface.doc), versions, w)?;
+    write_synopsis(ns, &[&interface.method, &interface.functions], &static_ns, w)?;
+
+    writeln!(w, ".SH DESCRIPTION")?;
+    write_info(&interface.info, w)?;
+    write_doc(&interface.doc, w)?;
+
+    write_functions("METHODS", &interface.method, &static_ns, ns, w)?;
+    write_functions("FUNCTIONS", &interface.functions, &static_ns, ns, w)?;
+    write_properties(&interface.properties, ns, w)?;
+    write_signals(&interface.signals, ns, w)?;
+
+    let mut refs: Vec<String> = interface.prerequisites.iter().map(|p| reference(p, ns, versions)).collect();
+    refs.push(format!(".BR {} ({})", versions.get(ns), SECTION));
+    write_see_also(&refs, w)?;
+    Ok(())
+}
+
+fn write_record<W: Write>(record: &Record, ns: &str, versions: &Versions, w: &mut W) -> Result<()> {
+    let static_ns = format!("{}.{}", ns, record.name);
+    write_header(&static_ns, ns, &summary(&record.doc), versions, w)?;
+    write_synopsis(ns, &[&record.constructor, &record.method, &record.functions], &static_ns, w)?;
+
+    writeln!(w, ".SH DESCRIPTION")?;
+    write_info(&record.info, w)?;
+    write_doc(&record.doc, w)?;
+
+    write_functions("CONSTRUCTORS", &record.constructor, &static_ns, ns, w)?;
+    write_functions("METHODS", &record.method, &static_ns, ns, w)?;
+    write_functions("FUNCTIONS", &record.functions, &static_ns, ns, w)?;
+    write_fields(&record.fields, ns, w)?;
+
+    write_see_also(&[format!(".BR {} ({})", versions.get(ns), SECTION)], w)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use super::Man;
+    use crate::lang::Generator;
+    use crate::resolve::{read_repository, Resolver};
+    use crate::sink::MemorySink;
+
+    /// The lines of `section` in `page`, up to the next section
+    fn section<'a>(page: &'a str, section: &str) -> Vec<&'a str> {
+        page.lines()
+            .skip_while(|l| *l != format!(".SH {}", section))
+            .skip(1)
+            .take_while(|l| !l.starts_with(".SH "))
+            .collect()
+    }
+
+    #[test]
+    fn man_works() {
+        let repo = Arc::new(read_repository("testdata/girs/GMime-3.0.gir").unwrap());
+        let sink = MemorySink::new();
+        Man::new().generate(&repo, &sink).unwrap();
+        assert!(sink.get(Path::new("man3/GMime-3.0.3lua")).is_some());
+        let page = sink.get(Path::new("man3/GMime-3.0.Message.3lua")).unwrap();
+        let page = String::from_utf8(page).unwrap();
+
+        assert!(page.starts_with(".TH \"GMime-3.0.Message\" \"3lua\""));
+        assert_eq!(section(&page, "NAME"), ["GMime.Message \\- A MIME Message object."]);
+        let synopsis = section(&page, "SYNOPSIS");
+        assert_eq!(synopsis[..2], [".nf", "local GMime = require('lgi').require('GMime')"]);
+        assert!(synopsis.contains(&"GMime.Message.new(pretty_headers: boolean): GMime.Message"));
+        assert!(synopsis.contains(&"GMime.Message:set_subject(subject: string, charset: string)"));
+        assert_eq!(synopsis.last(), Some(&".fi"));
+        assert_eq!(section(&page, "SEE ALSO"), [
+            ".BR GMime-3.0.Object (3lua),",
+            ".BR GObject-2.0.Object (3lua),",
+            ".BR GMime-3.0 (3lua)",
+        ]);
+    }
+
+    #[test]
+    fn versions_works() {
+        let resolver = Arc::new(Resolver::new(vec![PathBuf::from("testdata/girs")]));
+        let man = Man::new().resolver(resolver.clone());
+        let sink = MemorySink::new();
+        for file in ["Toolkit-3.0.gir", "Toolkit-4.0.gir"] {
+            let repo = resolver.load_file(&format!("testdata/girs/{}", file)).unwrap();
+            man.generate(&repo, &sink).unwrap();
+        }
+        for version in ["3.0", "4.0"] {
+            let page = sink.get(Path::new(&format!("man3/Toolkit-{}.Button.3lua", version))).unwrap();
+            let page = String::from_utf8(page).unwrap();
+            assert!(page.contains(&format!(".BR Toolkit-{}.Widget (3lua),", version)));
+        }
+    }
+}
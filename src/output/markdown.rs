@@ -0,0 +1,622 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use anyhow::{Result, Context};
+
+use crate::library::*;
+use crate::resolve::{Resolver, Scope, split_name};
+use crate::lang::Generator;
+use crate::sink::Sink;
+use crate::lang::lua::{show_anytyp, translate, translate_ns, in_param, lua_params, signature, callback_type};
+use super::{luafy, summary};
+
+/// A generated documentation page
+pub struct Page {
+    /// Path relative to the output directory, like `Gtk-4.0/Widget.md`
+    pub path: PathBuf,
+    pub title: String,
+    pub markdown: String,
+}
+
+/// Generates a markdown API reference, one page per namespace and one
+/// per class, interface and record. A SUMMARY.md is written so the output
+/// can be used as the src directory of an mdBook.
+pub struct Markdown {
+    resolver: Arc<Resolver>,
+    // the directories of the namespaces that get pages in this run,
+    // the ones links can go to
+    namespaces: Mutex<HashSet<String>>,
+    // the pages of every generated namespace, for the summary
+    summary: Mutex<BTreeMap<String, Vec<(String, PathBuf)>>>,
+}
+
+/// Where types link to: their page if it can be found from the namespace
+/// and is generated in this run
+struct Links<'a> {
+    scope: &'a Scope,
+    /// The directories of the namespaces the scope has, by namespace name
+    dirs: HashMap<String, String>,
+    namespaces: &'a HashSet<String>,
+}
+
+impl Markdown {
+    pub fn new() -> Markdown {
+        Markdown {
+            resolver: Arc::new(Resolver::default()),
+            namespaces: Mutex::new(HashSet::new()),
+            summary: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Look up types from other namespaces in `resolver`
+    pub fn resolver(mut self, resolver: Arc<Resolver>) -> Markdown {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Create the pages for the namespaces of `repo`
+    pub fn pages(&self, repo: &Arc<Repository>) -> Result<Vec<Page>> {
+        self.resolver.insert(repo);
+        let scope = self.resolver.scope(repo);
+        let mut namespaces = self.namespaces.lock().unwrap().clone();
+        namespaces.extend(repo.namespace.iter().filter_map(namespace_dir));
+        let mut dirs: HashMap<String, String> = repo.include.iter()
+            .map(|i| (i.name.clone(), dir_name(&i.name, i.version.as_deref())))
+            .collect();
+        dirs.extend(repo.namespace.iter().filter_map(|ns| Some((ns.name.clone()?, namespace_dir(ns)?))));
+        let links = Links { scope: &scope, dirs, namespaces: &namespaces };
+        let mut pages = vec![];
+        for ns in repo.namespace.iter() {
+            let name = ns.name.as_ref().context("Failed to read name")?;
+            pages.extend(namespace_pages(ns, name, &links)?);
+        }
+        Ok(pages)
+    }
+}
+
+impl Default for Markdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator for Markdown {
+    fn default_dir(&self) -> &'static str {
+        "docs"
+    }
+
+    fn incremental(&self) -> bool {
+        false
+    }
+
+    fn prepare(&self, repos: &[Arc<Repository>]) {
+        let mut namespaces = self.namespaces.lock().unwrap();
+        for repo in repos.iter() {
+            namespaces.extend(repo.namespace.iter().filter_map(namespace_dir));
+        }
+    }
+
+    fn generate(&self, repo: &Arc<Repository>, sink: &dyn Sink) -> Result<()> {
+        let pages = self.pages(repo)?;
+        for page in pages.iter() {
+            sink.write(&page.path, page.markdown.as_bytes())?;
+        }
+        let mut summary = self.summary.lock().unwrap();
+        for page in pages.into_iter() {
+            let ns = page.path.parent()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            summary.entry(ns).or_default().push((page.title, page.path));
+        }
+        Ok(())
+    }
+
+    fn finish(&self, sink: &dyn Sink) -> Result<()> {
+        let summary = self.summary.lock().unwrap();
+        let mut w = vec![];
+        writeln!(w, "# Summary\n")?;
+        for pages in summary.values() {
+            // the namespace page is first, the rest are nested under it
+            for (i, (title, path)) in pages.iter().enumerate() {
+                let indent = if i == 0 { "" } else { "  " };
+                writeln!(w, "{}- [{}]({})", indent, title, path.display())?;
+            }
+        }
+        sink.write(Path::new("SUMMARY.md"), &w)
+    }
+}
+
+/// The directory of the pages of a namespace, named like the gir file,
+/// so two versions of a namespace can be generated together
+pub(crate) fn dir_name(name: &str, version: Option<&str>) -> String {
+    match version {
+        Some(version) => format!("{}-{}", name, version),
+        None => name.to_string(),
+    }
+}
+
+pub(crate) fn namespace_dir(ns: &Namespace) -> Option<String> {
+    Some(dir_name(ns.name.as_ref()?, ns.version.as_deref()))
+}
+
+/// Make `str` safe to put in a table cell
+fn cell(str: &str) -> String {
+    str.replace('|', "\\|").replace('\n', " ")
+}
+
+/// A link to the page of `typ` if there is one, otherwise the type as code.
+/// Links are relative to a page in a namespace directory.
+fn link(typ: &str, ns: &str, links: &Links) -> String {
+    let (tns, name) = split_name(typ, ns);
+    match links.dirs.get(tns) {
+        Some(dir) if links.namespaces.contains(dir) && links.scope.is_compound(typ, ns) =>
+            format!("[{}.{}](../{}/{}.md)", tns, name, dir, name),
+        _ => format!("`{}`", cell(&translate(typ, ns))),
+    }
+}
+
+/// A type rendered as in lua, linking to its page if it's a single type
+fn type_link(typ: &AnyType, ns: &str, links: &Links) -> String {
+    let shown = show_anytyp(typ, ns);
+    if let AnyType::Type(t) = typ {
+        if let Some(ref name) = t.name {
+            if t.children.is_empty() && shown == translate_ns(name, ns) {
+                return link(name, ns, links);
+            }
+        }
+    }
+    format!("`{}`", cell(&shown))
+}
+
+fn write_doc<W: Write>(doc: &InfoElements, w: &mut W) -> Result<()> {
+    if let Some(ref doc) = doc.doc {
+        writeln!(w, "{}\n", luafy(&doc.content))?;
+    }
+    Ok(())
+}
+
+fn write_info<W: Write>(info: &InfoAttrs, w: &mut W) -> Result<()> {
+    if let Some(true) = info.deprecated {
+        if let Some(ref version) = info.deprecated_version {
+            writeln!(w, "> **Deprecated** since {}\n", version)?;
+        } else {
+            writeln!(w, "> **Deprecated**\n")?;
+        }
+    }
+    if let Some(ref version) = info.version {
+        writeln!(w, "*Since {}*\n", version)?;
+    }
+    Ok(())
+}
+
+fn namespace_pages(ns: &Namespace, name: &str, links: &Links) -> Result<Vec<Page>> {
+    let mut pages = vec![];
+    let dir = PathBuf::from(dir_name(name, ns.version.as_deref()));
+
+    let mut w = vec![];
+    write_namespace(ns, name, links, &mut w)?;
+    let title = match ns.version {
+        Some(ref version) => format!("{} {}", name, version),
+        None => name.to_string(),
+    };
+    pages.push(Page {
+        path: dir.join("index.md"),
+        title,
+        markdown: String::from_utf8(w)?,
+    });
+
+    for class in ns.classes.iter().filter(|c| c.info.is_introspectable()) {
+        let mut w = vec![];
+        write_class(class, name, links, &mut w)?;
+        pages.push(Page {
+            path: dir.join(format!("{}.md", class.name)),
+            title: format!("{}.{}", name, class.name),
+            markdown: String::from_utf8(w)?,
+        });
+    }
+    for interface in ns.interfaces.iter().filter(|i| i.info.is_introspectable()) {
+        let mut w = vec![];
+        write_interface(interface, name, links, &mut w)?;
+        pages.push(Page {
+            path: dir.join(format!("{}.md", interface.name)),
+            title: format!("{}.{}", name, interface.name),
+            markdown: String::from_utf8(w)?,
+        });
+    }
+    for record in ns.record.iter()
+        .filter(|r| r.info.is_introspectable() && r.glib_is_gtype_struct_for.is_none()) {
+        let mut w = vec![];
+        write_record(record, name, links, &mut w)?;
+        pages.push(Page {
+            path: dir.join(format!("{}.md", record.name)),
+            title: format!("{}.{}", name, record.name),
+            markdown: String::from_utf8(w)?,
+        });
+    }
+    Ok(pages)
+}
+
+/// A table of the types in a namespace, linking to their pages
+fn write_type_table<W: Write>(title: &str, types: &[(&str, &InfoElements)], w: &mut W) -> Result<()> {
+    if types.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, "## {}\n", title)?;
+    writeln!(w, "| Name | Description |")?;
+    writeln!(w, "|------|-------------|")?;
+    for (name, doc) in types {
+        writeln!(w, "| [{}]({}.md) | {} |", name, name, cell(&summary(doc)))?;
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+fn write_namespace<W: Write>(ns: &Namespace, name: &str, links: &Links, w: &mut W) -> Result<()> {
+    match ns.version {
+        Some(ref version) => writeln!(w, "# {} {}\n", name, version)?,
+        None => writeln!(w, "# {}\n", name)?,
+    }
+    if !ns.classes.is_empty() || !ns.interfaces.is_empty() {
+        writeln!(w, "Include with `local {} = require('lgi').require('{}')`.\n", name, name)?;
+    }
+
+    let classes: Vec<(&str, &InfoElements)> = ns.classes
+        .iter()
+        .filter(|c| c.info.is_introspectable())
+        .map(|c| (c.name.as_str(), &c.doc))
+        .collect();
+    write_type_table("Classes", &classes, w)?;
+    let interfaces: Vec<(&str, &InfoElements)> = ns.interfaces
+        .iter()
+        .filter(|i| i.info.is_introspectable())
+        .map(|i| (i.name.as_str(), &i.doc))
+        .collect();
+    write_type_table("Interfaces", &interfaces, w)?;
+    let records: Vec<(&str, &InfoElements)> = ns.record
+        .iter()
+        .filter(|r| r.info.is_introspectable() && r.glib_is_gtype_struct_for.is_none())
+        .map(|r| (r.name.as_str(), &r.doc))
+        .collect();
+    write_type_table("Records", &records, w)?;
+
+    write_function_table("Functions", &ns.functions, name, name, w)?;
+
+    let enums: Vec<(&str, &InfoAttrs, &InfoElements, &[Member])> = ns.enums
+        .iter()
+        .map(|e| (e.name.as_str(), &e.info, &e.doc, e.members.as_slice()))
+        .collect();
+    write_members("Enums", &enums, name, w)?;
+    let bitfields: Vec<(&str, &InfoAttrs, &InfoElements, &[Member])> = ns.bitfield
+        .iter()
+        .map(|b| (b.name.as_str(), &b.info, &b.doc, b.members.as_slice()))
+        .collect();
+    write_members("Bitfields", &bitfields, name, w)?;
+
+    let constants: Vec<&Constant> = ns.constant.iter().filter(|c| c.info.is_introspectable()).collect();
+    if !constants.is_empty() {
+        writeln!(w, "## Constants\n")?;
+        writeln!(w, "| Name | Type | Value | Description |")?;
+        writeln!(w, "|------|------|-------|-------------|")?;
+        for constant in constants {
+            let typ = constant.typ.as_ref()
+                .map(|t| type_link(t, name, links))
+                .unwrap_or_default();
+            writeln!(w, "| `{}.{}` | {} | `{}` | {} |", name, constant.name, typ,
+                cell(&constant.value), cell(&summary(&constant.doc)))?;
+        }
+        writeln!(w)?;
+    }
+
+    let callbacks: Vec<&Function> = ns.callback.iter().filter(|c| c.info.is_introspectable()).collect();
+    if !callbacks.is_empty() {
+        writeln!(w, "## Callbacks\n")?;
+        writeln!(w, "| Name | Type | Description |")?;
+        writeln!(w, "|------|------|-------------|")?;
+        for callback in callbacks {
+            writeln!(w, "| `{}.{}` | `{}` | {} |", name, callback.name,
+                cell(&callback_type(callback, name)), cell(&summary(&callback.doc)))?;
+        }
+        writeln!(w)?;
+    }
+
+    if !ns.alias.is_empty() {
+        writeln!(w, "## Aliases\n")?;
+        writeln!(w, "| Name | Type |")?;
+        writeln!(w, "|------|------|")?;
+        for alias in ns.alias.iter() {
+            writeln!(w, "| `{}.{}` | {} |", name, alias.name, type_link(&alias.typ, name, links))?;
+        }
+        writeln!(w)?;
+    }
+
+    write_function_details("Functions", &ns.functions, name, name, links, w)?;
+    Ok(())
+}
+
+type MemberList<'a> = (&'a str, &'a InfoAttrs, &'a InfoElements, &'a [Member]);
+
+fn write_members<W: Write>(title: &str, types: &[MemberList], ns: &str, w: &mut W) -> Result<()> {
+    let types: Vec<&MemberList> = types.iter().filter(|t| t.1.is_introspectable()).collect();
+    if types.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, "## {}\n", title)?;
+    for (name, info, doc, members) in types {
+        writeln!(w, "### {}.{}\n", ns, name)?;
+        write_info(info, w)?;
+        write_doc(doc, w)?;
+        writeln!(w, "| Value | Number | Description |")?;
+        writeln!(w, "|-------|--------|-------------|")?;
+        for member in members.iter().filter(|m| m.info.is_introspectable()) {
+            writeln!(w, "| `{}.{}.{}` | {} | {} |", ns, name, member.name.to_uppercase(),
+                member.value, cell(&summary(&member.doc)))?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// The anchor of the heading for a function
+pub(crate) fn anchor(name: &str) -> String {
+    name.to_lowercase()
+}
+
+fn write_function_table<W: Write>(title: &str, funs: &[Function], static_ns: &str, ns: &str, w: &mut W) -> Result<()> {
+    let funs: Vec<&Function> = funs.iter().filter(|f| f.info.is_introspectable()).collect();
+    if funs.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, "## {}\n", title)?;
+    writeln!(w, "| Name | Signature | Description |")?;
+    writeln!(w, "|------|-----------|-------------|")?;
+    for fun in funs {
+        writeln!(w, "| [{}](#{}) | `{}` | {} |", fun.name, anchor(&fun.name),
+            cell(&signature(fun, static_ns, ns)), cell(&summary(&fun.doc)))?;
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+fn write_function_details<W: Write>(title: &str, funs: &[Function], static_ns: &str, ns: &str,
+    links: &Links, w: &mut W) -> Result<()> {
+    let funs: Vec<&Function> = funs.iter().filter(|f| f.info.is_introspectable()).collect();
+    if funs.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, "## {} details\n", title)?;
+    for fun in funs {
+        writeln!(w, "### {}\n", fun.name)?;
+        writeln!(w, "```lua\n{}\n```\n", signature(fun, static_ns, ns))?;
+        write_info(&fun.info, w)?;
+        write_doc(&fun.doc, w)?;
+        let params: Vec<&Parameter> = lua_params(&fun.parameters, fun.ret.as_ref())
+            .into_iter()
+            .filter(|p| in_param(&p.direction))
+            .collect();
+        if !params.is_empty() {
+            writeln!(w, "| Parameter | Type | Description |")?;
+            writeln!(w, "|-----------|------|-------------|")?;
+            for param in params {
+                writeln!(w, "| `{}` | {} | {} |", param.name,
+                    type_link(&param.typ, ns, links), cell(&summary(&param.doc)))?;
+            }
+            writeln!(w)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_properties<W: Write>(props: &[Property], ns: &str, links: &Links, w: &mut W) -> Result<()> {
+    let props: Vec<&Property> = props.iter().filter(|p| p.info.is_introspectable()).collect();
+    if props.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, "## Properties\n")?;
+    writeln!(w, "| Name | Type | Access | Description |")?;
+    writeln!(w, "|------|------|--------|-------------|")?;
+    for prop in props {
+        let mut access = vec![];
+        if prop.readable {
+            access.push("read");
+        }
+        if prop.writable && !prop.construct_only {
+            access.push("write");
+        }
+        if prop.construct || prop.construct_only {
+            access.push("construct");
+        }
+        writeln!(w, "| `{}` | {} | {} | {} |", prop.name.replace('-', "_"),
+            type_link(&prop.typ, ns, links), access.join(", "), cell(&summary(&prop.doc)))?;
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+fn write_signals<W: Write>(signals: &[Signal], ns: &str, w: &mut W) -> Result<()> {
+    let signals: Vec<&Signal> = signals.iter().filter(|s| s.info.is_introspectable()).collect();
+    if signals.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, "## Signals\n")?;
+    writeln!(w, "| Name | Handler | Description |")?;
+    writeln!(w, "|------|---------|-------------|")?;
+    for signal in signals {
+        let params: Vec<String> = lua_params(&signal.parameters, signal.ret.as_ref())
+            .into_iter()
+            .filter(|p| in_param(&p.direction))
+            .map(|p| format!("{}: {}", p.name, show_anytyp(&p.typ, ns)))
+            .collect();
+        let mut handler = format!("fun(self, {})", params.join(", "));
+        if params.is_empty() {
+            handler = "fun(self)".to_string();
+        }
+        if let Some(ref ret) = signal.ret {
+            let ret = show_anytyp(&ret.typ, ns);
+            if ret != "nil" {
+                handler = format!("{}: {}", handler, ret);
+            }
+        }
+        writeln!(w, "| `{}` | `{}` | {} |", signal.name, cell(&handler), cell(&summary(&signal.doc)))?;
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+fn write_fields<W: Write>(fields: &[Field], ns: &str, links: &Links, w: &mut W) -> Result<()> {
+    let fields: Vec<&Field> = fields.iter().filter(|f| !f.private).collect();
+    if fields.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, "## Fields\n")?;
+    writeln!(w, "| Name | Type | Description |")?;
+    writeln!(w, "|------|------|-------------|")?;
+    for field in fields {
+        writeln!(w, "| `{}` | {} | {} |", field.name,
+            type_link(&field.typ, ns, links), cell(&summary(&field.doc)))?;
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+fn write_class<W: Write>(class: &Class, ns: &str, links: &Links, w: &mut W) -> Result<()> {
+    let static_ns = format!("{}.{}", ns, class.name);
+    writeln!(w, "# {}\n", static_ns)?;
+
+    let mut chain: Vec<String> = links.scope.ancestors(&class.name, ns)
+        .iter()
+        .rev()
+        .map(|a| link(a, ns, links))
+        .collect();
+    // the parent might be in a namespace that couldn't be loaded
+    if chain.is_empty() {
+        if let Some(ref parent) = class.parent {
+            chain.push(link(parent, ns, links));
+        }
+    }
+    if !chain.is_empty() {
+        chain.push(format!("**{}**", static_ns));
+        writeln!(w, "Inheritance: {}\n", chain.join(" → "))?;
+    }
+    if !class.implements.is_empty() {
+        let implements: Vec<String> = class.implements
+            .iter()
+            .map(|i| link(&i.name, ns, links))
+            .collect();
+        writeln!(w, "Implements: {}\n", implements.join(", "))?;
+    }
+    write_info(&class.info, w)?;
+    write_doc(&class.doc, w)?;
+
+    write_function_table("Constructors", &class.constructor, &static_ns, ns, w)?;
+    write_function_table("Methods", &class.method, &static_ns, ns, w)?;
+    write_function_table("Functions", &class.functions, &static_ns, ns, w)?;
+    write_properties(&class.properties, ns, links, w)?;
+    write_signals(&class.signals, ns, w)?;
+    write_fields(&class.fields, ns, links, w)?;
+
+    write_function_details("Constructors", &class.constructor, &static_ns, ns, links, w)?;
+    write_function_details("Methods", &class.method, &static_ns, ns, links, w)?;
+    write_function_details("Functions", &class.functions, &static_ns, ns, links, w)?;
+    Ok(())
+}
+
+fn write_interface<W: Write>(interface: &Interface, ns: &str, links: &Links, w: &mut W) -> Result<()> {
+    let static_ns = format!("{}.{}", ns, interface.name);
+    writeln!(w, "# {}\n", static_ns)?;
+    if !interface.prerequisites.is_empty() {
+        let prereqs: Vec<String> = interface.prerequisites
+            .iter()
+            .map(|p| link(p, ns, links))
+            .collect();
+        writeln!(w, "Prerequisites: {}\n", prereqs.join(", "))?;
+    }
+    write_info(&interface.info, w)?;
+    write_doc(&interface.doc, w)?;
+
+    write_function_table("Methods", &interface.method, &static_ns, ns, w)?;
+    write_function_table("Functions", &interface.functions, &static_ns, ns, w)?;
+    write_properties(&interface.properties, ns, links, w)?;
+    write_signals(&interface.signals, ns, w)?;
+
+    write_function_details("Methods", &interface.method, &static_ns, ns, links, w)?;
+    write_function_details("Functions", &interface.functions, &static_ns, ns, links, w)?;
+    Ok(())
+}
+
+fn write_record<W: Write>(record: &Record, ns: &str, links: &Links, w: &mut W) -> Result<()> {
+    let static_ns = format!("{}.{}", ns, record.name);
+    writeln!(w, "# {}\n", static_ns)?;
+    write_info(&record.info, w)?;
+    write_doc(&record.doc, w)?;
+
+    write_function_table("Constructors", &record.constructor, &static_ns, ns, w)?;
+    write_function_table("Methods", &record.method, &static_ns, ns, w)?;
+    write_function_table("Functions", &record.functions, &static_ns, ns, w)?;
+    write_fields(&record.fields, ns, links, w)?;
+
+    write_function_details("Constructors", &record.constructor, &static_ns, ns, links, w)?;
+    write_function_details("Methods", &record.method, &static_ns, ns, links, w)?;
+    write_function_details("Functions", &record.functions, &static_ns, ns, links, w)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use super::{link, Links, Markdown};
+    use crate::lang::Generator;
+    use crate::resolve::{Resolver, Scope};
+    use crate::sink::MemorySink;
+
+    /// Generates the markdown of `files`
+    fn generate(files: &[&str]) -> MemorySink {
+        let resolver = Arc::new(Resolver::new(vec![PathBuf::from("testdata/girs")]));
+        let gen = Markdown::new().resolver(resolver.clone());
+        let repos: Vec<_> = files.iter()
+            .map(|f| resolver.load_file(&format!("testdata/girs/{}", f)).unwrap())
+            .collect();
+        gen.prepare(&repos);
+        let sink = MemorySink::new();
+        for repo in repos.iter() {
+            gen.generate(repo, &sink).unwrap();
+        }
+        gen.finish(&sink).unwrap();
+        sink
+    }
+
+    /// The inheritance line of Toolkit.Button, generated with `files`
+    fn inheritance(files: &[&str]) -> String {
+        let sink = generate(files);
+        let page = String::from_utf8(sink.get(Path::new("Toolkit-4.0/Button.md")).unwrap()).unwrap();
+        page.lines().find(|l| l.starts_with("Inheritance")).unwrap().to_string()
+    }
+
+    #[test]
+    fn links_works() {
+        // only namespaces with pages are linked
+        assert_eq!(inheritance(&["Toolkit-4.0.gir"]),
+            "Inheritance: `GObject.Object` → `GObject.InitiallyUnowned` → [Toolkit.Widget](../Toolkit-4.0/Widget.md) → **Toolkit.Button**");
+        assert_eq!(inheritance(&["GObject-2.0.gir", "Toolkit-4.0.gir"]),
+            "Inheritance: [GObject.Object](../GObject-2.0/Object.md) → [GObject.InitiallyUnowned](../GObject-2.0/InitiallyUnowned.md) → [Toolkit.Widget](../Toolkit-4.0/Widget.md) → **Toolkit.Button**");
+
+        // types that can't be found are named as in lua
+        let scope = Scope::default();
+        let links = Links { scope: &scope, dirs: HashMap::new(), namespaces: &HashSet::new() };
+        assert_eq!(link("Missing", "Toolkit", &links), "`Toolkit.Missing`");
+        assert_eq!(link("gboolean", "Toolkit", &links), "`boolean`");
+    }
+
+    #[test]
+    fn versions_works() {
+        // two versions of a namespace don't overwrite each other
+        let sink = generate(&["Toolkit-3.0.gir", "Toolkit-4.0.gir"]);
+        assert!(sink.get(Path::new("Toolkit-3.0/index.md")).is_some());
+        assert!(sink.get(Path::new("Toolkit-4.0/index.md")).is_some());
+        let summary = String::from_utf8(sink.get(Path::new("SUMMARY.md")).unwrap()).unwrap();
+        assert!(summary.contains("- [Toolkit 3.0](Toolkit-3.0/index.md)"));
+        assert!(summary.contains("- [Toolkit 4.0](Toolkit-4.0/index.md)"));
+    }
+}
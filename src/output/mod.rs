@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use crate::library::{InfoElements, Repository};
+
+pub mod docset;
+pub mod gir;
+pub mod html;
+pub mod json;
+pub mod man;
+pub mod markdown;
+pub mod vimdoc;
+
+/// The namespaces a repository refers to, its includes and its own, by
+/// name with their version, like `Gtk-4.0` for `Gtk`. Files and tags are
+/// named after these so two versions of a namespace don't clash.
+pub(crate) struct Versions(HashMap<String, String>);
+
+impl Versions {
+    pub(crate) fn new(repo: &Repository) -> Versions {
+        let mut versions = HashMap::new();
+        for include in repo.include.iter() {
+            if let Some(ref version) = include.version {
+                versions.insert(include.name.clone(), format!("{}-{}", include.name, version));
+            }
+        }
+        for ns in repo.namespace.iter() {
+            if let (Some(name), Some(version)) = (&ns.name, &ns.version) {
+                versions.insert(name.clone(), format!("{}-{}", name, version));
+            }
+        }
+        Versions(versions)
+    }
+
+    /// The namespace with its version, the name if the version isn't known
+    pub(crate) fn get<'a>(&'a self, ns: &'a str) -> &'a str {
+        self.0.get(ns).map(String::as_str).unwrap_or(ns)
+    }
+
+    /// A lua name with the version after the namespace, like
+    /// `Gtk-4.0.Widget:show()` for `Gtk.Widget:show()`
+    pub(crate) fn qualify(&self, name: &str) -> String {
+        match name.split_once('.') {
+            Some((ns, rest)) => format!("{}.{}", self.get(ns), rest),
+            None => self.get(name).to_string(),
+        }
+    }
+}
+
+/// Replace the C-isms in gir docs with their lua counterparts
+pub(crate) fn luafy(doc: &str) -> String {
+    doc.replace("%NULL", "nil")
+        .replace("%TRUE", "true")
+        .replace("%FALSE", "false")
+}
+
+/// The first paragraph of the docs, on one line. Used where there
+/// is only room for a short description, like tables.
+pub(crate) fn summary(doc: &InfoElements) -> String {
+    doc.doc
+        .as_ref()
+        .map(|d| {
+            let para = d.content.split("\n\n").next().unwrap_or("");
+            luafy(&para.replace('\n', " "))
+        })
+        .unwrap_or_default()
+}
@@ -1,192 +1,529 @@
-use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
 use std::io::prelude::*;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::Result;
 
-// do not do like this
-// this is just for now 
-// in the future we should be be able
-// generate the stub and vimdoc from 
-// the same document
+use crate::library::*;
+use crate::lang::Generator;
+use crate::sink::Sink;
+use crate::visit::{self, Context, Parent, Visitor};
+use crate::lang::lua::{show_anytyp, translate_ns, in_param, out_param, lua_params};
+use super::{luafy, Versions};
 
-type Result<T> = std::io::Result<T>;
+const WIDTH: usize = 78;
 
+/// Generates vim :help files, one <Namespace>-<version>.txt per namespace.
+/// Types are shown the way they are used from lua.
 pub struct VimDoc {
     // level: Level
 }
 
-pub fn write_enum<W: Write>(e: &Comp, w: &mut W) -> Result<()> {
-    writeln!(w, "{}", e.name)
+impl VimDoc {
+    pub fn new(/* level: Level */) -> VimDoc {
+        VimDoc{}
+    }
 }
 
-fn create_section<W: Write>(ns: &str, name: &str, w: &mut W) -> Result<()> {
-    writeln!(w, "{:=>76}", "=")?;
-    let str = format!("*{}.{}*", ns, name); 
-    writeln!(w, "{}.{} {:>pad$}", ns, name, str, pad=79-str.len())?;
-    Ok(())
+impl Default for VimDoc {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-#[macro_export]
-macro_rules! section {
-    ( $( $w:ident, $name:ident, $section:ident ),* ) => {
-        {
-            if !self.$x.is_empty() {
-                create_section(&$name, "$x", $w)?;
-                for section in self.$x.iter() {
-                    section.gen($name, $w)?;
-                }
-            }
+impl Generator for VimDoc {
+    fn default_dir(&self) -> &'static str {
+        "doc"
+    }
+
+    fn generate(&self, repo: &Arc<Repository>, sink: &dyn Sink) -> Result<()> {
+        let tags = Versions::new(repo);
+        let mut help = Help {
+            tags: &tags,
+            sink,
+            w: vec![],
+            contents: vec![],
+            parts: BTreeMap::new(),
         };
+        visit::walk_repository(&mut help, repo)
     }
 }
 
-impl VimDoc {
-    pub fn new(/* level: Level */) {
-        VimDoc{}
+/// Write `text` with `tag` right aligned on the same line,
+/// or on a line of its own if it doesn't fit. Tags start with the
+/// namespace and its version, like `Gtk-4.0.Widget`, so the help of
+/// two versions can be installed together.
+fn write_tagged<W: Write>(text: &str, tag: &str, tags: &Versions, w: &mut W) -> Result<()> {
+    let tag = format!("*{}*", tags.qualify(tag));
+    if text.len() + tag.len() + 1 > WIDTH {
+        writeln!(w, "{:>width$}", tag, width = WIDTH)?;
+        writeln!(w, "{}", text)?;
+    } else {
+        writeln!(w, "{}{:>pad$}", text, tag, pad = WIDTH - text.len())?;
     }
-    fn gen(&self, filename: &str) {
-        let now: DateTime<Utc> = Utc::now();
-        let datestr = format!("Last Generated: {}", now.format("%d/%m/%Y %H:%M"));
-        writeln!(w, "*{}.txt* {:>pad$}", self.ns, datestr, pad=76-5-self.ns.len())?;
-
-        section!(w, name, classes);
-        if !self.classes.is_empty() {
-            create_section(&name, "classes", w)?;
-            for class in self.classes.iter() {
-                writeln!(w, "{}.{} = {{}}", &name, class.name)?;
-                class.gen(&name, w)?;
-            }
-        }
-        if !self.functions.is_empty() {
-            create_section(&name, "Function", w)?;
-            for function in self.functions.iter() {
-                function.gen(&name, &name, w)?;
-            }
+    Ok(())
+}
+
+/// Write a table of contents `entry`, indented by `indent`, with `link`
+/// right aligned after `fill`, or on a line of its own if it doesn't fit.
+fn write_entry<W: Write>(indent: usize, entry: &str, link: &str, fill: char, w: &mut W) -> Result<()> {
+    let used = indent + entry.len() + link.len();
+    if used + 1 > WIDTH {
+        writeln!(w, "{:indent$}{}", "", entry, indent = indent)?;
+        writeln!(w, "{:>width$}", link, width = WIDTH)?;
+    } else {
+        let fill = fill.to_string().repeat(WIDTH - used);
+        writeln!(w, "{:indent$}{}{}{}", "", entry, fill, link, indent = indent)?;
+    }
+    Ok(())
+}
+
+fn create_section<W: Write>(ns: &str, name: &str, tags: &Versions, w: &mut W) -> Result<()> {
+    writeln!(w, "{:=>width$}", "=", width = WIDTH)?;
+    let str = format!("{}.{}", ns, name);
+    write_tagged(&str, &str, tags, w)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+fn write_contents<W: Write>(contents: &[(&str, Vec<String>)], name: &str, tags: &Versions, w: &mut W) -> Result<()> {
+    writeln!(w, "{:=>width$}", "=", width = WIDTH)?;
+    write_tagged("CONTENTS", &format!("{}-contents", tags.qualify(name)), tags, w)?;
+    writeln!(w)?;
+    let sections = contents.iter().filter(|(_, entries)| !entries.is_empty());
+    for (num, (title, entries)) in sections.enumerate() {
+        let entry = format!("{}. {}", num + 1, title);
+        let link = format!("|{}.{}|", tags.get(name), title);
+        write_entry(2, &entry, &link, '.', w)?;
+        // functions and constants are listed in their sections
+        if matches!(*title, "Functions" | "Constants") {
+            continue;
         }
-        if !self.enums.is_empty() {
-            create_section(&name, "Enums", w)?;
-            for enu in self.enums.iter() {
-                enu.gen(&name, w)?;
-            }
+        for entry in entries.iter() {
+            let link = format!("|{}.{}|", tags.get(name), entry);
+            write_entry(4, entry, &link, ' ', w)?;
         }
-        if !self.record.is_empty() {
-            create_section(&name, "Record", w)?;
-            for record in self.record.iter() {
-                record.gen(&name, w)?;
-            }
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+/// The parts of the help of a type, in the order they are written
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Part {
+    Fields,
+    Properties,
+    Signals,
+    Constructors,
+    Methods,
+    Functions,
+}
+
+impl Part {
+    fn title(self) -> &'static str {
+        match self {
+            Part::Fields => "Fields",
+            Part::Properties => "Properties",
+            Part::Signals => "Signals",
+            Part::Constructors => "Constructors",
+            Part::Methods => "Methods",
+            Part::Functions => "Functions",
         }
-        if !self.constant.is_empty() {
-            create_section(&name, "Constant", w)?;
-            for cons in self.constant.iter() {
-                cons.gen(&name, w)?;
-            }
+    }
+
+    /// Lists end with an empty line, functions with their own
+    fn is_list(self) -> bool {
+        self < Part::Constructors
+    }
+}
+
+/// Writes the help of a namespace. The sections of the namespace come in
+/// the order of the walk and start with the first element that is shown;
+/// the walk visits the functions of a type before its fields, so these are
+/// written to the part of the type they are listed in.
+struct Help<'h> {
+    tags: &'h Versions,
+    sink: &'h dyn Sink,
+    /// The sections after the table of contents
+    w: Vec<u8>,
+    /// The titles of the sections with the names of the entries in them
+    contents: Vec<(&'static str, Vec<String>)>,
+    /// The type being visited
+    parts: BTreeMap<Part, Vec<u8>>,
+}
+
+impl Help<'_> {
+    /// Start the section `title` if it isn't the current one
+    fn section(&mut self, ns: &str, title: &'static str) -> Result<()> {
+        if self.contents.last().map(|(t, _)| *t) != Some(title) {
+            create_section(ns, title, self.tags, &mut self.w)?;
+            self.contents.push((title, vec![]));
         }
-        if !self.bitfield.is_empty() {
-            create_section(&name, "Bitfield", w)?;
-            for bitfield in self.bitfield.iter() {
-                bitfield.gen(&name, w)?;
-            }
+        Ok(())
+    }
+
+    /// Add `name` to the table of contents of the current section
+    fn entry(&mut self, name: &str) {
+        if let Some((_, entries)) = self.contents.last_mut() {
+            entries.push(name.to_string());
         }
-        if !self.alias.is_empty() {
-            create_section(&name, "Alias", w)?;
-            for alias in self.bitfield.iter() {
-                alias.gen(&name, w)?;
+    }
+
+    fn part(&mut self, part: Part) -> &mut Vec<u8> {
+        self.parts.entry(part).or_insert_with(|| {
+            if part.is_list() {
+                format!("{}~\n", part.title()).into_bytes()
+            } else {
+                format!("{}~\n\n", part.title()).into_bytes()
             }
-        }
-        if !self.unions.is_empty() {
-            create_section(&name, "Union", w)?;
-            for unions in self.bitfield.iter() {
-                unions.gen(&name, w)?;
+        })
+    }
+
+    /// The start of the help of the type `static_ns`
+    fn write_type(&mut self, static_ns: &str) -> Result<()> {
+        writeln!(self.w, "{:->width$}", "-", width = WIDTH)?;
+        write_tagged(static_ns, static_ns, self.tags, &mut self.w)?;
+        writeln!(self.w)?;
+        Ok(())
+    }
+
+    /// The start of an enum or bitfield, the members follow
+    fn write_enum(&mut self, name: &str, info: &InfoAttrs, doc: &InfoElements, cx: Context) -> Result<()> {
+        let static_ns = cx.path(name);
+        write_tagged(&static_ns, &static_ns, self.tags, &mut self.w)?;
+        writeln!(self.w)?;
+        write_info(info, &mut self.w)?;
+        write_doc(doc, &mut self.w)
+    }
+
+    /// Write the parts of the type that has been visited
+    fn write_parts(&mut self) -> Result<()> {
+        for (part, w) in std::mem::take(&mut self.parts) {
+            self.w.extend(w);
+            if part.is_list() {
+                writeln!(self.w)?;
             }
         }
-
-        writeln!(w, "")?;
-        write!(w, "vim:tw=78:ts=8:noet:ft=help:norl:")?;
-        w.flush()?;
         Ok(())
     }
 }
 
+impl Visitor for Help<'_> {
+    fn visit_namespace<'a>(&mut self, ns: &'a Namespace, cx: Context<'a>) -> Result<()> {
+        visit::walk_namespace(self, ns, cx)?;
+        let (tags, name) = (self.tags, cx.ns);
+        // no date, so the same gir always gives the same file
+        let title = match ns.version {
+            Some(ref version) => format!("{} {}", name, version),
+            None => name.to_string(),
+        };
+        let file = format!("*{}.txt*", tags.qualify(name));
+        let mut w = vec![];
+        writeln!(w, "{}{:>pad$}", file, title, pad = WIDTH.saturating_sub(file.len()))?;
+        writeln!(w)?;
+        if let Some(ref version) = ns.version {
+            writeln!(w, "{} {} API reference, as used from lua.\n", name, version)?;
+        }
+        write_contents(&std::mem::take(&mut self.contents), name, tags, &mut w)?;
+        w.append(&mut self.w);
+        writeln!(w)?;
+        writeln!(w, "vim:tw=78:ts=8:noet:ft=help:norl:")?;
+        self.sink.write(Path::new(&format!("{}.txt", tags.qualify(name))), &w)
+    }
 
-// namespace
-pub fn write<W: Write>(&self, w: &mut W) -> Result<()> {
-}
+    fn visit_class<'a>(&mut self, class: &'a Class, cx: Context<'a>) -> Result<()> {
+        if !class.info.is_introspectable() {
+            return Ok(())
+        }
+        self.section(cx.ns, "Classes")?;
+        self.entry(&class.name);
+        self.write_type(&cx.path(&class.name))?;
+        let (tags, ns, w) = (self.tags, cx.ns, &mut self.w);
+        if let Some(ref parent) = class.parent {
+            writeln!(w, "\tExtends: |{}|", tags.qualify(&translate_ns(parent, ns)))?;
+        }
+        if !class.implements.is_empty() {
+            let implements: Vec<String> = class.implements
+                .iter()
+                .map(|i| format!("|{}|", tags.qualify(&translate_ns(&i.name, ns))))
+                .collect();
+            writeln!(w, "\tImplements: {}", implements.join(", "))?;
+        }
+        writeln!(w)?;
+        write_info(&class.info, w)?;
+        write_doc(&class.doc, w)?;
+        visit::walk_class(self, class, cx)?;
+        self.write_parts()
+    }
 
-// class
-pub fn write<W: Write>(&self, ns: &str, w: &mut W) -> Result<()>{
-    let static_ns = format!("{}.{}", ns, self.name);
+    fn visit_interface<'a>(&mut self, interface: &'a Interface, cx: Context<'a>) -> Result<()> {
+        if !interface.info.is_introspectable() {
+            return Ok(())
+        }
+        self.section(cx.ns, "Interfaces")?;
+        self.entry(&interface.name);
+        self.write_type(&cx.path(&interface.name))?;
+        let (tags, ns, w) = (self.tags, cx.ns, &mut self.w);
+        if !interface.prerequisites.is_empty() {
+            let prereqs: Vec<String> = interface.prerequisites
+                .iter()
+                .map(|p| format!("|{}|", tags.qualify(&translate_ns(p, ns))))
+                .collect();
+            writeln!(w, "\tPrerequisites: {}\n", prereqs.join(", "))?;
+        }
+        write_info(&interface.info, w)?;
+        write_doc(&interface.doc, w)?;
+        visit::walk_interface(self, interface, cx)?;
+        self.write_parts()
+    }
 
-    create_section(ns, &self.name, w)?;
-    let local_ns = self.name.to_lowercase();
+    fn visit_record<'a>(&mut self, record: &'a Record, cx: Context<'a>) -> Result<()> {
+        if cx.parent.is_some() || !record.info.is_introspectable() {
+            return Ok(())
+        }
+        self.section(cx.ns, "Records")?;
+        // class structs, like GtkWidgetClass
+        if record.name.ends_with("Class") {
+            return Ok(())
+        }
+        self.entry(&record.name);
+        self.write_type(&cx.path(&record.name))?;
+        write_info(&record.info, &mut self.w)?;
+        write_doc(&record.doc, &mut self.w)?;
+        visit::walk_record(self, record, cx)?;
+        self.write_parts()
+    }
 
-    if let Some(ref doc) = self.doc {
-        writeln!(w, "{}\n", doc)?;
+    fn visit_union<'a>(&mut self, _union: &'a Union, _cx: Context<'a>) -> Result<()> {
+        Ok(())
     }
 
-    if !self.fields.is_empty() {
-        writeln!(w, "Fields~")?;
-        for field in self.fields.iter() {
-            let str = format!("{}.{}", static_ns, field.0);
-            w.write_all(&str.as_bytes())?;
-            writeln!(w, "")?;
+    fn visit_boxed<'a>(&mut self, _boxed: &'a Boxed, _cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_enum<'a>(&mut self, e: &'a Enumeration, cx: Context<'a>) -> Result<()> {
+        if !e.info.is_introspectable() {
+            return Ok(())
         }
+        self.section(cx.ns, "Enums")?;
+        self.entry(&e.name);
+        self.write_enum(&e.name, &e.info, &e.doc, cx)?;
+        visit::walk_enum(self, e, cx)?;
+        writeln!(self.w)?;
+        Ok(())
     }
-    if !self.constructor.is_empty() {
-        writeln!(w, "Constructors~")?;
-        for constr in self.constructor.iter() {
-            constr.write(&static_ns, w)?;
-            writeln!(w, "")?;
+
+    fn visit_bitfield<'a>(&mut self, b: &'a Bitfield, cx: Context<'a>) -> Result<()> {
+        if !b.info.is_introspectable() {
+            return Ok(())
         }
+        self.section(cx.ns, "Bitfields")?;
+        self.entry(&b.name);
+        self.write_enum(&b.name, &b.info, &b.doc, cx)?;
+        visit::walk_bitfield(self, b, cx)?;
+        writeln!(self.w)?;
+        Ok(())
     }
-    if !self.method.is_empty() {
-        writeln!(w, "Methods~")?;
-        for method in self.method.iter() {
-            method.write(&local_ns, w)?;
-            writeln!(w, "")?;
+
+    fn visit_member<'a>(&mut self, member: &'a Member, cx: Context<'a>) -> Result<()> {
+        if member.info.is_introspectable() {
+            writeln!(self.w, "\t{} = {}", cx.path(&member.name.to_uppercase()), member.value)?;
         }
+        Ok(())
     }
-    if !self.func.is_empty() {
-        writeln!(w, "Functions~")?;
-        for func in self.func.iter() {
-            func.write(&static_ns, w)?;
-            writeln!(w, "")?;
+
+    fn visit_function<'a>(&mut self, fun: &'a Function, cx: Context<'a>) -> Result<()> {
+        if !fun.info.is_introspectable() {
+            return Ok(())
         }
+        let tags = self.tags;
+        let part = match (cx.parent, &fun.typ) {
+            (None, FunctionType::Function) => {
+                self.section(cx.ns, "Functions")?;
+                self.entry(&fun.name);
+                return write_function(fun, cx.ns, cx.ns, tags, &mut self.w)
+            }
+            // interfaces are made by the classes implementing them
+            (Some(Parent::Class(_) | Parent::Record(_)), FunctionType::Constructor) => Part::Constructors,
+            (Some(Parent::Class(_) | Parent::Interface(_) | Parent::Record(_)), FunctionType::Method) => Part::Methods,
+            (Some(Parent::Class(_) | Parent::Interface(_) | Parent::Record(_)), FunctionType::Function) => Part::Functions,
+            _ => return Ok(()),
+        };
+        write_function(fun, &cx.qualified(), cx.ns, tags, self.part(part))
     }
-    if !self.virt.is_empty() {
-        writeln!(w, "Virtual~")?;
-        for virt in self.virt.iter() {
-            virt.write(&local_ns, w)?;
-            writeln!(w, "")?;
+
+    fn visit_field<'a>(&mut self, field: &'a Field, cx: Context<'a>) -> Result<()> {
+        if field.private || !matches!(cx.parent, Some(Parent::Class(_) | Parent::Record(_))) {
+            return Ok(())
         }
+        writeln!(self.part(Part::Fields), "\t{{{}}} `{}`", field.name, show_anytyp(&field.typ, cx.ns))?;
+        Ok(())
+    }
+
+    fn visit_property<'a>(&mut self, prop: &'a Property, cx: Context<'a>) -> Result<()> {
+        let access = match (prop.readable, prop.writable && !prop.construct_only) {
+            (true, false) => " (read-only)",
+            (false, true) => " (write-only)",
+            _ => "",
+        };
+        writeln!(self.part(Part::Properties), "\t{{{}}} `{}`{}", prop.name.replace('-', "_"),
+            show_anytyp(&prop.typ, cx.ns), access)?;
+        Ok(())
+    }
+
+    fn visit_signal<'a>(&mut self, signal: &'a Signal, cx: Context<'a>) -> Result<()> {
+        let tags = self.tags;
+        let w = self.part(Part::Signals);
+        let params: Vec<String> = lua_params(&signal.parameters, signal.ret.as_ref())
+            .into_iter()
+            .filter(|p| in_param(&p.direction))
+            .map(|p| format!("{{{}}}", p.name))
+            .collect();
+        let tag = format!("{}::{}", cx.qualified(), signal.name);
+        write_tagged(&format!("\"{}\"({})", signal.name, params.join(", ")), &tag, tags, w)?;
+        write_doc(&signal.doc, w)?;
+        write_params(&lua_params(&signal.parameters, signal.ret.as_ref()), cx.ns, w)
+    }
+
+    fn visit_constant<'a>(&mut self, c: &'a Constant, cx: Context<'a>) -> Result<()> {
+        if cx.parent.is_some() || !c.info.is_introspectable() {
+            return Ok(())
+        }
+        self.section(cx.ns, "Constants")?;
+        self.entry(&c.name);
+        let static_ns = cx.path(&c.name);
+        write_tagged(&format!("{} = {}", static_ns, c.value), &static_ns, self.tags, &mut self.w)?;
+        write_doc(&c.doc, &mut self.w)
+    }
+
+    fn visit_alias<'a>(&mut self, alias: &'a Alias, cx: Context<'a>) -> Result<()> {
+        self.section(cx.ns, "Aliases")?;
+        self.entry(&alias.name);
+        writeln!(self.w, "{} = `{}`", cx.path(&alias.name), show_anytyp(&alias.typ, cx.ns))?;
+        Ok(())
     }
-    Ok(())
 }
 
-fn get_typeless(args: &Vec<(String, Type)>) -> Vec<String> {
-    args.into_iter().map(|x| format!("{{{}}}", x.0)).collect()
+fn write_doc<W: Write>(doc: &InfoElements, w: &mut W) -> Result<()> {
+    if let Some(ref doc) = doc.doc {
+        let luafied = luafy(&doc.content);
+        for line in luafied.lines() {
+            if line.is_empty() {
+                writeln!(w)?;
+            } else {
+                writeln!(w, "\t{}", line)?;
+            }
+        }
+        writeln!(w)?;
+    }
+    Ok(())
 }
 
-fn write_doc<W: Write>(doc: &Option<String>, w: &mut W) -> Result<()> {
-    if let Some(ref doc) = doc {
-        let lines = doc.lines();
-        for line in lines.into_iter() {
-            writeln!(w, "\t{}", line)?;
+fn write_info<W: Write>(info: &InfoAttrs, w: &mut W) -> Result<()> {
+    if let Some(true) = info.deprecated {
+        if let Some(ref version) = info.deprecated_version {
+            writeln!(w, "\tDeprecated since {}\n", version)?;
+        } else {
+            writeln!(w, "\tDeprecated\n")?;
         }
-        writeln!(w, "")?;
+    }
+    if let Some(ref version) = info.version {
+        writeln!(w, "\tSince {}\n", version)?;
     }
     Ok(())
 }
 
-pub fn write<W: Write>(&self, ns: &str, w: &mut W) -> Result<()> {
-    let typeless = get_typeless(&self.args);
-    let fmt = format!("*{}.{}()*", ns, self.name);
-    writeln!(w, "{:>78}", fmt)?;
-    writeln!(w, "{}.{}({})\n", ns, self.name, typeless.join(", "))?;
+fn write_params<W: Write>(params: &[&Parameter], ns: &str, w: &mut W) -> Result<()> {
+    let params: Vec<&&Parameter> = params.iter().filter(|p| in_param(&p.direction)).collect();
+    if params.is_empty() {
+        return Ok(())
+    }
+    writeln!(w, "\tParameters:~")?;
+    for param in params {
+        let opt = if param.optional || param.allow_none { "?" } else { "" };
+        writeln!(w, "\t\t{{{}}} `{}{}`", param.name, show_anytyp(&param.typ, ns), opt)?;
+    }
+    Ok(())
+}
+
+fn write_function<W: Write>(fun: &Function, static_ns: &str, ns: &str, tags: &Versions, w: &mut W) -> Result<()> {
+    if let Some(false) = fun.info.introspectable {
+        return Ok(())
+    }
+    let sep = if fun.typ == FunctionType::Method { ":" } else { "." };
+    let lua_params = lua_params(&fun.parameters, fun.ret.as_ref());
+    let params: Vec<&Parameter> = lua_params
+        .iter()
+        .copied()
+        .filter(|p| in_param(&p.direction))
+        .collect();
+    let names: Vec<String> = params.iter().map(|p| format!("{{{}}}", p.name)).collect();
+
+    let tag = format!("{}{}{}()", static_ns, sep, fun.name);
+    write_tagged(&format!("{}{}{}({})", static_ns, sep, fun.name, names.join(", ")), &tag, tags, w)?;
+    writeln!(w)?;
+    write_info(&fun.info, w)?;
+    write_doc(&fun.doc, w)?;
+
+    if !params.is_empty() {
+        writeln!(w, "\tParameters:~")?;
+        for param in params {
+            let opt = if param.optional || param.allow_none { "?" } else { "" };
+            let doc = param.doc.doc.as_ref()
+                .map(|d| luafy(&d.content.replace('\n', " ")))
+                .unwrap_or_default();
+            writeln!(w, "\t\t{{{}}} `{}{}` {}", param.name,
+                show_anytyp(&param.typ, ns), opt, doc)?;
+        }
+    }
 
-    write_doc(&self.doc, w)?;
-    writeln!(w, "\tArguments:~")?;
-    for arg in self.args.iter() {
-        // writeln!(w, "`{}`: `{}` {}", arg.0, arg.1, arg.2)?;
-        writeln!(w, "\t\t{{{}}} `{}`", arg.0, arg.1)?;
+    let mut rets = vec![];
+    if let Some(ref ret) = fun.ret {
+        rets.push(ret);
+    }
+    rets.extend(lua_params.into_iter().filter(|p| out_param(&p.direction)));
+    let rets: Vec<String> = rets
+        .iter()
+        .map(|p| show_anytyp(&p.typ, ns))
+        .filter(|t| t != "nil")
+        .collect();
+    if !rets.is_empty() {
+        writeln!(w, "\tReturns:~\n\t\t`{}`", rets.join(", "))?;
     }
-    writeln!(w, "\tReturns:~\n\t\t`{}`", self.ret.join(", "))?;
+    writeln!(w)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+    use super::{write_entry, VimDoc, WIDTH};
+    use crate::lang::Generator;
+    use crate::resolve::read_repository;
+    use crate::sink::MemorySink;
+
+    #[test]
+    fn vimdoc_works() {
+        let repo = Arc::new(read_repository("testdata/girs/GMime-3.0.gir").unwrap());
+        let sink = MemorySink::new();
+        VimDoc::new().generate(&repo, &sink).unwrap();
+        let doc = String::from_utf8(sink.get(Path::new("GMime-3.0.txt")).unwrap()).unwrap();
+        // tags have the version, so the help of two versions doesn't clash
+        assert!(doc.starts_with("*GMime-3.0.txt*"));
+        assert!(doc.contains("*GMime-3.0-contents*"));
+        assert!(doc.contains("*GMime-3.0.Message*"));
+        assert!(doc.contains("\tExtends: |GObject-2.0.Object|"));
+        assert!(doc.contains("\tGMime.AddressType.REPLY_TO = 2"));
+        let contents = &doc[..doc.find("*GMime-3.0.Classes*").unwrap()];
+        assert!(contents.lines().all(|l| l.len() <= WIDTH));
+
+        // too long for the link, which goes on its own line
+        let entry = "A".repeat(WIDTH);
+        let mut w = vec![];
+        write_entry(4, &entry, "|GMime.A|", ' ', &mut w).unwrap();
+        let expected = format!("    {}\n{:>width$}\n", entry, "|GMime.A|", width = WIDTH);
+        assert_eq!(String::from_utf8(w).unwrap(), expected);
+    }
+}
@@ -1,14 +1,10 @@
 use crate::library::*;
-use xmltree::Element;
 use core::fmt;
-use std::collections::HashSet;
-use std::io::Read;
+use std::io::{BufRead, BufReader, Read};
 use std::str::FromStr;
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
 
-fn read_return(e: &Element) -> Option<Parameter> {
-    let ret = e.get_child("return-value")?;
-    read_param(ret)
-}
 
 impl FromStr for ParameterScope {
     type Err = String;
@@ -63,6 +59,7 @@ impl FromStr for Transfer {
     fn from_str(name: &str) -> Result<Transfer, String> {
         use self::Transfer::*;
         match name {
+            "none" => Ok(None),
             "container" => Ok(Container),
             "full" => Ok(Full),
             _ => Err(format!("Unknown parameter direction '{}'", name)),
@@ -70,1251 +67,1088 @@ impl FromStr for Transfer {
     }
 }
 
-// fn get_attribute(e: &Element, attr: &str) -> Option<String> {
-//     e.attributes.get(attr).map(|x| x.to_string())
-// }
-
-fn r_anytype(e: &Element) -> Option<AnyType> {
-    match e.name.as_str() {
-        "type" => { 
-            let name = attribute(e, "name");
-            let ctype = attribute(e, "type");
-            let introspectable = attr_bool(e, "introspectable");
-            let doc = read_infoelements(e)?;
-            let mut children = vec![];
-            for node in e.children.iter() {
-                if let Some(e) = node.as_element() {
-                    if let Some(typ) = read_anytype(e) {
-                        children.push(typ)
-                    }
-                }
-            }
-            if Some("GLib.HashTable") == name.as_deref() {
-                for node in e.children.iter() {
-                    if let Some(e) = node.as_element() {
-                        println!("{:?}", e);
-                        if let Some(typ) = read_anytype(e) {
-                            children.push(typ)
-                        }
-                    }
-                }
-            }
-            Some(AnyType::Type(Type{
-                name,
-                ctype,
-                introspectable,
-                doc,
-                children,
-            }))
-        }
-        "array" => {
-            let name = attribute(e, "name");
-            let zero_terminated = attr_bool(e, "zero-terminated");
-            let fixed_size = attr_bool(e, "fixed-size");
-            let introspectable = attr_bool(e, "introspectable");
-            let length = attr_value(e, "length");
-            let ctype = attribute(e, "type");
-            for node in e.children.iter() {
-                if let Some(e) = node.as_element() {
-                    let typ = attribute(e, "name")?;
-                    return Some(AnyType::Array(Array{
-                        name,
-                        zero_terminated,
-                        fixed_size,
-                        introspectable,
-                        length,
-                        ctype,
-                        typ,
-                    }))
-                }
-            }
-            None
-        }
-        "varargs" => {
-            Some(AnyType::VarArg)
-        }
-        _ => None
-    }
+
+/// An error reading a gir file
+#[derive(Debug)]
+pub enum ParseError {
+    /// The xml is malformed, at the byte offset
+    Xml(quick_xml::Error, u64),
+    /// The file ended before the root element was closed
+    UnexpectedEof,
 }
 
-fn read_anytype(e: &Element) -> Option<AnyType> {
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
-                "type" => { 
-                    let name = attribute(e, "name");
-                    let ctype = attribute(e, "type");
-                    let introspectable = attr_bool(e, "introspectable");
-                    let doc = read_infoelements(e)?;
-                    let mut children = vec![];
-                    for node in e.children.iter() {
-                        if let Some(e) = node.as_element() {
-                            if let Some(typ) = r_anytype(e) {
-                                children.push(typ)
-                            }
-                        }
-                    }
-                    return Some(AnyType::Type(Type{
-                        name,
-                        ctype,
-                        introspectable,
-                        doc,
-                        children,
-                    }))
-                }
-                "array" => {
-                    let name = attribute(e, "name");
-                    let zero_terminated = attr_bool(e, "zero-terminated");
-                    let fixed_size = attr_bool(e, "fixed-size");
-                    let introspectable = attr_bool(e, "introspectable");
-                    let length = attr_value(e, "length");
-                    let ctype = attribute(e, "type");
-                    for node in e.children.iter() {
-                        if let Some(e) = node.as_element() {
-                            let typ = attribute(e, "name")?;
-                            // let typ = read_anytype(e)?;
-                            // let btyp = Box::new(typ);
-                            return Some(AnyType::Array(Array{
-                                name,
-                                zero_terminated,
-                                fixed_size,
-                                introspectable,
-                                length,
-                                ctype,
-                                typ,
-                            }))
-                        }
-                    }
-                }
-                "varargs" => {
-                    return Some(AnyType::VarArg)
-                }
-                _ => {}
-            }
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Xml(err, pos) => write!(f, "malformed xml at byte {}: {}", pos, err),
+            ParseError::UnexpectedEof => f.write_str("unexpected end of file"),
         }
     }
-    None
 }
 
-fn read_param(e: &Element) -> Option<Parameter> {
-    let name = attribute(e, "name").unwrap_or("".to_string());
-    let nullable = attr_bool(e, "nullable").unwrap_or(false);
-    let allow_none = attr_bool(e, "allow-none").unwrap_or(false);
-    let introspectable = attr_bool(e, "introspectable");
-    let closure = attribute(e, "closure");
-    let destroy = attribute(e, "destroy");
-    let scope = attribute(e, "scope");
-    let direction = attr_value(e, "direction");
-    let caller_allocates = attr_bool(e, "caller-allocates").unwrap_or(false);
-    let optional = attr_bool(e, "optional").unwrap_or(false);
-    let skip = attr_bool(e, "skip").unwrap_or(false);
-    let transfer = attr_value(e, "transfer");
-    let doc = read_infoelements(e)?;
-    let typ = read_anytype(e)?;
-
-    Some(Parameter{
-        name,
-        nullable,
-        allow_none,
-        introspectable,
-        closure,
-        destroy,
-        scope,
-        direction,
-        caller_allocates,
-        optional,
-        skip,
-        transfer,
-        doc,
-        typ,
-    })
+impl std::error::Error for ParseError {}
+
+type ParseResult<T> = std::result::Result<T, ParseError>;
+
+/// A start tag. Names are without the prefix, like `type` for `c:type`,
+/// and of attributes with the same name the last counts.
+struct Tag {
+    name: String,
+    prefix: Option<String>,
+    attrs: Vec<(String, String)>,
 }
 
-fn para_get_len(param: &Parameter) -> Option<usize> {
-    if let AnyType::Array(array) = &param.typ {
-        return array.length
+impl Tag {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().rev().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
     }
-    None
-}
 
-fn read_params(e: &Element) -> Option<Vec<Parameter>> {
-    let mut ret: Vec<Parameter> = vec![];
-    let parameters = e.get_child("parameters")?;
+    fn attr(&self, name: &str) -> Option<String> {
+        self.get(name).map(str::to_string)
+    }
 
-    let mut skip: HashSet<usize> = HashSet::new();
-    let mut num = 0;
-    for parameter in parameters.children.iter() {
-        if skip.contains(&num) {
-            continue;
-        }
-        if let Some(e) = parameter.as_element() {
-            match e.name.as_ref() {
-                "parameter" | "instance-parameter" => {
-                    let para = read_param(e);
-                    if let Some(para) = para {
-                        if let Some(len) = para_get_len(&para) {
-                            skip.insert(len);
-                        }
-                        ret.push(para);
-                    }
-                    if e.name == "parameter" {
-                        num = num + 1;
-                    } 
-                },
-                _ => return None,
-            }
+    fn bool(&self, name: &str) -> Option<bool> {
+        match self.get(name) {
+            Some("0") => Some(false),
+            Some("1") => Some(true),
+            _ => None,
         }
     }
-    Some(ret)
+
+    fn value<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name).and_then(|v| T::from_str(v).ok())
+    }
 }
 
-fn read_macro_param(e: &Element) -> Option<MacroParam> {
-    let name = attribute(e, "name")?;
-    let doc = read_infoelements(e)?;
-    Some(MacroParam{
-        name,
-        doc,
-    })
+enum Node {
+    Start(Tag),
+    Text(String),
+    End,
 }
 
-fn read_macro(e: &Element) -> Option<Macro> {
-    let name = attribute(e, "name")?;
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
+/// An element of the gir that couldn't be read and is missing from
+/// the `Repository`, like a method without a name
+#[derive(Debug)]
+pub struct ParseFailure {
+    pub ns: String,
+    /// The names of the element and the elements it's in, like `Gtk.Widget.show`.
+    /// Just the names of the parents if the element has no name.
+    pub path: String,
+    /// The gir element, like `method` or `property`
+    pub kind: String,
+}
 
-    let c_identifier = attribute(e, "identifier");
+/// Reads the library types straight from the xml events, every element
+/// is read once and nothing of the document is kept. Readers are called
+/// after the start tag and read up to and including the end tag.
+struct Parser<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    /// The names of the elements the parser is in
+    path: Vec<Option<String>>,
+    /// The name of the namespace being read
+    ns: Option<String>,
+    failures: Vec<ParseFailure>,
+}
 
-    let mut param: Vec<MacroParam> = vec![];
+impl<R: BufRead> Parser<R> {
+    fn new(read: R) -> Parser<R> {
+        let mut reader = Reader::from_reader(read);
+        reader.config_mut().expand_empty_elements = true;
+        Parser {
+            reader,
+            buf: vec![],
+            path: vec![],
+            ns: None,
+            failures: vec![],
+        }
+    }
 
-    let parameters = e.get_child("parameters")?;
+    fn error(&self, err: quick_xml::Error) -> ParseError {
+        ParseError::Xml(err, self.reader.error_position())
+    }
 
-    for parameter in parameters.children.iter() {
-        if let Some(e) = parameter.as_element() {
-            if e.name == "parameter" {
-                let para = read_macro_param(e);
-                if let Some(para) = para {
-                    param.push(para);
+    fn next(&mut self) -> ParseResult<Node> {
+        loop {
+            self.buf.clear();
+            let event = match self.reader.read_event_into(&mut self.buf) {
+                Ok(event) => event,
+                Err(err) => return Err(self.error(err)),
+            };
+            let node = match event {
+                Event::Start(start) => Node::Start(tag(&start)),
+                Event::End(_) => Node::End,
+                Event::Text(text) => {
+                    let text = text.unescape();
+                    match text {
+                        // whitespace between elements
+                        Ok(text) if text.chars().all(|c| matches!(c, ' ' | '\t' | '\n' | '\r')) => continue,
+                        Ok(text) => Node::Text(text.into_owned()),
+                        Err(err) => return Err(self.error(err)),
+                    }
                 }
-            }
+                Event::CData(data) => Node::Text(String::from_utf8_lossy(&data).into_owned()),
+                Event::Eof => return Err(ParseError::UnexpectedEof),
+                _ => continue,
+            };
+            return Ok(node)
         }
     }
-    Some(Macro {
-        info,
-        doc,
-        name,
-        c_identifier,
-        parameters: param,
-    })
-}
 
-fn read_signal(e: &Element) -> Option<Signal> {
-    let name = attribute(e, "name")?;
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-
-    let detailed = attr_bool(e, "detailed");
-    let when = attribute(e, "action");
-    let action = attr_bool(e, "action");
-    let no_hooks = attr_bool(e, "detailed");
-    let no_recurse = attr_bool(e, "action");
-    let emitter = attribute(e, "detailed");
-
-    let ret = read_return(e);
-    let parameters = read_params(e).unwrap_or(vec![]);
-
-    Some(Signal { 
-        name,
-        info,
-        doc,
-        detailed,
-        when,
-        action,
-        no_hooks,
-        no_recurse,
-        emitter,
-        parameters,
-        ret,
-    })
-}
+    /// The next child of the element being read, None at its end
+    fn child(&mut self) -> ParseResult<Option<Tag>> {
+        loop {
+            match self.next()? {
+                Node::Start(tag) => {
+                    self.path.push(tag.attr("name"));
+                    return Ok(Some(tag))
+                }
+                Node::End => {
+                    self.path.pop();
+                    return Ok(None)
+                }
+                Node::Text(_) => {}
+            }
+        }
+    }
 
-fn read_function(e: &Element, typ: FunctionType) -> Option<Function> {
-    let name = attribute(e, "name")?;
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-
-    let c_identifier = attribute(e, "identifier");
-    let shadowed_by = attribute(e, "shadowed-by");
-    let shadows = attribute(e, "shadows");
-    let throws = attr_bool(e, "throws");
-    let moved_to = attribute(e, "moved-to");
-    let introspectable = attr_bool(e, "introspectable");
-
-    let ret = read_return(e);
-    let parameters = read_params(e).unwrap_or(vec![]);
-
-    Some(Function {
-        info,
-        doc,
-        typ,
-        name,
-        introspectable,
-        c_identifier,
-        shadowed_by,
-        shadows,
-        throws,
-        moved_to,
-        parameters,
-        ret,
-    })
-}
+    /// Skip the rest of the element being read
+    fn skip(&mut self) -> ParseResult<()> {
+        while self.child()?.is_some() {
+            self.skip()?;
+        }
+        Ok(())
+    }
 
-fn get_doc(e: &Element) -> Option<Doc> {
-    let preserve_space = attribute(e, "space");
-    let preserve_white = attribute(e, "whitespace");
-    let filename = attribute(e, "filename")?;
-    let line = attribute(e, "line")?;
-    let column = attribute(e, "column");
-    let content = e.get_text().map(|x| x.into())?;
-    Some(Doc{
-        preserve_space,
-        preserve_white,
-        filename,
-        line,
-        column,
-        content,
-    })
-}
-fn get_doc_versioned(e: &Element) -> Option<DocVersioned> {
-    let preserve_space = attribute(e, "space");
-    let preserve_white = attribute(e, "whitespace");
-    let content = e.get_text().map(|x| x.into())?;
-    Some(DocVersioned{
-        preserve_space,
-        preserve_white,
-        content,
-    })
-}
+    /// The text of the element being read, without the text of children
+    fn text(&mut self) -> ParseResult<Option<String>> {
+        let mut text: Option<String> = None;
+        loop {
+            match self.next()? {
+                Node::Start(tag) => {
+                    self.path.push(tag.attr("name"));
+                    self.skip()?;
+                }
+                Node::Text(t) => text.get_or_insert_with(String::new).push_str(&t),
+                Node::End => {
+                    self.path.pop();
+                    return Ok(text)
+                }
+            }
+        }
+    }
 
-fn get_source_position(e: &Element) -> Option<DocPosition> {
-    let filename = attribute(e, "filename")?;
-    let line = attribute(e, "line")?;
-    let column = attribute(e, "column");
-    Some(DocPosition{
-        filename,
-        line,
-        column,
-    })
-}
+    /// Read a symbol with `read`, remembering it as a failure if it can't
+    /// be read. Failures inside of it are replaced by it.
+    fn symbol<T, F>(&mut self, tag: &Tag, read: F) -> ParseResult<Option<T>>
+    where
+        F: FnOnce(&mut Self, &Tag) -> ParseResult<Option<T>>,
+    {
+        let start = self.failures.len();
+        let ret = read(self, tag)?;
+        if ret.is_none() {
+            if let Some(ref ns) = self.ns {
+                // the repository and namespace are first
+                let mut path = ns.clone();
+                for name in self.path.iter().skip(2).flatten().chain(tag.get("name").map(str::to_string).iter()) {
+                    path.push('.');
+                    path.push_str(name);
+                }
+                self.failures.truncate(start);
+                self.failures.push(ParseFailure {
+                    ns: ns.clone(),
+                    path,
+                    kind: tag.name.clone(),
+                });
+            }
+        }
+        Ok(ret)
+    }
 
+    /// Read `tag` into `doc` if it's documentation, the last of a kind counts
+    fn info_element(&mut self, doc: &mut InfoElements, tag: &Tag) -> ParseResult<bool> {
+        match tag.name.as_str() {
+            "doc" => doc.doc = self.doc(tag)?,
+            "doc-stability" => doc.doc_stability = self.doc_versioned(tag)?,
+            "doc-deprecated" => doc.doc_deprecated = self.doc_versioned(tag)?,
+            "doc-version" => doc.doc_version = self.doc_versioned(tag)?,
+            "source-position" => {
+                doc.doc_pos = source_position(tag);
+                self.skip()?;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
 
-fn read_property(e: &Element) -> Option<Property> {
-    let name = attribute(e, "name")?;
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-
-    let readable = attr_bool(e, "readable").unwrap_or(true);
-    let writable = attr_bool(e, "writable").unwrap_or(false);
-    let construct = attr_bool(e, "construct").unwrap_or(false);
-    let construct_only = attr_bool(e, "construct-only").unwrap_or(false);
-    let setter = attribute(e, "setter");
-    let getter = attribute(e, "getter");
-    let transfer = attr_value(e, "transfer");
-    let typ = read_anytype(e)?;
-
-    Some(Property{
-        name,
-        info,
-        doc,
-        readable,
-        writable,
-        construct,
-        construct_only,
-        setter,
-        getter,
-        transfer,
-        typ,
-    })
-}
+    /// Read `tag` into `typ` if it's a type and `typ` has none yet
+    fn type_element(&mut self, typ: &mut Option<AnyType>, tag: &Tag) -> ParseResult<bool> {
+        if !matches!(tag.name.as_str(), "type" | "array" | "callback" | "varargs") {
+            return Ok(false)
+        }
+        if typ.is_some() {
+            self.skip()?;
+        } else {
+            *typ = self.anytype(tag)?;
+        }
+        Ok(true)
+    }
 
-pub fn attr_bool(e: &Element, name: &str) -> Option<bool>
-{
-    if let Some(value_str) = e.attributes.get(name) {
-        match value_str.as_str() {
-            "0" => Some(false),
-            "1" => Some(true),
-            _ => None
+    /// A child that isn't read by the element, documentation or skipped
+    fn other(&mut self, doc: &mut InfoElements, tag: &Tag) -> ParseResult<()> {
+        if !self.info_element(doc, tag)? {
+            self.skip()?;
         }
-    } else {
-        None
+        Ok(())
     }
-}
 
-fn read_infoattrs(e: &Element) -> Option<InfoAttrs> {
-    let introspectable = attr_bool(e, "introspectable");
-    let deprecated = attr_bool(e, "deprecated");
-    let deprecated_version = attribute(e, "deprecated-version");
-    let version = attribute(e, "version");
-    let stability = attribute(e, "stability");
-    Some(InfoAttrs {
-        introspectable,
-        deprecated,
-        deprecated_version,
-        version,
-        stability,
-    })
-}
+    fn doc(&mut self, tag: &Tag) -> ParseResult<Option<Doc>> {
+        let content = self.text()?;
+        required(|| Some(Doc {
+            preserve_space: tag.attr("space"),
+            preserve_white: tag.attr("whitespace"),
+            filename: tag.attr("filename")?,
+            line: tag.attr("line")?,
+            column: tag.attr("column"),
+            content: content?,
+        }))
+    }
 
+    fn doc_versioned(&mut self, tag: &Tag) -> ParseResult<Option<DocVersioned>> {
+        let content = self.text()?;
+        Ok(content.map(|content| DocVersioned {
+            preserve_space: tag.attr("space"),
+            preserve_white: tag.attr("whitespace"),
+            content,
+        }))
+    }
 
-fn read_infoelements(e: &Element) -> Option<InfoElements> {
-    let mut doc = None;
-    let mut doc_stability = None;
-    let mut doc_version = None;
-    let mut doc_deprecated = None;
-    let mut doc_pos = None;
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
-                "doc" => {
-                    let docs = get_doc(e);
-                    doc = docs;
-                }
-                "doc-stability" => {
-                    let docs = get_doc_versioned(e);
-                    doc_stability = docs;
-                }
-                "doc-deprecated" => {
-                    let docs = get_doc_versioned(e);
-                    doc_deprecated = docs;
-                }
-                "doc-version" => {
-                    let docs = get_doc_versioned(e);
-                    doc_version = docs;
+    fn anytype(&mut self, tag: &Tag) -> ParseResult<Option<AnyType>> {
+        match tag.name.as_str() {
+            "type" => {
+                let mut doc = info_elements();
+                let mut children = vec![];
+                while let Some(child) = self.child()? {
+                    if matches!(child.name.as_str(), "type" | "array" | "callback" | "varargs") {
+                        if let Some(typ) = self.anytype(&child)? {
+                            children.push(typ);
+                        }
+                    } else {
+                        self.other(&mut doc, &child)?;
+                    }
                 }
-                "source-position" => {
-                    let docs = get_source_position(e);
-                    doc_pos = docs;
+                Ok(Some(AnyType::Type(Type {
+                    name: tag.attr("name"),
+                    ctype: tag.attr("type"),
+                    introspectable: tag.bool("introspectable"),
+                    doc,
+                    children,
+                })))
+            }
+            "array" => {
+                let mut typ = None;
+                while let Some(child) = self.child()? {
+                    if !self.type_element(&mut typ, &child)? {
+                        self.skip()?;
+                    }
                 }
-                _ => {}
+                Ok(typ.map(|typ| AnyType::Array(Array {
+                    name: tag.attr("name"),
+                    zero_terminated: tag.bool("zero-terminated"),
+                    fixed_size: tag.value("fixed-size"),
+                    introspectable: tag.bool("introspectable"),
+                    length: tag.value("length"),
+                    ctype: tag.attr("type"),
+                    typ: Box::new(typ),
+                })))
+            }
+            "callback" => {
+                let fun = self.symbol(tag, |p, tag| p.function(tag, FunctionType::Callback))?;
+                Ok(fun.map(|fun| AnyType::Callback(Box::new(fun))))
+            }
+            "varargs" => {
+                self.skip()?;
+                Ok(Some(AnyType::VarArg))
+            }
+            _ => {
+                self.skip()?;
+                Ok(None)
             }
         }
     }
-    Some(InfoElements{
-        doc,
-        doc_stability,
-        doc_version,
-        doc_deprecated,
-        doc_pos,
-    })
-}
 
-fn read_class(e: &Element) -> Option<Class> {
-    let name = attribute(e, "name")?;
-    let glib_type_name = attribute(e, "type-name")?;
-    let glib_get_type = attribute(e, "get-type")?;
-    let parent = attribute(e, "parent");
-    let glib_type_struct = attribute(e, "type-stuct");
-    let ref_func = attribute(e, "ref-func");
-    let unref_func = attribute(e, "unref-func");
-    let set_value_func = attribute(e, "set-value-func");
-    let get_value_func = attribute(e, "get-value-func");
-    let ctype = attribute(e, "type");
-    let symbol_prefix = attribute(e, "symbol-prefix");
-    let abstracts = attribute(e, "abstract");
-    let glib_fundamental = attribute(e, "fundamental");
-    let finals = attribute(e, "final");
-
-    let info = read_infoattrs(e)?;
-
-    let mut constructor = vec![];
-    let mut functions = vec![];
-    let mut method = vec![];
-    let mut virtual_method = vec![];
-    let mut callbacks = vec![];
-
-    let mut record = vec![];
-    let mut fields = vec![];
-    let mut signals = vec![];
-    let mut unions = vec![];
-    let mut constant = vec![];
-    let mut properties = vec![];
-    let mut implements = vec![];
-
-    let doc = read_infoelements(e)?;
-
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
-                "constructor" => {
-                    if let Some(fun) = read_function(e, FunctionType::Constructor) {
-                        constructor.push(fun)
-                    }
-                }
-                "function" => {
-                    if let Some(fun) = read_function(e, FunctionType::Function) {
-                        functions.push(fun)
-                    }
-                }
-                "method" => {
-                    if let Some(fun) = read_function(e, FunctionType::Method) {
-                        method.push(fun)
-                    }
-                }
-                "virtual-method" => { 
-                    if let Some(fun) = read_function(e, FunctionType::Virtual) {
-                        virtual_method.push(fun)
-                    }
-                }
-                "callback" => { 
-                    if let Some(fun) = read_function(e, FunctionType::Callback) {
-                        callbacks.push(fun)
-                    }
-                }
-                "union" => { 
-                    if let Some(fun) = read_union(e) {
-                        unions.push(fun)
-                    }
-                }
-                "constant" => { 
-                    if let Some(fun) = read_constant(e) {
-                        constant.push(fun)
-                    }
-                }
-                "record" => { 
-                    if let Some(fun) = read_record(e) {
-                        record.push(fun)
-                    }
-                }
-                "field" => { 
-                    if let Some(fun) = read_field(e) {
-                        fields.push(fun)
-                    }
-                }
-                "property" => {
-                    if let Some(prop) = read_property(e) {
-                        properties.push(prop)
-                    }
-                }
-                "signal" => {
-                    if let Some(fun) = read_signal(e) {
-                        signals.push(fun)
-                    }
-                }
-                "implements" => {
-                    if let Some(str) = attribute(e, "name"){
-                        implements.push(Implement { name: str })
+    fn param(&mut self, tag: &Tag) -> ParseResult<Option<Parameter>> {
+        let mut doc = info_elements();
+        let mut typ = None;
+        while let Some(child) = self.child()? {
+            if !self.type_element(&mut typ, &child)? {
+                self.other(&mut doc, &child)?;
+            }
+        }
+        Ok(typ.map(|typ| Parameter {
+            name: tag.attr("name").unwrap_or_default(),
+            instance: tag.name == "instance-parameter",
+            nullable: tag.bool("nullable").unwrap_or(false),
+            allow_none: tag.bool("allow-none").unwrap_or(false),
+            introspectable: tag.bool("introspectable"),
+            closure: tag.attr("closure"),
+            destroy: tag.attr("destroy"),
+            scope: tag.attr("scope"),
+            direction: tag.value("direction"),
+            caller_allocates: tag.bool("caller-allocates").unwrap_or(false),
+            optional: tag.bool("optional").unwrap_or(false),
+            skip: tag.bool("skip").unwrap_or(false),
+            transfer: tag.value("transfer-ownership"),
+            doc,
+            typ,
+        }))
+    }
+
+    /// The parameters, None if there is anything else in the element
+    fn params(&mut self) -> ParseResult<Option<Vec<Parameter>>> {
+        let mut ret = Some(vec![]);
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
+                "parameter" | "instance-parameter" => {
+                    if let Some(param) = self.symbol(&child, Self::param)? {
+                        if let Some(ref mut ret) = ret {
+                            ret.push(param);
+                        }
                     }
                 }
                 _ => {
+                    self.skip()?;
+                    ret = None;
                 }
             }
         }
+        Ok(ret)
     }
-    Some(Class {
-        info,
-        name,
-        glib_type_name,
-        glib_get_type,
-        parent,
-        glib_type_struct,
-        ref_func,
-        unref_func,
-        set_value_func,
-        get_value_func,
-        ctype,
-        symbol_prefix,
-        abstracts,
-        glib_fundamental,
-        finals,
-        constructor,
-        functions,
-        method,
-        virtual_method,
-        callbacks,
-        doc,
-        record,
-        fields,
-        signals,
-        unions,
-        constant,
-        properties,
-        implements,
-    })
-}
 
-fn read_field(e: &Element) -> Option<Field> {
-    let name = attribute(e, "name")?;
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-    
-    let typ = read_anytype(e)?;
-    let writeable = attr_bool(e, "writeable").unwrap_or(false);
-    let readable = attr_bool(e, "readable").unwrap_or(true);
-    let private = attr_bool(e, "private").unwrap_or(false);
-    let bits = attr_value(e, "bits");
-
-    Some(Field {
-        name,
-        info,
-        doc,
-        typ,
-        writeable,
-        readable,
-        private,
-        bits,
-    })
-}
+    /// The return value and parameters of a function or signal, from the
+    /// first `return-value` and `parameters`
+    fn callable(&mut self, child: &Tag, ret: &mut Option<Option<Parameter>>,
+        params: &mut Option<Option<Vec<Parameter>>>) -> ParseResult<bool> {
+        match child.name.as_str() {
+            "return-value" if ret.is_none() => *ret = Some(self.param(child)?),
+            "parameters" if params.is_none() => *params = Some(self.params()?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
 
-fn read_record(e: &Element) -> Option<Record> {
-    let name = attribute(e, "name")?;
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-
-    let c_type = attribute(e, "value");
-    let disguised = attr_bool(e, "disguised");
-    let symbol_prefix = attribute(e, "symbol-prefix");
-    let glib_get_type = attribute(e, "get-type");
-    let glib_type_name = attribute(e, "type-name");
-    let glib_is_gtype_struct_for = attribute(e, "is-gtype-struct-for");
-    let foreign = attr_bool(e, "foreign");
-
-    let mut constructor = vec![];
-    let mut functions = vec![];
-    let mut method = vec![];
-
-    let mut fields = vec![];
-    let mut unions = vec![];
-
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
-                "constructor" => {
-                    if let Some(fun) = read_function(e, FunctionType::Constructor) {
-                        constructor.push(fun)
-                    }
-                }
-                "function" => {
-                    if let Some(fun) = read_function(e, FunctionType::Function) {
-                        functions.push(fun)
-                    }
-                }
-                "method" => {
-                    if let Some(fun) = read_function(e, FunctionType::Method) {
-                        method.push(fun)
-                    }
-                }
-                "union" => { 
-                    if let Some(fun) = read_union(e) {
-                        unions.push(fun)
-                    }
-                }
-                "field" => { 
-                    if let Some(fun) = read_field(e) {
-                        fields.push(fun)
-                    }
-                }
-                _ => {
-                }
+    fn function(&mut self, tag: &Tag, typ: FunctionType) -> ParseResult<Option<Function>> {
+        let mut doc = info_elements();
+        let mut ret = None;
+        let mut params = None;
+        while let Some(child) = self.child()? {
+            if !self.callable(&child, &mut ret, &mut params)? {
+                self.other(&mut doc, &child)?;
             }
         }
+        let Some(name) = tag.attr("name") else {
+            return Ok(None)
+        };
+        Ok(Some(Function {
+            info: info_attrs(tag),
+            doc,
+            typ,
+            name,
+            introspectable: tag.bool("introspectable"),
+            c_identifier: tag.attr("identifier"),
+            shadowed_by: tag.attr("shadowed-by"),
+            shadows: tag.attr("shadows"),
+            throws: tag.bool("throws"),
+            moved_to: tag.attr("moved-to"),
+            c_type: tag.attr("type"),
+            invoker: tag.attr("invoker"),
+            parameters: params.flatten().unwrap_or_default(),
+            ret: ret.flatten(),
+        }))
     }
-    Some(Record{
-        name,
-        info,
-        doc,
-        c_type,
-        disguised,
-        symbol_prefix,
-        glib_get_type,
-        glib_type_name,
-        glib_is_gtype_struct_for,
-        foreign,
-        fields,
-        unions,
-        constructor,
-        functions,
-        method,
-    })
-}
 
-fn read_constant(e: &Element) -> Option<Constant> {
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-
-    let name = attribute(e, "name")?;
-    let value = attribute(e, "value")?;
-    let c_identifier = attribute(e, "identifier");
-    let c_type = attribute(e, "type");
-    let typ = read_anytype(e);
-
-    Some(Constant {
-        name,
-        c_identifier,
-        info,
-        doc,
-        value,
-        c_type,
-        typ,
-    })
-}
-fn read_union(e: &Element) -> Option<Union> {
-    let name = attribute(e, "name");
-    let c_type = attribute(e, "type");
-    let glib_type_name = attribute(e, "type-name");
-    let glib_get_type = attribute(e, "get-type");
-    let symbol_prefix = attribute(e, "symbol-prefix");
-
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-
-    let mut constructor = vec![];
-    let mut functions = vec![];
-    let mut method = vec![];
-
-    let mut record = vec![];
-    let mut fields = vec![];
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
-                "constructor" => {
-                    if let Some(fun) = read_function(e, FunctionType::Constructor) {
-                        constructor.push(fun)
-                    }
-                }
-                "function" => {
-                    if let Some(fun) = read_function(e, FunctionType::Function) {
-                        functions.push(fun)
-                    }
-                }
-                "method" => {
-                    if let Some(fun) = read_function(e, FunctionType::Method) {
-                        method.push(fun)
-                    }
-                }
-                "record" => { 
-                    if let Some(fun) = read_record(e) {
-                        record.push(fun)
-                    }
-                }
-                "field" => { 
-                    if let Some(fun) = read_field(e) {
-                        fields.push(fun)
-                    }
-                }
-                _name => {
-                }
+    fn signal(&mut self, tag: &Tag) -> ParseResult<Option<Signal>> {
+        let mut doc = info_elements();
+        let mut ret = None;
+        let mut params = None;
+        while let Some(child) = self.child()? {
+            if !self.callable(&child, &mut ret, &mut params)? {
+                self.other(&mut doc, &child)?;
             }
         }
+        let Some(name) = tag.attr("name") else {
+            return Ok(None)
+        };
+        Ok(Some(Signal {
+            name,
+            info: info_attrs(tag),
+            doc,
+            detailed: tag.bool("detailed"),
+            when: tag.attr("when"),
+            action: tag.bool("action"),
+            no_hooks: tag.bool("no-hooks"),
+            no_recurse: tag.bool("no-recurse"),
+            emitter: tag.attr("emitter"),
+            parameters: params.flatten().unwrap_or_default(),
+            ret: ret.flatten(),
+        }))
     }
 
-    Some(Union {
-        name,
-        info,
-        doc,
-        c_type,
-        symbol_prefix,
-        glib_type_name,
-        glib_get_type,
-        fields,
-        constructor,
-        method,
-        functions,
-        record,
-    })
-}
+    fn macro_param(&mut self, tag: &Tag) -> ParseResult<Option<MacroParam>> {
+        let mut doc = info_elements();
+        while let Some(child) = self.child()? {
+            self.other(&mut doc, &child)?;
+        }
+        Ok(tag.attr("name").map(|name| MacroParam { name, doc }))
+    }
 
-fn read_namespace(e: &Element) -> Option<Namespace> {
-    let name = attribute(e, "name");
-    let version = attribute(e, "version");
-
-    let shared_library = attribute(e, "shared-library");
-    let identifier_prefixes = attribute(e, "identifier-prefixes");
-    let symbol_prefixes= attribute(e, "symbol-prefixes");
-    let prefix = attribute(e, "prefix");
-    // let shared_library = s.split(',').collect();
-
-
-    let mut classes = vec![];
-    let mut functions = vec![];
-    let mut macros = vec![];
-    let mut callback = vec![];
-
-    let mut enums = vec![];
-    let mut record = vec![];
-    let mut constant = vec![];
-    let mut bitfield = vec![];
-    let mut interfaces = vec![];
-    let mut alias = vec![];
-    let mut unions = vec![];
-    let mut boxed = vec![];
-
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
-                "class" => {
-                    if let Some(class) = read_class(e) {
-                        classes.push(class);
-                    }
-                }
-                "function" => {
-                    if let Some(fun) = read_function(e, FunctionType::Function) {
-                        functions.push(fun);
-                    }
-                }
-                "function-macro" => {
-                    if let Some(fun) = read_macro(e) {
-                        macros.push(fun);
-                    }
-                }
-                "callback" => {
-                    if let Some(cb) = read_function(e, FunctionType::Callback) {
-                        callback.push(cb);
-                    }
-                }
-                "enumeration" => {
-                    if let Some(enu) = read_enum(e) {
-                        enums.push(enu);
-                    }
-                }
-                "record" => {
-                    if let Some(rec) = read_record(e) {
-                        record.push(rec);
-                    }
-                }
-                "constant" => {
-                    if let Some(consts) = read_constant(e) {
-                        constant.push(consts);
-                    }
-                }
-                "bitfield" => {
-                    if let Some(bf) = read_bitfield(e) {
-                        bitfield.push(bf)
-                    }
-                }
-                "union" => {
-                    if let Some(union) = read_union(e) {
-                        unions.push(union)
-                    }
-                }
-                "docsection" => {
-                }
-                "name" => {
-                }
-                "alias" => {
-                    if let Some(bf) = read_alias(e) {
-                        alias.push(bf)
-                    }
-                }
-                "interface" => {
-                    if let Some(bf) = read_interface(e) {
-                        interfaces.push(bf)
-                    }
-                }
-                "boxed" => {
-                    if let Some(bf) = read_boxed(e) {
-                        boxed.push(bf)
+    fn macro_(&mut self, tag: &Tag) -> ParseResult<Option<Macro>> {
+        let mut doc = info_elements();
+        let mut parameters = None;
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
+                "parameters" if parameters.is_none() => {
+                    // macro parameters have no type, they aren't failures
+                    let mut params = vec![];
+                    while let Some(param) = self.child()? {
+                        if param.name == "parameter" {
+                            params.extend(self.macro_param(&param)?);
+                        } else {
+                            self.skip()?;
+                        }
                     }
+                    parameters = Some(params);
                 }
-                _ => {
-                }
+                _ => self.other(&mut doc, &child)?,
             }
         }
+        required(|| Some(Macro {
+            info: info_attrs(tag),
+            doc,
+            name: tag.attr("name")?,
+            c_identifier: tag.attr("identifier"),
+            parameters: parameters?,
+        }))
     }
-    Some(Namespace {
-        name,
-        version,
-        shared_library,
-        identifier_prefixes,
-        symbol_prefixes,
-        prefix,
-        classes,
-        functions,
-        macros,
-        callback,
-        interfaces,
-        enums,
-        record,
-        constant,
-        bitfield,
-        alias,
-        unions,
-        boxed,
-    })
-}
 
-fn read_alias(e: &Element) -> Option<Alias> {
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-    let name = attribute(e, "name")?;
-    let c_type = attribute(e, "type")?;
-    let typ = read_anytype(e)?;
-
-    let mut functions = vec![];
-
-    // XXX: Should we even do this?
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            if e.name.as_str() == "constructor" {
-                if let Some(fun) = read_function(e, FunctionType::Constructor) {
-                    functions.push(fun) 
-                }
+    fn property(&mut self, tag: &Tag) -> ParseResult<Option<Property>> {
+        let mut doc = info_elements();
+        let mut typ = None;
+        while let Some(child) = self.child()? {
+            if !self.type_element(&mut typ, &child)? {
+                self.other(&mut doc, &child)?;
             }
         }
+        required(|| Some(Property {
+            name: tag.attr("name")?,
+            info: info_attrs(tag),
+            doc,
+            readable: tag.bool("readable").unwrap_or(true),
+            writable: tag.bool("writable").unwrap_or(false),
+            construct: tag.bool("construct").unwrap_or(false),
+            construct_only: tag.bool("construct-only").unwrap_or(false),
+            setter: tag.attr("setter"),
+            getter: tag.attr("getter"),
+            transfer: tag.value("transfer-ownership"),
+            typ: typ?,
+        }))
     }
-    Some(Alias {
-        name,
-        info,
-        doc,
-        c_type,
-        typ,
-    })
-}
 
-fn read_interface(e: &Element) -> Option<Interface> {
-    let info = read_infoattrs(e)?;
-    let name = attribute(e, "name")?;
+    fn field(&mut self, tag: &Tag) -> ParseResult<Option<Field>> {
+        let mut doc = info_elements();
+        let mut typ = None;
+        while let Some(child) = self.child()? {
+            if !self.type_element(&mut typ, &child)? {
+                self.other(&mut doc, &child)?;
+            }
+        }
+        required(|| Some(Field {
+            name: tag.attr("name")?,
+            info: info_attrs(tag),
+            doc,
+            typ: typ?,
+            writeable: tag.bool("writable").unwrap_or(false),
+            readable: tag.bool("readable").unwrap_or(true),
+            private: tag.bool("private").unwrap_or(false),
+            bits: tag.value("bits"),
+        }))
+    }
 
-    let glib_type_name = attribute(e, "type-name")?;
-    let glib_get_type = attribute(e, "get-type")?;
-    let symbol_prefix = attribute(e, "symbol-prefix");
-    let c_type = attribute(e, "type");
-    let glib_type_struct = attribute(e, "type-struct");
+    fn constant(&mut self, tag: &Tag) -> ParseResult<Option<Constant>> {
+        let mut doc = info_elements();
+        let mut typ = None;
+        while let Some(child) = self.child()? {
+            if !self.type_element(&mut typ, &child)? {
+                self.other(&mut doc, &child)?;
+            }
+        }
+        required(|| Some(Constant {
+            name: tag.attr("name")?,
+            c_identifier: tag.attr("identifier"),
+            info: info_attrs(tag),
+            doc,
+            value: tag.attr("value")?,
+            c_type: tag.attr("type"),
+            typ,
+        }))
+    }
 
-    let mut constructor = None;
-    let mut functions = vec![];
-    let mut method = vec![];
-    let mut virtual_method = vec![];
-    let mut callbacks = vec![];
+    fn member(&mut self, tag: &Tag) -> ParseResult<Option<Member>> {
+        let mut doc = info_elements();
+        while let Some(child) = self.child()? {
+            self.other(&mut doc, &child)?;
+        }
+        required(|| Some(Member {
+            info: info_attrs(tag),
+            doc,
+            name: tag.attr("name")?,
+            value: tag.attr("value")?,
+            c_identifier: tag.attr("identifier"),
+            glib_nick: tag.attr("nick"),
+        }))
+    }
 
-    let mut prerequisites = vec![];
-    let mut implements = vec![];
+    fn class(&mut self, tag: &Tag) -> ParseResult<Option<Class>> {
+        let mut doc = info_elements();
+        let mut constructor = vec![];
+        let mut functions = vec![];
+        let mut method = vec![];
+        let mut virtual_method = vec![];
+        let mut callbacks = vec![];
+        let mut record = vec![];
+        let mut fields = vec![];
+        let mut signals = vec![];
+        let mut unions = vec![];
+        let mut constant = vec![];
+        let mut properties = vec![];
+        let mut implements = vec![];
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
+                "constructor" => constructor.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Constructor))?),
+                "function" => functions.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Function))?),
+                "method" => method.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Method))?),
+                "virtual-method" => virtual_method.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Virtual))?),
+                "callback" => callbacks.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Callback))?),
+                "union" => unions.extend(self.symbol(&child, Self::union)?),
+                "constant" => constant.extend(self.symbol(&child, Self::constant)?),
+                "record" => record.extend(self.symbol(&child, Self::record)?),
+                "field" => fields.extend(self.symbol(&child, Self::field)?),
+                "property" => properties.extend(self.symbol(&child, Self::property)?),
+                "signal" => signals.extend(self.symbol(&child, Self::signal)?),
+                "implements" => {
+                    implements.extend(child.attr("name").map(|name| Implement { name }));
+                    self.skip()?;
+                }
+                _ => self.other(&mut doc, &child)?,
+            }
+        }
+        required(|| Some(Class {
+            info: info_attrs(tag),
+            name: tag.attr("name")?,
+            glib_type_name: tag.attr("type-name")?,
+            glib_get_type: tag.attr("get-type")?,
+            parent: tag.attr("parent"),
+            glib_type_struct: tag.attr("type-struct"),
+            ref_func: tag.attr("ref-func"),
+            unref_func: tag.attr("unref-func"),
+            set_value_func: tag.attr("set-value-func"),
+            get_value_func: tag.attr("get-value-func"),
+            ctype: tag.attr("type"),
+            symbol_prefix: tag.attr("symbol-prefix"),
+            abstracts: tag.attr("abstract"),
+            glib_fundamental: tag.attr("fundamental"),
+            finals: tag.attr("final"),
+            constructor,
+            functions,
+            method,
+            virtual_method,
+            callbacks,
+            doc,
+            record,
+            fields,
+            signals,
+            unions,
+            constant,
+            properties,
+            implements,
+        }))
+    }
 
-    let mut fields = vec![];
-    let mut signals = vec![];
-    let mut constant = vec![];
-    let mut properties = vec![];
+    fn record(&mut self, tag: &Tag) -> ParseResult<Option<Record>> {
+        let mut doc = info_elements();
+        let mut constructor = vec![];
+        let mut functions = vec![];
+        let mut method = vec![];
+        let mut fields = vec![];
+        let mut unions = vec![];
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
+                "constructor" => constructor.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Constructor))?),
+                "function" => functions.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Function))?),
+                "method" => method.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Method))?),
+                "union" => unions.extend(self.symbol(&child, Self::union)?),
+                "field" => fields.extend(self.symbol(&child, Self::field)?),
+                _ => self.other(&mut doc, &child)?,
+            }
+        }
+        required(|| Some(Record {
+            name: tag.attr("name")?,
+            info: info_attrs(tag),
+            doc,
+            c_type: tag.attr("type"),
+            disguised: tag.bool("disguised"),
+            symbol_prefix: tag.attr("symbol-prefix"),
+            glib_get_type: tag.attr("get-type"),
+            glib_type_name: tag.attr("type-name"),
+            glib_is_gtype_struct_for: tag.attr("is-gtype-struct-for"),
+            foreign: tag.bool("foreign"),
+            fields,
+            unions,
+            constructor,
+            functions,
+            method,
+        }))
+    }
 
-    let doc = read_infoelements(e)?;
+    fn union(&mut self, tag: &Tag) -> ParseResult<Option<Union>> {
+        let mut doc = info_elements();
+        let mut constructor = vec![];
+        let mut functions = vec![];
+        let mut method = vec![];
+        let mut record = vec![];
+        let mut fields = vec![];
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
+                "constructor" => constructor.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Constructor))?),
+                "function" => functions.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Function))?),
+                "method" => method.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Method))?),
+                "record" => record.extend(self.symbol(&child, Self::record)?),
+                "field" => fields.extend(self.symbol(&child, Self::field)?),
+                _ => self.other(&mut doc, &child)?,
+            }
+        }
+        Ok(Some(Union {
+            name: tag.attr("name"),
+            info: info_attrs(tag),
+            doc,
+            c_type: tag.attr("type"),
+            symbol_prefix: tag.attr("symbol-prefix"),
+            glib_type_name: tag.attr("type-name"),
+            glib_get_type: tag.attr("get-type"),
+            fields,
+            constructor,
+            method,
+            functions,
+            record,
+        }))
+    }
 
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
+    fn interface(&mut self, tag: &Tag) -> ParseResult<Option<Interface>> {
+        let mut doc = info_elements();
+        let mut constructor = None;
+        let mut functions = vec![];
+        let mut method = vec![];
+        let mut virtual_method = vec![];
+        let mut callbacks = vec![];
+        let mut prerequisites = vec![];
+        let mut implements = vec![];
+        let mut fields = vec![];
+        let mut signals = vec![];
+        let mut constant = vec![];
+        let mut properties = vec![];
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
                 "constructor" => {
-                    if let Some(fun) = read_function(e, FunctionType::Constructor) {
-                        constructor = Some(fun)
-                    }
-                }
-                "function" => {
-                    if let Some(fun) = read_function(e, FunctionType::Function) {
-                        functions.push(fun)
-                    }
-                }
-                "method" => {
-                    if let Some(fun) = read_function(e, FunctionType::Method) {
-                        method.push(fun)
-                    }
-                }
-                "virtual-method" => { 
-                    if let Some(fun) = read_function(e, FunctionType::Virtual) {
-                        virtual_method.push(fun)
-                    }
-                }
-                "callback" => { 
-                    if let Some(fun) = read_function(e, FunctionType::Callback) {
-                        callbacks.push(fun)
-                    }
-                }
-                "constant" => { 
-                    if let Some(fun) = read_constant(e) {
-                        constant.push(fun)
-                    }
-                }
-                "field" => { 
-                    if let Some(fun) = read_field(e) {
-                        fields.push(fun)
-                    }
-                }
-                "property" => {
-                    if let Some(prop) = read_property(e) {
-                        properties.push(prop)
+                    if let Some(fun) = self.symbol(&child, |p, t| p.function(t, FunctionType::Constructor))? {
+                        constructor = Some(fun);
                     }
                 }
-                "signal" => {
-                    if let Some(fun) = read_signal(e) {
-                        signals.push(fun)
-                    }
-                }
-                "prerequisites" => {
-                    if let Some(str) = attribute(e, "name"){
-                        prerequisites.push(str)
-                    }
+                "function" => functions.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Function))?),
+                "method" => method.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Method))?),
+                "virtual-method" => virtual_method.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Virtual))?),
+                "callback" => callbacks.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Callback))?),
+                "constant" => constant.extend(self.symbol(&child, Self::constant)?),
+                "field" => fields.extend(self.symbol(&child, Self::field)?),
+                "property" => properties.extend(self.symbol(&child, Self::property)?),
+                "signal" => signals.extend(self.symbol(&child, Self::signal)?),
+                "prerequisite" => {
+                    prerequisites.extend(child.attr("name"));
+                    self.skip()?;
                 }
                 "implements" => {
-                    if let Some(str) = attribute(e, "name"){
-                        implements.push(str)
-                    }
-                }
-                _ => {
+                    implements.extend(child.attr("name"));
+                    self.skip()?;
                 }
+                _ => self.other(&mut doc, &child)?,
             }
         }
+        required(|| Some(Interface {
+            name: tag.attr("name")?,
+            info: info_attrs(tag),
+            doc,
+            glib_type_name: tag.attr("type-name")?,
+            glib_get_type: tag.attr("get-type")?,
+            symbol_prefix: tag.attr("symbol-prefix"),
+            c_type: tag.attr("type"),
+            glib_type_struct: tag.attr("type-struct"),
+            constructor,
+            prerequisites,
+            implements,
+            functions,
+            method,
+            virtual_method,
+            callbacks,
+            fields,
+            properties,
+            signals,
+            constant,
+        }))
     }
-    Some(Interface {
-        name,
-        info,
-        doc,
-        glib_type_name,
-        glib_get_type,
-        symbol_prefix,
-        c_type,
-        glib_type_struct,
-        constructor,
-        prerequisites,
-        implements,
-        functions,
-        method,
-        virtual_method,
-        callbacks,
-        fields,
-        properties,
-        signals,
-        constant,
-    })
-}
 
-fn read_boxed(e: &Element) -> Option<Boxed> {
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-    let glib_name = attribute(e, "name")?;
-
-    let symbol_prefix = attribute(e, "symbol-prefix");
-    let glib_type_name = attribute(e, "type-name");
-    let glib_get_type = attribute(e, "get-type");
-    let mut functions = vec![];
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
-                "function" => {
-                    if let Some(fun) = read_function(e, FunctionType::Function) {
-                        functions.push(fun);
-                    }
-                },
-                _ => panic!("Only functions allowed as boxed children")
+    fn boxed(&mut self, tag: &Tag) -> ParseResult<Option<Boxed>> {
+        let mut doc = info_elements();
+        let mut functions = vec![];
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
+                "function" => functions.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Function))?),
+                _ => self.other(&mut doc, &child)?,
             }
         }
+        required(|| Some(Boxed {
+            glib_name: tag.attr("name")?,
+            info: info_attrs(tag),
+            doc,
+            symbol_prefix: tag.attr("symbol-prefix"),
+            glib_type_name: tag.attr("type-name"),
+            glib_get_type: tag.attr("get-type"),
+            functions,
+        }))
     }
 
-    Some(Boxed {
-        glib_name,
-        info,
-        doc,
-        symbol_prefix,
-        glib_type_name,
-        glib_get_type,
-        functions,
-    })
-}
+    fn enumeration(&mut self, tag: &Tag) -> ParseResult<Option<Enumeration>> {
+        let mut doc = info_elements();
+        let mut members = vec![];
+        let mut functions = vec![];
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
+                "member" => members.extend(self.symbol(&child, Self::member)?),
+                "function" => functions.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Member))?),
+                _ => self.other(&mut doc, &child)?,
+            }
+        }
+        required(|| Some(Enumeration {
+            info: info_attrs(tag),
+            doc,
+            name: tag.attr("name")?,
+            c_type: tag.attr("type")?,
+            glib_type_name: tag.attr("type-name"),
+            glib_get_type: tag.attr("get-type"),
+            glib_error_domain: tag.attr("error-domain"),
+            members,
+            functions,
+        }))
+    }
 
-fn read_bitfield(e: &Element) -> Option<Bitfield> {
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-    let name = attribute(e, "name")?;
+    fn bitfield(&mut self, tag: &Tag) -> ParseResult<Option<Bitfield>> {
+        let mut doc = info_elements();
+        let mut members = vec![];
+        let mut functions = vec![];
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
+                "member" => members.extend(self.symbol(&child, Self::member)?),
+                "function" => functions.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Member))?),
+                _ => self.other(&mut doc, &child)?,
+            }
+        }
+        required(|| Some(Bitfield {
+            info: info_attrs(tag),
+            doc,
+            name: tag.attr("name")?,
+            c_type: tag.attr("type")?,
+            glib_type_name: tag.attr("type-name"),
+            glib_get_type: tag.attr("get-type"),
+            members,
+            functions,
+        }))
+    }
 
-    let c_type = attribute(e, "type")?;
-    let glib_type_name = attribute(e, "type-name");
-    let glib_get_type = attribute(e, "get-type");
+    fn alias(&mut self, tag: &Tag) -> ParseResult<Option<Alias>> {
+        let mut doc = info_elements();
+        let mut typ = None;
+        while let Some(child) = self.child()? {
+            if child.name == "constructor" {
+                // XXX: Should we even do this?
+                self.symbol(&child, |p, t| p.function(t, FunctionType::Constructor))?;
+            } else if !self.type_element(&mut typ, &child)? {
+                self.other(&mut doc, &child)?;
+            }
+        }
+        required(|| Some(Alias {
+            name: tag.attr("name")?,
+            info: info_attrs(tag),
+            doc,
+            c_type: tag.attr("type")?,
+            typ: typ?,
+        }))
+    }
 
-    let mut functions = vec![];
-    let mut members = vec![];
+    fn docsection(&mut self, tag: &Tag) -> ParseResult<Option<DocSection>> {
+        let mut doc = info_elements();
+        while let Some(child) = self.child()? {
+            self.other(&mut doc, &child)?;
+        }
+        Ok(tag.attr("name").map(|name| DocSection { name, doc }))
+    }
 
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
-                "function" => {
-                    if let Some(fun) = read_function(e, FunctionType::Member) {
-                        functions.push(fun);
-                    }
-                }
-                "member" => {
-                    if let Some(mem) = read_member(e) {
-                        members.push(mem);
-                    }
-                }
-                _ => {
-                }
+    fn namespace(&mut self, tag: &Tag) -> ParseResult<Namespace> {
+        self.ns = Some(tag.attr("name").unwrap_or_default());
+        let mut classes = vec![];
+        let mut functions = vec![];
+        let mut macros = vec![];
+        let mut callback = vec![];
+        let mut enums = vec![];
+        let mut record = vec![];
+        let mut constant = vec![];
+        let mut bitfield = vec![];
+        let mut interfaces = vec![];
+        let mut alias = vec![];
+        let mut unions = vec![];
+        let mut boxed = vec![];
+        let mut docsections = vec![];
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
+                "class" => classes.extend(self.symbol(&child, Self::class)?),
+                "function" => functions.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Function))?),
+                "function-macro" => macros.extend(self.symbol(&child, Self::macro_)?),
+                "callback" => callback.extend(self.symbol(&child, |p, t| p.function(t, FunctionType::Callback))?),
+                "enumeration" => enums.extend(self.symbol(&child, Self::enumeration)?),
+                "record" => record.extend(self.symbol(&child, Self::record)?),
+                "constant" => constant.extend(self.symbol(&child, Self::constant)?),
+                "bitfield" => bitfield.extend(self.symbol(&child, Self::bitfield)?),
+                "union" => unions.extend(self.symbol(&child, Self::union)?),
+                "docsection" => docsections.extend(self.docsection(&child)?),
+                "alias" => alias.extend(self.symbol(&child, Self::alias)?),
+                "interface" => interfaces.extend(self.symbol(&child, Self::interface)?),
+                "boxed" => boxed.extend(self.symbol(&child, Self::boxed)?),
+                _ => self.skip()?,
             }
         }
+        self.ns = None;
+        Ok(Namespace {
+            name: tag.attr("name"),
+            version: tag.attr("version"),
+            shared_library: tag.attr("shared-library"),
+            identifier_prefixes: tag.attr("identifier-prefixes"),
+            symbol_prefixes: tag.attr("symbol-prefixes"),
+            prefix: tag.attr("prefix"),
+            classes,
+            functions,
+            macros,
+            callback,
+            interfaces,
+            enums,
+            record,
+            constant,
+            bitfield,
+            alias,
+            unions,
+            boxed,
+            docsections,
+        })
     }
-    Some(Bitfield {
-        info,
-        doc,
-        name,
-        c_type,
-        glib_type_name,
-        glib_get_type,
-        members,
-        functions,
-    })
-}
-
-fn read_member(e: &Element) -> Option<Member> {
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-    let name = attribute(e, "name")?;
-    let value = attribute(e, "value")?;
-    let c_identifier = attribute(e, "identifier");
-    let glib_nick = attribute(e, "nick");
-
-    Some(Member{
-        info,
-        doc,
-        name,
-        value,
-        c_identifier,
-        glib_nick,
-    })
-}
 
-fn read_enum(e: &Element) -> Option<Enumeration> {
-    let name = attribute(e, "name")?;
-    let info = read_infoattrs(e)?;
-    let doc = read_infoelements(e)?;
-
-    let c_type = attribute(e, "type")?;
-    let glib_type_name = attribute(e, "type-name");
-    let glib_get_type = attribute(e, "get-type");
-    let glib_error_domain = attribute(e, "error-domain");
-
-    let mut members = vec![];
-    let mut functions = vec![];
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
-                "member" => {
-                    if let Some(class) = read_member(e) {
-                        members.push(class);
-                    }
+    fn repository(&mut self) -> ParseResult<Repository> {
+        // the root element, after the declaration and comments
+        let tag = loop {
+            match self.next()? {
+                Node::Start(tag) => break tag,
+                Node::Text(_) | Node::End => {}
+            }
+        };
+        self.path.push(tag.attr("name"));
+        let mut include = vec![];
+        let mut cinclude = vec![];
+        let mut package = vec![];
+        let mut namespace = vec![];
+        while let Some(child) = self.child()? {
+            match child.name.as_str() {
+                // c:include has the same local name
+                "include" if child.prefix.as_deref() == Some("c") => {
+                    cinclude.extend(child.attr("name").map(|name| CInclude { name }));
+                    self.skip()?;
                 }
-                "function" => {
-                    if let Some(fun) = read_function(e, FunctionType::Member) {
-                        functions.push(fun);
+                "include" => {
+                    if let Some(name) = child.attr("name") {
+                        include.push(Include { name, version: child.attr("version") });
                     }
+                    self.skip()?;
                 }
-                _ => {
+                "cinclude" => {
+                    cinclude.extend(child.attr("name").map(|name| CInclude { name }));
+                    self.skip()?;
                 }
+                "package" => {
+                    package.extend(child.attr("name").map(|name| Package { name }));
+                    self.skip()?;
+                }
+                "namespace" => namespace.push(self.namespace(&child)?),
+                _ => self.skip()?,
             }
         }
+        Ok(Repository {
+            version: tag.value("version"),
+            xmlns: tag.attr("xmlns"),
+            identifier_prefixes: tag.attr("identifier-prefixes"),
+            symbol_prefixes: tag.attr("symbol-prefixes"),
+            include,
+            cinclude,
+            package,
+            namespace,
+        })
     }
-    Some(Enumeration{
-        info,
-        doc,
-        name,
-        c_type,
-        glib_type_name,
-        glib_get_type,
-        glib_error_domain,
-        members,
-        functions,
-    })
+}
 
+/// The tag of `start`, namespace declarations aren't attributes
+fn tag(start: &BytesStart) -> Tag {
+    let name = start.name();
+    let attrs = start.attributes()
+        .filter_map(|a| a.ok())
+        .filter(|a| a.key.as_namespace_binding().is_none())
+        .map(|a| {
+            let key = String::from_utf8_lossy(a.key.local_name().as_ref()).into_owned();
+            let value = match a.unescape_value() {
+                Ok(value) => value.into_owned(),
+                Err(_) => String::from_utf8_lossy(&a.value).into_owned(),
+            };
+            (key, value)
+        })
+        .collect();
+    Tag {
+        name: String::from_utf8_lossy(name.local_name().as_ref()).into_owned(),
+        prefix: name.prefix().map(|p| String::from_utf8_lossy(p.as_ref()).into_owned()),
+        attrs,
+    }
 }
 
-pub fn attribute(e: &Element, attr: &str) -> Option<String> {
-    e.attributes.get(attr).map(|x| x.to_owned())
+/// What `build` returns, None if a required attribute or child is missing
+fn required<T>(build: impl FnOnce() -> Option<T>) -> ParseResult<Option<T>> {
+    Ok(build())
 }
 
-pub fn attr_value<T>(e: &Element, name: &str) -> Option<T>
-where
-    T: FromStr,
-    T::Err: fmt::Display,
-{
-    if let Some(value_str) = e.attributes.get(name) {
-        match T::from_str(value_str) {
-            Ok(value) => Some(value),
-            Err(_error) => None 
-        }
-    } else {
-        None
+fn info_attrs(tag: &Tag) -> InfoAttrs {
+    InfoAttrs {
+        introspectable: tag.bool("introspectable"),
+        deprecated: tag.bool("deprecated"),
+        deprecated_version: tag.attr("deprecated-version"),
+        version: tag.attr("version"),
+        stability: tag.attr("stability"),
     }
 }
 
-fn read_include(e: &Element) -> Option<Include> {
-    let name = attribute(e, "name")?;
-    let version = attribute(e, "version");
-    Some(Include { 
-        name, 
-        version
-    })
+fn info_elements() -> InfoElements {
+    InfoElements {
+        doc: None,
+        doc_stability: None,
+        doc_version: None,
+        doc_deprecated: None,
+        doc_pos: None,
+    }
 }
-fn read_cinclude(e: &Element) -> Option<CInclude> {
-    let name = attribute(e, "name")?;
-    Some(CInclude { 
-        name, 
+
+fn source_position(tag: &Tag) -> Option<DocPosition> {
+    Some(DocPosition {
+        filename: tag.attr("filename")?,
+        line: tag.attr("line")?,
+        column: tag.attr("column"),
     })
 }
-fn read_package(e: &Element) -> Option<Package> {
-    let name = attribute(e, "name")?;
-    Some(Package { 
-        name, 
-    })
+
+/// Every element `parse_gir` skips because it couldn't be read
+pub fn parse_failures<R: Read>(read: R) -> Result<Vec<ParseFailure>, ParseError> {
+    let mut parser = Parser::new(BufReader::new(read));
+    parser.repository()?;
+    Ok(parser.failures)
 }
 
-// should return repo?
-fn read_repository(e: &Element) -> Option<Repository> {
-    let version = attr_value(e, "version");
-    let xmlns = attribute(e, "xmlns");
-    let identifier_prefixes = attribute(e, "identifier-prefixes");
-    let symbol_prefixes = attribute(e, "symbol-prefixes");
-
-    let mut include = vec![];
-    let mut cinclude = vec![];
-    let mut package = vec![];
-    let mut namespace = vec![];
-    
-    for node in e.children.iter() {
-        if let Some(e) = node.as_element() {
-            match e.name.as_str() {
-                "include" => {
-                    if let Some(ns) = read_include(e) {
-                        include.push(ns)
-                    }
-                }
-                "cinclude" => {
-                    if let Some(ns) = read_cinclude(e) {
-                        cinclude.push(ns)
-                    }
-                }
-                "package" => {
-                    if let Some(ns) = read_package(e) {
-                        package.push(ns)
-                    }
-                }
-                "namespace" => {
-                    if let Some(ns) = read_namespace(e) {
-                        namespace.push(ns)
-                    }
-                }
-                _ => {
-                }
-            }
-        }
-    }
-    Some(Repository {
-        version,
-        xmlns,
-        identifier_prefixes,
-        symbol_prefixes,
-        include,
-        cinclude,
-        package,
-        namespace,
-    })
+pub fn parse_gir<R: Read>(read: R) -> Result<Repository, ParseError> {
+    Parser::new(BufReader::new(read)).repository()
 }
 
-pub fn parse_gir<R: Read>(read: R) -> Result<Repository, xmltree::ParseError> {
-    let names_element = Element::parse(read)?;
-    let repo = read_repository(&names_element);
-    if let Some(repo) = repo {
-        return Ok(repo)
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+    use super::parse_gir;
+    use crate::lang::Generator;
+    use crate::output::json::Json;
+    use crate::sink::MemorySink;
+
+    #[test]
+    fn gmime_works() {
+        let f = std::fs::File::open("testdata/girs/GMime-3.0.gir").unwrap();
+        let repo = Arc::new(parse_gir(f).unwrap());
+        let sink = MemorySink::new();
+        Json::new().generate(&repo, &sink).unwrap();
+        let json = String::from_utf8(sink.get(Path::new("GMime-3.0.json")).unwrap()).unwrap();
+
+        // the snapshot is the output of the xmltree parser
+        let mut settings = insta::Settings::clone_current();
+        settings.set_snapshot_path("../testdata/parse/");
+        settings.set_prepend_module_to_snapshot(false);
+        settings.bind(|| insta::assert_snapshot!("gmime", json));
+    }
+
+    #[test]
+    fn empty_works() {
+        let gir = r#"<?xml version="1.0"?>
+<repository version="1.2"
+            xmlns="http://www.gtk.org/introspection/core/1.0"
+            xmlns:c="http://www.gtk.org/introspection/c/1.0"
+            xmlns:glib="http://www.gtk.org/introspection/glib/1.0">
+  <namespace name="Test" version="1.0">
+    <class name="Empty" glib:type-name="TestEmpty" glib:get-type="test_empty_get_type"/>
+    <class name="Widget" glib:type-name="TestWidget" glib:get-type="test_widget_get_type">
+      <record name="Private"/>
+      <method name="show" c:identifier="test_widget_show">
+        <return-value><type name="none"/></return-value>
+      </method>
+    </class>
+    <enumeration name="Mode" c:type="TestMode"/>
+  </namespace>
+</repository>"#;
+        let repo = parse_gir(gir.as_bytes()).unwrap();
+        let ns = &repo.namespace[0];
+        // an empty element has no end tag, the elements after it are its siblings
+        assert_eq!(ns.classes.len(), 2);
+        assert!(ns.classes[0].method.is_empty() && ns.classes[0].record.is_empty());
+        let widget = &ns.classes[1];
+        assert_eq!(widget.record[0].name, "Private");
+        assert_eq!(widget.method[0].name, "show");
+        assert_eq!(ns.enums[0].name, "Mode");
+    }
+
+    #[test]
+    fn boxed_works() {
+        let gir = r#"<?xml version="1.0"?>
+<repository version="1.2"
+            xmlns="http://www.gtk.org/introspection/core/1.0"
+            xmlns:c="http://www.gtk.org/introspection/c/1.0"
+            xmlns:glib="http://www.gtk.org/introspection/glib/1.0">
+  <namespace name="Test" version="1.0">
+    <glib:boxed glib:name="Value" glib:type-name="TestValue" glib:get-type="test_value_get_type">
+      <doc xml:space="preserve" filename="test.h" line="1">A boxed value.</doc>
+      <source-position filename="test.h" line="3"/>
+      <function name="copy" c:identifier="test_value_copy">
+        <return-value><type name="none"/></return-value>
+      </function>
+    </glib:boxed>
+  </namespace>
+</repository>"#;
+        // documentation and children that aren't read are skipped, not fatal
+        let repo = parse_gir(gir.as_bytes()).unwrap();
+        let boxed = &repo.namespace[0].boxed[0];
+        assert_eq!(boxed.glib_name, "Value");
+        assert_eq!(boxed.doc.doc.as_ref().unwrap().content, "A boxed value.");
+        assert_eq!(boxed.functions[0].name, "copy");
     }
-    Err(xmltree::ParseError::CannotParse)
 }
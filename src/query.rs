@@ -0,0 +1,221 @@
+//! Searching repositories by name, C identifier or the types functions
+//! take and return. Results are rendered as lua, like in the stubs and
+//! the documentation.
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::library::*;
+use crate::lang::lua::{in_param, lua_params, out_param, show_anytyp, signature, translate_ns};
+use crate::visit::{self, Context, Visitor};
+
+#[derive(Debug, Default)]
+pub struct Query {
+    /// A part of the lua name, like `Box:append`, or of the C identifier,
+    /// like `gtk_widget_grab_focus`. Case doesn't matter and `.` matches `:`.
+    pub name: Option<String>,
+    /// Only functions returning this type, as a gir name (`Gio.File`) or lua type (`string`)
+    pub returns: Option<String>,
+    /// Only functions taking a parameter of this type
+    pub takes: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Hit {
+    /// The lua name, like `Gtk.Box:append`
+    pub path: String,
+    /// Like `method`, `class` or `property`
+    pub kind: &'static str,
+    pub c_identifier: Option<String>,
+    /// The lua signature, like `Gtk.Box:append(child: Gtk.Widget)`
+    pub signature: String,
+}
+
+impl Query {
+    /// Every symbol of `repo` matching the query
+    pub fn search(&self, repo: &Repository) -> Result<Vec<Hit>> {
+        let mut search = Search { query: self, hits: vec![] };
+        visit::walk_repository(&mut search, repo)?;
+        Ok(search.hits)
+    }
+
+    fn matches_name(&self, path: &str, c_identifier: Option<&str>) -> bool {
+        let name = match self.name {
+            Some(ref name) => name.to_lowercase().replace(':', "."),
+            None => return true,
+        };
+        path.to_lowercase().replace(':', ".").contains(&name)
+            || c_identifier.is_some_and(|c| c.to_lowercase().contains(&name))
+    }
+
+    fn types_only(&self) -> bool {
+        self.returns.is_some() || self.takes.is_some()
+    }
+}
+
+/// Whether `typ` is or contains `want`, like `GLib.List<Gio.File>` for `Gio.File`
+fn has_type(typ: &AnyType, want: &str, ns: &str) -> bool {
+    if show_anytyp(typ, ns) == want {
+        return true
+    }
+    match typ {
+        AnyType::Type(t) => {
+            t.name.as_deref().is_some_and(|n| n == want || translate_ns(n, ns) == want)
+                || t.children.iter().any(|c| has_type(c, want, ns))
+        }
+        AnyType::Array(a) => a.name.as_deref() == Some(want) || has_type(&a.typ, want, ns),
+        AnyType::Callback(_) | AnyType::VarArg => false,
+    }
+}
+
+struct Search<'q> {
+    query: &'q Query,
+    hits: Vec<Hit>,
+}
+
+impl Search<'_> {
+    fn push(&mut self, path: String, kind: &'static str, c_identifier: Option<&str>, signature: String) {
+        if self.query.matches_name(&path, c_identifier) {
+            self.hits.push(Hit { path, kind, c_identifier: c_identifier.map(str::to_string), signature });
+        }
+    }
+
+    /// Types and properties are only found by name
+    fn push_named(&mut self, path: String, kind: &'static str, c_identifier: Option<&str>, signature: String) {
+        if !self.query.types_only() {
+            self.push(path, kind, c_identifier, signature);
+        }
+    }
+}
+
+impl Visitor for Search<'_> {
+    fn visit_class<'a>(&mut self, class: &'a Class, cx: Context<'a>) -> Result<()> {
+        if !class.info.is_introspectable() {
+            return Ok(())
+        }
+        let path = format!("{}.{}", cx.ns, class.name);
+        let sig = match class.parent {
+            Some(ref parent) => format!("{} : {}", path, translate_ns(parent, cx.ns)),
+            None => path.clone(),
+        };
+        self.push_named(path, "class", class.ctype.as_deref(), sig);
+        visit::walk_class(self, class, cx)
+    }
+
+    fn visit_interface<'a>(&mut self, interface: &'a Interface, cx: Context<'a>) -> Result<()> {
+        if !interface.info.is_introspectable() {
+            return Ok(())
+        }
+        let path = format!("{}.{}", cx.ns, interface.name);
+        self.push_named(path.clone(), "interface", interface.c_type.as_deref(), path);
+        visit::walk_interface(self, interface, cx)
+    }
+
+    fn visit_record<'a>(&mut self, record: &'a Record, cx: Context<'a>) -> Result<()> {
+        if !record.info.is_introspectable() || record.glib_is_gtype_struct_for.is_some() {
+            return Ok(())
+        }
+        let path = cx.path(&record.name);
+        self.push_named(path.clone(), "record", record.c_type.as_deref(), path);
+        visit::walk_record(self, record, cx)
+    }
+
+    fn visit_enum<'a>(&mut self, e: &'a Enumeration, cx: Context<'a>) -> Result<()> {
+        if !e.info.is_introspectable() {
+            return Ok(())
+        }
+        let path = format!("{}.{}", cx.ns, e.name);
+        self.push_named(path.clone(), "enum", Some(&e.c_type), path);
+        visit::walk_enum(self, e, cx)
+    }
+
+    fn visit_bitfield<'a>(&mut self, b: &'a Bitfield, cx: Context<'a>) -> Result<()> {
+        if !b.info.is_introspectable() {
+            return Ok(())
+        }
+        let path = format!("{}.{}", cx.ns, b.name);
+        self.push_named(path.clone(), "bitfield", Some(&b.c_type), path);
+        visit::walk_bitfield(self, b, cx)
+    }
+
+    fn visit_function<'a>(&mut self, fun: &'a Function, cx: Context<'a>) -> Result<()> {
+        if !fun.info.is_introspectable() || matches!(fun.typ, FunctionType::Virtual | FunctionType::Callback) {
+            return Ok(())
+        }
+        let params = lua_params(&fun.parameters, fun.ret.as_ref());
+        if let Some(ref want) = self.query.returns {
+            let returns = fun.ret.iter()
+                .chain(params.iter().copied().filter(|p| out_param(&p.direction)))
+                .any(|p| has_type(&p.typ, want, cx.ns));
+            if !returns {
+                return Ok(())
+            }
+        }
+        if let Some(ref want) = self.query.takes {
+            let takes = params.iter()
+                .filter(|p| in_param(&p.direction))
+                .any(|p| has_type(&p.typ, want, cx.ns));
+            if !takes {
+                return Ok(())
+            }
+        }
+        let static_ns = cx.qualified();
+        let sig = signature(fun, &static_ns, cx.ns);
+        let sep = if fun.typ == FunctionType::Method { ":" } else { "." };
+        self.push(format!("{}{}{}", static_ns, sep, fun.name), fun.kind(),
+            fun.c_identifier.as_deref(), sig);
+        Ok(())
+    }
+
+    fn visit_signal<'a>(&mut self, signal: &'a Signal, cx: Context<'a>) -> Result<()> {
+        if !signal.info.is_introspectable() {
+            return Ok(())
+        }
+        let path = format!("{}::{}", cx.qualified(), signal.name);
+        let sig = format!("{} {}", path, signal.handler(&cx.qualified(), cx.ns));
+        self.push_named(path, "signal", None, sig);
+        Ok(())
+    }
+
+    fn visit_property<'a>(&mut self, prop: &'a Property, cx: Context<'a>) -> Result<()> {
+        if !prop.info.is_introspectable() {
+            return Ok(())
+        }
+        let path = cx.path(&prop.name.replace('-', "_"));
+        let sig = format!("{}: {}", path, show_anytyp(&prop.typ, cx.ns));
+        self.push_named(path, "property", None, sig);
+        Ok(())
+    }
+
+    fn visit_constant<'a>(&mut self, constant: &'a Constant, cx: Context<'a>) -> Result<()> {
+        if cx.parent.is_some() || !constant.info.is_introspectable() {
+            return Ok(())
+        }
+        let path = format!("{}.{}", cx.ns, constant.name);
+        let sig = format!("{} = {}", path, constant.value);
+        self.push_named(path, "constant", constant.c_identifier.as_deref(), sig);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Query;
+    use crate::parse::parse_gir;
+
+    #[test]
+    fn search_works() {
+        let f = std::fs::File::open("testdata/girs/GMime-3.0.gir").unwrap();
+        let repo = parse_gir(std::io::BufReader::new(f)).unwrap();
+
+        let query = Query { name: Some("g_mime_message_get_subject".to_string()), ..Query::default() };
+        let hits = query.search(&repo).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "GMime.Message:get_subject");
+        assert_eq!(hits[0].signature, "GMime.Message:get_subject(): string|nil");
+
+        let query = Query { returns: Some("GMime.ContentType".to_string()), ..Query::default() };
+        let hits = query.search(&repo).unwrap();
+        assert!(hits.iter().any(|h| h.path == "GMime.Object:get_content_type"));
+        assert!(hits.iter().all(|h| h.kind != "class"));
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use crate::library::*;
+use crate::parse;
+use crate::typelib;
+use crate::lang::open_gir;
+use anyhow::Result;
+
+/// Where typelibs are installed, they are searched after the gir files
+const TYPELIB_DIRS: &[&str] = &[
+    "/usr/lib/girepository-1.0/",
+    "/usr/lib64/girepository-1.0/",
+    "/usr/lib/x86_64-linux-gnu/girepository-1.0/",
+    "/usr/lib/aarch64-linux-gnu/girepository-1.0/",
+];
+
+/// Read a gir or a typelib file, depending on the extension. Like
+/// `open_gir`, a file that doesn't exist is looked up in the system
+/// directories.
+pub fn read_repository<P: AsRef<Path>>(filename: P) -> Result<Repository> {
+    let path = filename.as_ref();
+    match path.extension().and_then(OsStr::to_str) {
+        Some("gir") => Ok(parse::parse_gir(open_gir(path)?)?),
+        Some("typelib") => {
+            let data = match fs::read(path) {
+                Ok(data) => data,
+                Err(err) => TYPELIB_DIRS.iter()
+                    .find_map(|dir| fs::read(Path::new(dir).join(path)).ok())
+                    .ok_or(err)?,
+            };
+            typelib::parse_typelib(&data)
+        }
+        _ => Err(anyhow::anyhow!(format!("{} Filetype isn't gir or typelib", path.to_string_lossy()))),
+    }
+}
+
+/// Where `read_repository` reads `filename` from
+pub(crate) fn locate<P: AsRef<Path>>(filename: P) -> PathBuf {
+    let path = filename.as_ref();
+    if path.exists() {
+        return path.to_path_buf()
+    }
+    let dirs: &[&str] = match path.extension().and_then(OsStr::to_str) {
+        Some("gir") => &["/usr/share/gir-1.0/"],
+        Some("typelib") => TYPELIB_DIRS,
+        _ => &[],
+    };
+    dirs.iter()
+        .map(|dir| Path::new(dir).join(path))
+        .find(|p| p.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// A namespace name and version, like `("Gtk", "4.0")`. The version is
+/// empty if it isn't known.
+type Key = (String, String);
+
+fn key(name: &str, version: Option<&str>) -> Key {
+    (name.to_string(), version.unwrap_or_default().to_string())
+}
+
+/// Finds and caches the repositories of namespaces, so that types
+/// from included gir files (like GObject.Object) can be looked up.
+/// Versions of a namespace are kept apart, types are looked up in the
+/// `Scope` of a repository.
+pub struct Resolver {
+    search_path: Mutex<Vec<PathBuf>>,
+    // None if it couldn't be found
+    repos: Mutex<HashMap<Key, Option<Arc<Repository>>>>,
+    // namespaces being parsed, other threads wait for them instead
+    // of parsing them again
+    loading: Mutex<HashSet<Key>>,
+    loaded: Condvar,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        let mut search_path = vec![PathBuf::from("/usr/share/gir-1.0/")];
+        search_path.extend(TYPELIB_DIRS.iter().map(PathBuf::from));
+        Self::new(search_path)
+    }
+}
+
+impl Resolver {
+    pub fn new(search_path: Vec<PathBuf>) -> Resolver {
+        Resolver {
+            search_path: Mutex::new(search_path),
+            repos: Mutex::new(HashMap::new()),
+            loading: Mutex::new(HashSet::new()),
+            loaded: Condvar::new(),
+        }
+    }
+
+    /// Search `dir` before the other directories
+    pub fn add_search_path<P: AsRef<Path>>(&self, dir: P) {
+        let dir = dir.as_ref().to_path_buf();
+        let mut search_path = self.search_path.lock().unwrap();
+        if !search_path.contains(&dir) {
+            search_path.insert(0, dir);
+        }
+    }
+
+    /// Add an already parsed repository
+    pub fn add(&self, repo: Repository) -> Arc<Repository> {
+        let repo = Arc::new(repo);
+        let mut repos = self.repos.lock().unwrap();
+        for ns in repo.namespace.iter() {
+            if let Some(ref name) = ns.name {
+                repos.insert(key(name, ns.version.as_deref()), Some(repo.clone()));
+            }
+        }
+        repo
+    }
+
+    /// Add a repository that is already shared, unless its namespaces
+    /// have been loaded, and load its includes
+    pub fn insert(&self, repo: &Arc<Repository>) {
+        {
+            let mut repos = self.repos.lock().unwrap();
+            for ns in repo.namespace.iter() {
+                if let Some(ref name) = ns.name {
+                    let key = key(name, ns.version.as_deref());
+                    if !matches!(repos.get(&key), Some(Some(_))) {
+                        repos.insert(key, Some(repo.clone()));
+                    }
+                }
+            }
+        }
+        self.load_includes(repo);
+    }
+
+    /// Parse the gir or typelib file `filename`, add it and load its
+    /// includes. Includes are also searched for next to the file.
+    pub fn load_file(&self, filename: &str) -> Result<Arc<Repository>> {
+        let repo = self.add_file(filename, read_repository(filename)?);
+        self.load_includes(&repo);
+        Ok(repo)
+    }
+
+    /// Add `repo`, read from `filename`, without loading its includes.
+    /// Includes are also searched for next to the file.
+    pub fn add_file(&self, filename: &str, repo: Repository) -> Arc<Repository> {
+        if let Some(dir) = Path::new(filename).parent() {
+            self.add_search_path(dir);
+        }
+        self.add(repo)
+    }
+
+    /// Get the repository defining the namespace `name` in `version`, or
+    /// the newest version if it isn't given, searching for
+    /// `<name>-<version>.gir` or `.typelib` if it hasn't been loaded yet.
+    /// Safe to call from several threads, a namespace is only parsed once.
+    pub fn load(&self, name: &str, version: Option<&str>) -> Option<Arc<Repository>> {
+        let key = key(name, version);
+        let mut loading = self.loading.lock().unwrap();
+        loop {
+            if let Some(repo) = self.repos.lock().unwrap().get(&key) {
+                return repo.clone();
+            }
+            if loading.insert(key.clone()) {
+                break;
+            }
+            loading = self.loaded.wait(loading).unwrap();
+        }
+        drop(loading);
+        let repo = self.find(name, version)
+            .and_then(|path| read_repository(path).ok())
+            .map(Arc::new);
+        self.repos.lock().unwrap().insert(key.clone(), repo.clone());
+        self.loading.lock().unwrap().remove(&key);
+        self.loaded.notify_all();
+        repo
+    }
+
+    /// Load all the includes of `repo`, and their includes.
+    pub fn load_includes(&self, repo: &Repository) {
+        for include in repo.include.iter() {
+            let key = key(&include.name, include.version.as_deref());
+            let loaded = self.repos.lock().unwrap().contains_key(&key);
+            if loaded {
+                continue;
+            }
+            if let Some(inc) = self.load(&include.name, include.version.as_deref()) {
+                self.load_includes(&inc);
+            }
+        }
+    }
+
+    /// The files of the includes of `repo` and their includes, as far
+    /// as they can be found
+    pub fn include_files(&self, repo: &Repository) -> Vec<PathBuf> {
+        let mut files = vec![];
+        let mut seen: Vec<Key> = vec![];
+        let mut todo: Vec<Key> = repo.include.iter()
+            .map(|i| key(&i.name, i.version.as_deref()))
+            .collect();
+        while let Some((name, version)) = todo.pop() {
+            let version = Some(version.as_str()).filter(|v| !v.is_empty());
+            if seen.contains(&key(&name, version)) {
+                continue;
+            }
+            if let Some(path) = self.find(&name, version) {
+                files.push(path);
+            }
+            if let Some(inc) = self.load(&name, version) {
+                todo.extend(inc.include.iter().map(|i| key(&i.name, i.version.as_deref())));
+            }
+            seen.push(key(&name, version));
+        }
+        files
+    }
+
+    /// The namespaces `repo` can use: its own and those it includes,
+    /// directly or through other includes, in the included versions.
+    /// Includes are loaded if they haven't been.
+    pub fn scope(&self, repo: &Arc<Repository>) -> Scope {
+        let mut scope = Scope::default();
+        for ns in repo.namespace.iter() {
+            if let Some(ref name) = ns.name {
+                scope.repos.push((name.clone(), repo.clone()));
+            }
+        }
+        // breadth first, the includes closest to `repo` win
+        let mut todo: VecDeque<Key> = repo.include.iter()
+            .map(|i| key(&i.name, i.version.as_deref()))
+            .collect();
+        while let Some((name, version)) = todo.pop_front() {
+            if scope.repos.iter().any(|(n, _)| *n == name) {
+                continue;
+            }
+            let version = Some(version.as_str()).filter(|v| !v.is_empty());
+            if let Some(inc) = self.load(&name, version) {
+                todo.extend(inc.include.iter().map(|i| key(&i.name, i.version.as_deref())));
+                scope.repos.push((name, inc));
+            }
+        }
+        scope
+    }
+
+    fn find(&self, name: &str, version: Option<&str>) -> Option<PathBuf> {
+        let search_path = self.search_path.lock().unwrap();
+        for dir in search_path.iter() {
+            if let Some(version) = version {
+                let found = ["gir", "typelib"].iter()
+                    .map(|ext| dir.join(format!("{}-{}.{}", name, version, ext)))
+                    .find(|path| path.is_file());
+                if found.is_some() {
+                    return found;
+                }
+                continue;
+            }
+            let prefix = format!("{}-", name);
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut candidates: Vec<PathBuf> = entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    let file = p.file_name().and_then(|f| f.to_str()).unwrap_or("");
+                    file.starts_with(&prefix)
+                        && (file.ends_with(".gir") || file.ends_with(".typelib"))
+                })
+                .collect();
+            // the newest version, the gir file if there is a typelib too
+            candidates.sort_by_key(|p| (p.file_stem().map(OsStr::to_os_string),
+                p.extension() == Some(OsStr::new("gir"))));
+            if let Some(path) = candidates.pop() {
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    /// Every gir and typelib file on the search path, one per namespace
+    /// and version: the gir file if there is a typelib too, the first
+    /// found if more directories have it.
+    pub fn available(&self) -> Vec<PathBuf> {
+        let search_path = self.search_path.lock().unwrap();
+        let mut found: Vec<PathBuf> = vec![];
+        for dir in search_path.iter() {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut paths: Vec<PathBuf> = entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension() == Some(OsStr::new("gir"))
+                    || p.extension() == Some(OsStr::new("typelib")))
+                .collect();
+            // gir before typelib
+            paths.sort_by_key(|p| (p.file_stem().map(OsStr::to_os_string),
+                p.extension() != Some(OsStr::new("gir"))));
+            for path in paths {
+                if !found.iter().any(|f| f.file_stem() == path.file_stem()) {
+                    found.push(path);
+                }
+            }
+        }
+        found
+    }
+}
+
+/// The namespaces visible from one repository, see `Resolver::scope`.
+/// Types are looked up here, so each version of a namespace only sees
+/// the versions of the namespaces it was built against.
+#[derive(Clone, Default)]
+pub struct Scope {
+    repos: Vec<(String, Arc<Repository>)>,
+}
+
+impl Scope {
+    /// The repository of the namespace `name`, if it's in the scope
+    pub fn namespace(&self, name: &str) -> Option<Arc<Repository>> {
+        self.repos.iter().find(|(n, _)| n == name).map(|(_, repo)| repo.clone())
+    }
+
+    /// The parent classes of the class `typ`, closest first, as qualified names.
+    /// Stops at the first class that can't be found.
+    pub fn ancestors(&self, typ: &str, ns: &str) -> Vec<String> {
+        let mut ancestors: Vec<String> = vec![];
+        let (mut ns, mut name) = {
+            let (ns, name) = split_name(typ, ns);
+            (ns.to_string(), name.to_string())
+        };
+        loop {
+            let parent = self.namespace(&ns)
+                .and_then(|repo| {
+                    repo.get_namespace(&ns)
+                        .and_then(|n| n.get_class(&name))
+                        .and_then(|c| c.parent.clone())
+                });
+            let parent = match parent {
+                Some(parent) => parent,
+                None => break,
+            };
+            let (pns, pname) = {
+                let (pns, pname) = split_name(&parent, &ns);
+                (pns.to_string(), pname.to_string())
+            };
+            let qualified = format!("{}.{}", pns, pname);
+            if ancestors.contains(&qualified) {
+                break;
+            }
+            ancestors.push(qualified);
+            ns = pns;
+            name = pname;
+        }
+        ancestors
+    }
+
+    /// Whether `typ` is a class, interface or record in a loaded namespace
+    pub fn is_compound(&self, typ: &str, ns: &str) -> bool {
+        let (ns, name) = split_name(typ, ns);
+        self.namespace(ns)
+            .map(|repo| {
+                repo.get_namespace(ns).is_some_and(|n| {
+                    n.get_class(name).is_some()
+                        || n.get_interface(name).is_some()
+                        || n.get_record(name).is_some()
+                })
+            })
+            .unwrap_or(false)
+    }
+
+    /// Whether `typ` is defined in a loaded namespace, as any kind of type
+    pub fn is_type(&self, typ: &str, ns: &str) -> bool {
+        let (ns, name) = split_name(typ, ns);
+        self.namespace(ns)
+            .is_some_and(|repo| repo.get_namespace(ns).is_some_and(|n| n.has_type(name)))
+    }
+
+    /// Whether `typ` is a callback in a loaded namespace
+    pub fn is_callback(&self, typ: &str, ns: &str) -> bool {
+        let (ns, name) = split_name(typ, ns);
+        self.namespace(ns)
+            .is_some_and(|repo| repo.get_namespace(ns).is_some_and(|n| n.get_callback(name).is_some()))
+    }
+}
+
+/// Split a type name into namespace and name, `ns` is used
+/// for names that aren't qualified.
+pub fn split_name<'a>(name: &'a str, ns: &'a str) -> (&'a str, &'a str) {
+    match name.split_once('.') {
+        Some((ns, name)) => (ns, name),
+        None => (ns, name),
+    }
+}
+
+impl Repository {
+    /// The namespace called `name` in this repository
+    pub fn get_namespace(&self, name: &str) -> Option<&Namespace> {
+        self.namespace.iter().find(|ns| ns.name.as_deref() == Some(name))
+    }
+}
+
+impl Namespace {
+    pub fn get_class(&self, name: &str) -> Option<&Class> {
+        self.classes.iter().find(|c| c.name == name)
+    }
+    pub fn get_interface(&self, name: &str) -> Option<&Interface> {
+        self.interfaces.iter().find(|i| i.name == name)
+    }
+    pub fn get_record(&self, name: &str) -> Option<&Record> {
+        self.record.iter().find(|r| r.name == name)
+    }
+    pub fn get_callback(&self, name: &str) -> Option<&Function> {
+        self.callback.iter().find(|c| c.name == name)
+    }
+    /// Whether a class, interface, record, union, enum, bitfield,
+    /// callback, alias or boxed type is called `name`
+    pub fn has_type(&self, name: &str) -> bool {
+        self.get_class(name).is_some()
+            || self.get_interface(name).is_some()
+            || self.get_record(name).is_some()
+            || self.get_callback(name).is_some()
+            || self.unions.iter().any(|u| u.name.as_deref() == Some(name))
+            || self.enums.iter().any(|e| e.name == name)
+            || self.bitfield.iter().any(|b| b.name == name)
+            || self.alias.iter().any(|a| a.name == name)
+            || self.boxed.iter().any(|b| b.glib_name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Resolver;
+
+    #[test]
+    fn scope_works() {
+        // both versions at once, each only sees itself
+        let resolver = Resolver::new(vec![]);
+        let tk3 = resolver.load_file("testdata/girs/Toolkit-3.0.gir").unwrap();
+        let tk4 = resolver.load_file("testdata/girs/Toolkit-4.0.gir").unwrap();
+        for (repo, version, prop) in [(&tk3, "3.0", "has-tooltip"), (&tk4, "4.0", "css-name")] {
+            let scope = resolver.scope(repo);
+            let ns = scope.namespace("Toolkit").unwrap();
+            let ns = ns.get_namespace("Toolkit").unwrap();
+            assert_eq!(ns.version.as_deref(), Some(version));
+            let widget = ns.get_class("Widget").unwrap();
+            assert!(widget.properties.iter().any(|p| p.name == prop));
+            assert_eq!(scope.ancestors("Button", "Toolkit")[0], "Toolkit.Widget");
+        }
+        assert!(resolver.load("Toolkit", Some("3.0")).is_some_and(|r| std::sync::Arc::ptr_eq(&r, &tk3)));
+    }
+}
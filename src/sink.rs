@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use anyhow::Result;
+
+use crate::manifest;
+
+/// Where generators write their files to. Paths are relative to the root
+/// of the output, like `Gtk/Widget.md`.
+pub trait Sink: Send + Sync {
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()>;
+}
+
+/// Writes files below a directory, creating directories as needed. Files
+/// that already have the contents aren't touched, so file watchers only
+/// see the files that changed.
+pub struct DirSink {
+    root: PathBuf,
+}
+
+impl DirSink {
+    pub fn new<P: AsRef<Path>>(root: P) -> DirSink {
+        DirSink {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl Sink for DirSink {
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let path = self.root.join(path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        if fs::read(&path).is_ok_and(|old| old == contents) {
+            return Ok(())
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// Passes the files on to another sink and remembers their paths and
+/// a hash of what was written
+pub struct RecordingSink<'s> {
+    inner: &'s dyn Sink,
+    files: Mutex<BTreeMap<PathBuf, String>>,
+}
+
+impl<'s> RecordingSink<'s> {
+    pub fn new(inner: &'s dyn Sink) -> RecordingSink<'s> {
+        RecordingSink {
+            inner,
+            files: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// The paths of every file written, with a hash of the contents
+    pub fn into_files(self) -> BTreeMap<PathBuf, String> {
+        self.files.into_inner().unwrap()
+    }
+}
+
+impl Sink for RecordingSink<'_> {
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.files.lock().unwrap().insert(path.to_path_buf(), manifest::hash(contents));
+        self.inner.write(path, contents)
+    }
+}
+
+/// Keeps the files in memory, for build scripts, editors and tests
+#[derive(Default)]
+pub struct MemorySink {
+    files: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+}
+
+impl MemorySink {
+    pub fn new() -> MemorySink {
+        MemorySink::default()
+    }
+
+    /// The contents of the file at `path`, if it has been written
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().get(path.as_ref()).cloned()
+    }
+
+    /// The paths of every file written, sorted
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files.lock().unwrap().keys().cloned().collect()
+    }
+
+    pub fn into_files(self) -> BTreeMap<PathBuf, Vec<u8>> {
+        self.files.into_inner().unwrap()
+    }
+}
+
+impl Sink for MemorySink {
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.files.lock().unwrap().insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+}
+
+/// Writes the files into a tar archive. The archive is only complete
+/// after `finish`.
+pub struct TarSink<W: Write + Send> {
+    builder: Mutex<tar::Builder<W>>,
+}
+
+impl<W: Write + Send> TarSink<W> {
+    pub fn new(w: W) -> TarSink<W> {
+        TarSink {
+            builder: Mutex::new(tar::Builder::new(w)),
+        }
+    }
+
+    /// Write the end of the archive and return the writer
+    pub fn finish(self) -> Result<W> {
+        Ok(self.builder.into_inner().unwrap().into_inner()?)
+    }
+}
+
+impl<W: Write + Send> Sink for TarSink<W> {
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        // no mtime, so the same input gives the same archive
+        header.set_mtime(0);
+        let mut builder = self.builder.lock().unwrap();
+        builder.append_data(&mut header, path, contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+    use super::{MemorySink, Sink, TarSink};
+    use crate::generate::{Format, Options};
+    use crate::resolve::Resolver;
+
+    #[test]
+    fn memory_works() {
+        let resolver = Arc::new(Resolver::new(vec![]));
+        let repo = resolver.load_file("testdata/girs/GMime-3.0.gir").unwrap();
+        let options = Options { format: Format::Vimdoc, ..Options::default() };
+        let gen = options.generator(resolver).unwrap();
+        let sink = MemorySink::new();
+        gen.generate(&repo, &sink).unwrap();
+        gen.finish(&sink).unwrap();
+        assert_eq!(sink.paths(), vec![Path::new("GMime-3.0.txt")]);
+    }
+
+    #[test]
+    fn tar_works() {
+        let sink = TarSink::new(vec![]);
+        sink.write(Path::new("Gtk/Widget.md"), b"# Gtk.Widget\n").unwrap();
+        let tar = sink.finish().unwrap();
+        let mut archive = tar::Archive::new(tar.as_slice());
+        let entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.path().unwrap(), Path::new("Gtk/Widget.md"));
+        assert_eq!(entry.size(), 13);
+    }
+}
@@ -0,0 +1,1004 @@
+//! A reader for the binary typelib format (version 4) that g-ir-compiler
+//! produces from a gir file. Typelibs don't carry documentation, c types or
+//! parameter names of instance parameters, so the repository read from one
+//! is missing those, everything else maps onto the same `library` types as
+//! a parsed gir file.
+use std::collections::HashMap;
+use anyhow::{anyhow, bail, Result};
+
+use crate::library::*;
+
+const MAGIC: &[u8] = b"GOBJ\nMETADATA\r\n\x1a";
+
+const BLOB_FUNCTION: u16 = 1;
+const BLOB_CALLBACK: u16 = 2;
+const BLOB_STRUCT: u16 = 3;
+const BLOB_BOXED: u16 = 4;
+const BLOB_ENUM: u16 = 5;
+const BLOB_FLAGS: u16 = 6;
+const BLOB_OBJECT: u16 = 7;
+const BLOB_INTERFACE: u16 = 8;
+const BLOB_CONSTANT: u16 = 9;
+const BLOB_UNION: u16 = 11;
+
+const TAG_VOID: u32 = 0;
+const TAG_ARRAY: u32 = 15;
+const TAG_INTERFACE: u32 = 16;
+const TAG_GLIST: u32 = 17;
+const TAG_GSLIST: u32 = 18;
+const TAG_GHASH: u32 = 19;
+const TAG_ERROR: u32 = 20;
+
+/// Marks a missing method index, like a property without a setter
+const NO_INDEX: usize = 0x3ff;
+
+/// The size of every kind of blob, as stored in the header
+struct Sizes {
+    entry: usize,
+    function: usize,
+    callback: usize,
+    signal: usize,
+    vfunc: usize,
+    arg: usize,
+    property: usize,
+    field: usize,
+    value: usize,
+    constant: usize,
+    signature: usize,
+    enumeration: usize,
+    structure: usize,
+    object: usize,
+    interface: usize,
+    union: usize,
+}
+
+struct Typelib<'a> {
+    data: &'a [u8],
+    sizes: Sizes,
+    directory: usize,
+    n_entries: usize,
+    prefix: String,
+    // attributes by the offset of the blob they belong to
+    attributes: HashMap<usize, Vec<(String, String)>>,
+}
+
+/// Read the typelib in `data`
+pub fn parse_typelib(data: &[u8]) -> Result<Repository> {
+    Typelib::new(data)?.repository()
+}
+
+fn no_info(deprecated: bool) -> InfoAttrs {
+    InfoAttrs {
+        introspectable: None,
+        deprecated: deprecated.then_some(true),
+        deprecated_version: None,
+        version: None,
+        stability: None,
+    }
+}
+
+fn no_doc() -> InfoElements {
+    InfoElements {
+        doc: None,
+        doc_stability: None,
+        doc_version: None,
+        doc_deprecated: None,
+        doc_pos: None,
+    }
+}
+
+fn bit(flags: u32, n: u32) -> bool {
+    flags & (1 << n) != 0
+}
+
+fn named_type(name: &str, children: Vec<AnyType>) -> AnyType {
+    AnyType::Type(Type {
+        name: Some(name.to_string()),
+        ctype: None,
+        introspectable: None,
+        doc: no_doc(),
+        children,
+    })
+}
+
+/// The gir name of a basic type tag
+fn basic_type(tag: u32, pointer: bool) -> Option<&'static str> {
+    let name = match tag {
+        TAG_VOID if pointer => "gpointer",
+        TAG_VOID => "none",
+        1 => "gboolean",
+        2 => "gint8",
+        3 => "guint8",
+        4 => "gint16",
+        5 => "guint16",
+        6 => "gint32",
+        7 => "guint32",
+        8 => "gint64",
+        9 => "guint64",
+        10 => "gfloat",
+        11 => "gdouble",
+        12 => "GType",
+        13 => "utf8",
+        14 => "filename",
+        21 => "gunichar",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn transfer(full: bool, container: bool) -> Transfer {
+    if full {
+        Transfer::Full
+    } else if container {
+        Transfer::Container
+    } else {
+        Transfer::None
+    }
+}
+
+impl<'a> Typelib<'a> {
+    fn new(data: &'a [u8]) -> Result<Typelib<'a>> {
+        if !data.starts_with(MAGIC) {
+            bail!("Not a typelib file");
+        }
+        let mut typelib = Typelib {
+            data,
+            sizes: Sizes {
+                entry: 0, function: 0, callback: 0, signal: 0, vfunc: 0,
+                arg: 0, property: 0, field: 0, value: 0, constant: 0,
+                signature: 0, enumeration: 0, structure: 0, object: 0,
+                interface: 0, union: 0,
+            },
+            directory: 0,
+            n_entries: 0,
+            prefix: String::new(),
+            attributes: HashMap::new(),
+        };
+        let major = typelib.u8(16)?;
+        if major != 4 {
+            bail!("Unsupported typelib version {}", major);
+        }
+        let size = |n: usize| typelib.u16(n).map(usize::from);
+        typelib.sizes = Sizes {
+            entry: size(60)?,
+            function: size(62)?,
+            callback: size(64)?,
+            signal: size(66)?,
+            vfunc: size(68)?,
+            arg: size(70)?,
+            property: size(72)?,
+            field: size(74)?,
+            value: size(76)?,
+            constant: size(80)?,
+            signature: size(84)?,
+            enumeration: size(86)?,
+            structure: size(88)?,
+            object: size(90)?,
+            interface: size(92)?,
+            union: size(94)?,
+        };
+        typelib.n_entries = typelib.u16(20)? as usize;
+        typelib.directory = typelib.u32(24)? as usize;
+        typelib.prefix = typelib.opt_string(56)?.unwrap_or_default();
+
+        let n_attributes = typelib.u32(28)? as usize;
+        let attributes = typelib.u32(32)? as usize;
+        let attribute_size = typelib.u16(78)? as usize;
+        for i in 0..n_attributes {
+            let off = attributes + i * attribute_size;
+            let blob = typelib.u32(off)? as usize;
+            let name = typelib.string(typelib.u32(off + 4)? as usize)?;
+            let value = typelib.string(typelib.u32(off + 8)? as usize)?;
+            typelib.attributes.entry(blob).or_default().push((name, value));
+        }
+        Ok(typelib)
+    }
+
+    fn bytes(&self, off: usize, len: usize) -> Result<&'a [u8]> {
+        self.data.get(off..off + len)
+            .ok_or_else(|| anyhow!("Typelib is truncated at offset {}", off))
+    }
+
+    fn u8(&self, off: usize) -> Result<u8> {
+        Ok(self.bytes(off, 1)?[0])
+    }
+
+    fn u16(&self, off: usize) -> Result<u16> {
+        let b = self.bytes(off, 2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&self, off: usize) -> Result<u32> {
+        let b = self.bytes(off, 4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// The nul terminated string at `off`
+    fn string(&self, off: usize) -> Result<String> {
+        let rest = self.data.get(off..)
+            .ok_or_else(|| anyhow!("String offset {} is out of bounds", off))?;
+        let len = rest.iter().position(|&b| b == 0)
+            .ok_or_else(|| anyhow!("String at offset {} isn't terminated", off))?;
+        Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+
+    /// The string that the offset stored at `off` points to, 0 means no string
+    fn opt_string(&self, off: usize) -> Result<Option<String>> {
+        match self.u32(off)? as usize {
+            0 => Ok(None),
+            s => self.string(s).map(Some),
+        }
+    }
+
+    fn attribute(&self, blob: usize, name: &str) -> Option<String> {
+        self.attributes.get(&blob)?
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+    }
+
+    /// The offset of the directory entry `index`, indexes start at 1
+    fn entry(&self, index: usize) -> Result<usize> {
+        if index == 0 || index > self.n_entries {
+            bail!("Directory index {} is out of bounds", index);
+        }
+        Ok(self.directory + (index - 1) * self.sizes.entry)
+    }
+
+    /// The type name of the directory entry `index`, qualified if it's
+    /// from another namespace
+    fn entry_name(&self, index: usize) -> Result<String> {
+        let off = self.entry(index)?;
+        let name = self.string(self.u32(off + 4)? as usize)?;
+        if bit(self.u16(off + 2)? as u32, 0) {
+            Ok(name)
+        } else {
+            let ns = self.string(self.u32(off + 8)? as usize)?;
+            Ok(format!("{}.{}", ns, name))
+        }
+    }
+
+    fn repository(&self) -> Result<Repository> {
+        let include = match self.opt_string(36)? {
+            Some(deps) => deps.split('|')
+                .filter(|d| !d.is_empty())
+                .map(|d| match d.rsplit_once('-') {
+                    Some((name, version)) => Include {
+                        name: name.to_string(),
+                        version: Some(version.to_string()),
+                    },
+                    None => Include { name: d.to_string(), version: None },
+                })
+                .collect(),
+            None => vec![],
+        };
+        Ok(Repository {
+            version: Some(Version(1, 2, 0)),
+            xmlns: None,
+            identifier_prefixes: None,
+            symbol_prefixes: None,
+            include,
+            cinclude: vec![],
+            package: vec![],
+            namespace: vec![self.namespace()?],
+        })
+    }
+
+    fn namespace(&self) -> Result<Namespace> {
+        let mut ns = Namespace {
+            name: self.opt_string(44)?,
+            version: self.opt_string(48)?,
+            shared_library: self.opt_string(52)?,
+            identifier_prefixes: Some(self.prefix.clone()).filter(|p| !p.is_empty()),
+            symbol_prefixes: None,
+            prefix: None,
+            classes: vec![],
+            functions: vec![],
+            macros: vec![],
+            callback: vec![],
+            interfaces: vec![],
+            enums: vec![],
+            record: vec![],
+            constant: vec![],
+            bitfield: vec![],
+            alias: vec![],
+            unions: vec![],
+            boxed: vec![],
+            docsections: vec![],
+        };
+        // class structs, by the type they are the class struct of
+        let mut type_structs: HashMap<String, String> = HashMap::new();
+
+        let n_local = self.u16(22)? as usize;
+        for index in 1..=n_local {
+            let entry = self.entry(index)?;
+            let off = self.u32(entry + 8)? as usize;
+            match self.u16(entry)? {
+                BLOB_FUNCTION => ns.functions.push(self.function(off, None)?),
+                BLOB_CALLBACK => ns.callback.push(self.callback(off)?),
+                BLOB_STRUCT => ns.record.push(self.record(off)?),
+                BLOB_BOXED => ns.boxed.push(self.boxed(off)?),
+                BLOB_ENUM => ns.enums.push(self.enumeration(off)?),
+                BLOB_FLAGS => ns.bitfield.push(self.bitfield(off)?),
+                BLOB_OBJECT => {
+                    let class = self.class(off)?;
+                    if let Some(ref s) = class.glib_type_struct {
+                        type_structs.insert(s.clone(), class.name.clone());
+                    }
+                    ns.classes.push(class);
+                }
+                BLOB_INTERFACE => {
+                    let iface = self.interface(off)?;
+                    if let Some(ref s) = iface.glib_type_struct {
+                        type_structs.insert(s.clone(), iface.name.clone());
+                    }
+                    ns.interfaces.push(iface);
+                }
+                BLOB_CONSTANT => ns.constant.push(self.constant(off)?),
+                BLOB_UNION => ns.unions.push(self.union(off)?),
+                _ => {}
+            }
+        }
+        for record in ns.record.iter_mut() {
+            if let Some(owner) = type_structs.remove(&record.name) {
+                record.glib_is_gtype_struct_for = Some(owner);
+            }
+        }
+        Ok(ns)
+    }
+
+    fn anytype(&self, off: usize) -> Result<AnyType> {
+        let simple = self.u32(off)?;
+        if simple & 0xff_ffff == 0 {
+            let tag = simple >> 27;
+            let pointer = bit(simple, 24);
+            let name = basic_type(tag, pointer)
+                .ok_or_else(|| anyhow!("Unknown type tag {} at offset {}", tag, off))?;
+            return Ok(named_type(name, vec![]));
+        }
+        let off = simple as usize;
+        let flags = self.u16(off)? as u32;
+        let tag = (flags >> 3) & 0x1f;
+        match tag {
+            TAG_ARRAY => {
+                let dimension = self.u16(off + 2)? as usize;
+                let name = match (flags >> 11) & 0x3 {
+                    1 => Some("GLib.Array"),
+                    2 => Some("GLib.PtrArray"),
+                    3 => Some("GLib.ByteArray"),
+                    _ => None,
+                };
+                Ok(AnyType::Array(Array {
+                    name: name.map(str::to_string),
+                    zero_terminated: Some(bit(flags, 8)),
+                    fixed_size: bit(flags, 10).then_some(dimension),
+                    introspectable: None,
+                    length: bit(flags, 9).then_some(dimension),
+                    ctype: None,
+                    typ: Box::new(self.anytype(off + 4)?),
+                }))
+            }
+            TAG_INTERFACE => {
+                let name = self.entry_name(self.u16(off + 2)? as usize)?;
+                Ok(named_type(&name, vec![]))
+            }
+            TAG_GLIST | TAG_GSLIST | TAG_GHASH => {
+                let name = match tag {
+                    TAG_GLIST => "GLib.List",
+                    TAG_GSLIST => "GLib.SList",
+                    _ => "GLib.HashTable",
+                };
+                let n_types = self.u16(off + 2)? as usize;
+                let children = (0..n_types)
+                    .map(|i| self.anytype(off + 4 + i * 4))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(named_type(name, children))
+            }
+            TAG_ERROR => Ok(named_type("GLib.Error", vec![])),
+            tag => {
+                let name = basic_type(tag, bit(flags, 0))
+                    .ok_or_else(|| anyhow!("Unknown type tag {} at offset {}", tag, off))?;
+                Ok(named_type(name, vec![]))
+            }
+        }
+    }
+
+    /// The parameters, the return value and whether it throws,
+    /// of the signature at `off`
+    fn signature(&self, off: usize) -> Result<(Vec<Parameter>, Parameter, bool)> {
+        let flags = self.u16(off + 4)? as u32;
+        let ret = Parameter {
+            name: String::new(),
+            instance: false,
+            nullable: bit(flags, 0),
+            allow_none: false,
+            introspectable: None,
+            closure: None,
+            destroy: None,
+            scope: None,
+            direction: None,
+            caller_allocates: false,
+            optional: false,
+            skip: bit(flags, 3),
+            transfer: Some(transfer(bit(flags, 1), bit(flags, 2))),
+            doc: no_doc(),
+            typ: self.anytype(off)?,
+        };
+        let n_args = self.u16(off + 6)? as usize;
+        let params = (0..n_args)
+            .map(|i| self.arg(off + self.sizes.signature + i * self.sizes.arg))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((params, ret, bit(flags, 5)))
+    }
+
+    fn arg(&self, off: usize) -> Result<Parameter> {
+        let flags = self.u32(off + 4)?;
+        let direction = match (bit(flags, 0), bit(flags, 1)) {
+            (true, true) => Some(ParameterDirection::InOut),
+            (false, true) => Some(ParameterDirection::Out),
+            _ => None,
+        };
+        let scope = match (flags >> 8) & 0x7 {
+            1 => Some("call"),
+            2 => Some("async"),
+            3 => Some("notified"),
+            4 => Some("forever"),
+            _ => None,
+        };
+        let index = |n: u8| (n as i8 >= 0).then(|| n.to_string());
+        let nullable = bit(flags, 3);
+        Ok(Parameter {
+            name: self.string(self.u32(off)? as usize)?,
+            instance: false,
+            nullable,
+            allow_none: nullable && direction.is_none(),
+            introspectable: None,
+            closure: index(self.u8(off + 8)?),
+            destroy: index(self.u8(off + 9)?),
+            scope: scope.map(str::to_string),
+            direction,
+            caller_allocates: bit(flags, 2),
+            optional: bit(flags, 4),
+            skip: bit(flags, 11),
+            transfer: Some(transfer(bit(flags, 5), bit(flags, 6))),
+            doc: no_doc(),
+            typ: self.anytype(off + 12)?,
+        })
+    }
+
+    /// The instance parameter of a method of `container`, typelibs don't
+    /// store its name so it's named after the type
+    fn instance(&self, container: &str, full: bool) -> Parameter {
+        Parameter {
+            name: container.to_lowercase(),
+            instance: true,
+            nullable: false,
+            allow_none: false,
+            introspectable: None,
+            closure: None,
+            destroy: None,
+            scope: None,
+            direction: None,
+            caller_allocates: false,
+            optional: false,
+            skip: false,
+            transfer: Some(transfer(full, false)),
+            doc: no_doc(),
+            typ: named_type(container, vec![]),
+        }
+    }
+
+    /// The function at `off`, `container` is the type it's declared in
+    fn function(&self, off: usize, container: Option<&str>) -> Result<Function> {
+        let flags = self.u16(off + 2)? as u32;
+        let is_static = bit(self.u16(off + 16)? as u32, 0);
+        let sig = self.u32(off + 12)? as usize;
+        let (mut parameters, ret, throws) = self.signature(sig)?;
+        let typ = match container {
+            Some(_) if bit(flags, 3) => FunctionType::Constructor,
+            Some(container) if !is_static => {
+                let full = bit(self.u16(sig + 4)? as u32, 4);
+                parameters.insert(0, self.instance(container, full));
+                FunctionType::Method
+            }
+            _ => FunctionType::Function,
+        };
+        Ok(Function {
+            info: no_info(bit(flags, 0)),
+            doc: no_doc(),
+            typ,
+            name: self.string(self.u32(off + 4)? as usize)?,
+            introspectable: None,
+            c_identifier: self.opt_string(off + 8)?,
+            shadowed_by: None,
+            shadows: None,
+            throws: (throws || bit(flags, 5)).then_some(true),
+            moved_to: None,
+            c_type: None,
+            invoker: None,
+            parameters,
+            ret: Some(ret),
+        })
+    }
+
+    fn callback(&self, off: usize) -> Result<Function> {
+        let flags = self.u16(off + 2)? as u32;
+        let (parameters, ret, throws) = self.signature(self.u32(off + 8)? as usize)?;
+        Ok(Function {
+            info: no_info(bit(flags, 0)),
+            doc: no_doc(),
+            typ: FunctionType::Callback,
+            name: self.string(self.u32(off + 4)? as usize)?,
+            introspectable: None,
+            c_identifier: None,
+            shadowed_by: None,
+            shadows: None,
+            throws: throws.then_some(true),
+            moved_to: None,
+            c_type: None,
+            invoker: None,
+            parameters,
+            ret: Some(ret),
+        })
+    }
+
+    fn vfunc(&self, off: usize, container: &str, methods: &[String]) -> Result<Function> {
+        let flags = self.u16(off + 4)? as u32;
+        let invoker = (self.u16(off + 10)? & 0x3ff) as usize;
+        let (mut parameters, ret, throws) = self.signature(self.u32(off + 16)? as usize)?;
+        parameters.insert(0, self.instance(container, false));
+        Ok(Function {
+            info: no_info(false),
+            doc: no_doc(),
+            typ: FunctionType::Virtual,
+            name: self.string(self.u32(off)? as usize)?,
+            introspectable: None,
+            c_identifier: None,
+            shadowed_by: None,
+            shadows: None,
+            throws: (throws || bit(flags, 4)).then_some(true),
+            moved_to: None,
+            c_type: None,
+            invoker: methods.get(invoker).filter(|_| invoker != NO_INDEX).cloned(),
+            parameters,
+            ret: Some(ret),
+        })
+    }
+
+    /// The fields starting at `off`, and the offset after them. Fields
+    /// holding a function pointer are followed by their callback.
+    fn fields(&self, mut off: usize, n: usize) -> Result<(Vec<Field>, usize)> {
+        let mut fields = vec![];
+        for _ in 0..n {
+            let flags = self.u8(off + 4)? as u32;
+            let bits = self.u8(off + 5)? as u32;
+            let typ = if bit(flags, 2) {
+                AnyType::Callback(Box::new(self.callback(off + self.sizes.field)?))
+            } else {
+                self.anytype(off + 12)?
+            };
+            fields.push(Field {
+                name: self.string(self.u32(off)? as usize)?,
+                info: no_info(false),
+                doc: no_doc(),
+                typ,
+                writeable: bit(flags, 1),
+                readable: bit(flags, 0),
+                private: false,
+                bits: (bits > 0).then_some(bits),
+            });
+            off += self.sizes.field;
+            if bit(flags, 2) {
+                off += self.sizes.callback;
+            }
+        }
+        Ok((fields, off))
+    }
+
+    fn functions(&self, off: usize, n: usize, container: &str) -> Result<Vec<Function>> {
+        (0..n)
+            .map(|i| self.function(off + i * self.sizes.function, Some(container)))
+            .collect()
+    }
+
+    fn property(&self, off: usize, methods: &[String]) -> Result<Property> {
+        let flags = self.u32(off + 4)?;
+        let method = |i: u32| {
+            let i = (i & 0x3ff) as usize;
+            methods.get(i).filter(|_| i != NO_INDEX).cloned()
+        };
+        Ok(Property {
+            name: self.string(self.u32(off)? as usize)?,
+            info: no_info(bit(flags, 0)),
+            doc: no_doc(),
+            writable: bit(flags, 2),
+            readable: bit(flags, 1),
+            construct: bit(flags, 3),
+            construct_only: bit(flags, 4),
+            setter: method(flags >> 7),
+            getter: method(flags >> 17),
+            transfer: Some(transfer(bit(flags, 5), bit(flags, 6))),
+            typ: self.anytype(off + 12)?,
+        })
+    }
+
+    fn signal(&self, off: usize) -> Result<Signal> {
+        let flags = self.u16(off)? as u32;
+        let when = if bit(flags, 1) {
+            Some("first")
+        } else if bit(flags, 2) {
+            Some("last")
+        } else if bit(flags, 3) {
+            Some("cleanup")
+        } else {
+            None
+        };
+        let (parameters, ret, _) = self.signature(self.u32(off + 12)? as usize)?;
+        Ok(Signal {
+            name: self.string(self.u32(off + 4)? as usize)?,
+            info: no_info(bit(flags, 0)),
+            doc: no_doc(),
+            detailed: bit(flags, 5).then_some(true),
+            when: when.map(str::to_string),
+            action: bit(flags, 6).then_some(true),
+            no_hooks: bit(flags, 7).then_some(true),
+            no_recurse: bit(flags, 4).then_some(true),
+            emitter: None,
+            parameters,
+            ret: Some(ret),
+        })
+    }
+
+    fn constant(&self, off: usize) -> Result<Constant> {
+        let flags = self.u16(off + 2)? as u32;
+        let typ = self.anytype(off + 8)?;
+        let size = self.u32(off + 12)? as usize;
+        let value = self.bytes(self.u32(off + 16)? as usize, size)?;
+        let mut raw = [0u8; 8];
+        raw[..size.min(8)].copy_from_slice(&value[..size.min(8)]);
+        let name = match typ {
+            AnyType::Type(ref t) => t.name.as_deref(),
+            _ => None,
+        };
+        let value = match name {
+            Some("gboolean") => (i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) != 0).to_string(),
+            Some("gint8") => (raw[0] as i8).to_string(),
+            Some("guint8") => raw[0].to_string(),
+            Some("gint16") => i16::from_le_bytes([raw[0], raw[1]]).to_string(),
+            Some("guint16") => u16::from_le_bytes([raw[0], raw[1]]).to_string(),
+            Some("gint32") => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]).to_string(),
+            Some("guint32") => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]).to_string(),
+            Some("gint64") => i64::from_le_bytes(raw).to_string(),
+            Some("guint64") => u64::from_le_bytes(raw).to_string(),
+            Some("gfloat") => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]).to_string(),
+            Some("gdouble") => f64::from_le_bytes(raw).to_string(),
+            _ => {
+                let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+                String::from_utf8_lossy(&value[..end]).into_owned()
+            }
+        };
+        Ok(Constant {
+            info: no_info(bit(flags, 0)),
+            doc: no_doc(),
+            name: self.string(self.u32(off + 4)? as usize)?,
+            value,
+            c_identifier: self.attribute(off, "c:identifier"),
+            c_type: None,
+            typ: Some(typ),
+        })
+    }
+
+    fn members(&self, off: usize, n: usize) -> Result<Vec<Member>> {
+        (0..n).map(|i| {
+            let off = off + i * self.sizes.value;
+            let flags = self.u32(off)?;
+            let value = self.u32(off + 8)?;
+            let value = if bit(flags, 1) {
+                value.to_string()
+            } else {
+                (value as i32).to_string()
+            };
+            Ok(Member {
+                info: no_info(bit(flags, 0)),
+                doc: no_doc(),
+                name: self.string(self.u32(off + 4)? as usize)?,
+                value,
+                c_identifier: self.attribute(off, "c:identifier"),
+                glib_nick: None,
+            })
+        }).collect()
+    }
+
+    /// The parts enums and flags have in common: name, type name,
+    /// get_type function, members and functions
+    #[allow(clippy::type_complexity)]
+    fn enum_parts(&self, off: usize) -> Result<(String, Option<String>, Option<String>, Vec<Member>, Vec<Function>)> {
+        let flags = self.u16(off + 2)? as u32;
+        let name = self.string(self.u32(off + 4)? as usize)?;
+        let registered = !bit(flags, 1);
+        let n_values = self.u16(off + 16)? as usize;
+        let n_methods = self.u16(off + 18)? as usize;
+        let values = off + self.sizes.enumeration;
+        let members = self.members(values, n_values)?;
+        let functions = self.functions(values + n_values * self.sizes.value, n_methods, &name)?;
+        Ok((
+            name,
+            self.opt_string(off + 8)?.filter(|_| registered),
+            self.opt_string(off + 12)?.filter(|_| registered),
+            members,
+            functions,
+        ))
+    }
+
+    fn enumeration(&self, off: usize) -> Result<Enumeration> {
+        let (name, glib_type_name, glib_get_type, members, functions) = self.enum_parts(off)?;
+        Ok(Enumeration {
+            info: no_info(bit(self.u16(off + 2)? as u32, 0)),
+            doc: no_doc(),
+            c_type: format!("{}{}", self.prefix, name),
+            name,
+            glib_type_name,
+            glib_get_type,
+            glib_error_domain: self.opt_string(off + 20)?,
+            members,
+            functions,
+        })
+    }
+
+    fn bitfield(&self, off: usize) -> Result<Bitfield> {
+        let (name, glib_type_name, glib_get_type, members, functions) = self.enum_parts(off)?;
+        Ok(Bitfield {
+            info: no_info(bit(self.u16(off + 2)? as u32, 0)),
+            doc: no_doc(),
+            c_type: format!("{}{}", self.prefix, name),
+            name,
+            glib_type_name,
+            glib_get_type,
+            members,
+            functions,
+        })
+    }
+
+    /// Split functions into constructors, methods and functions
+    fn split(functions: Vec<Function>) -> (Vec<Function>, Vec<Function>, Vec<Function>) {
+        let mut constructor = vec![];
+        let mut method = vec![];
+        let mut function = vec![];
+        for f in functions {
+            match f.typ {
+                FunctionType::Constructor => constructor.push(f),
+                FunctionType::Method => method.push(f),
+                _ => function.push(f),
+            }
+        }
+        (constructor, method, function)
+    }
+
+    fn record(&self, off: usize) -> Result<Record> {
+        let flags = self.u16(off + 2)? as u32;
+        let name = self.string(self.u32(off + 4)? as usize)?;
+        let registered = !bit(flags, 1);
+        let n_fields = self.u16(off + 20)? as usize;
+        let n_methods = self.u16(off + 22)? as usize;
+        let (fields, end) = self.fields(off + self.sizes.structure, n_fields)?;
+        let (constructor, method, functions) = Self::split(self.functions(end, n_methods, &name)?);
+        Ok(Record {
+            info: no_info(bit(flags, 0)),
+            doc: no_doc(),
+            c_type: Some(format!("{}{}", self.prefix, name)),
+            name,
+            disguised: None,
+            symbol_prefix: None,
+            glib_get_type: self.opt_string(off + 12)?.filter(|_| registered),
+            glib_type_name: self.opt_string(off + 8)?.filter(|_| registered),
+            glib_is_gtype_struct_for: None,
+            foreign: bit(flags, 9).then_some(true),
+            fields,
+            unions: vec![],
+            constructor,
+            functions,
+            method,
+        })
+    }
+
+    fn boxed(&self, off: usize) -> Result<Boxed> {
+        let flags = self.u16(off + 2)? as u32;
+        let name = self.string(self.u32(off + 4)? as usize)?;
+        let n_fields = self.u16(off + 20)? as usize;
+        let n_methods = self.u16(off + 22)? as usize;
+        let (_, end) = self.fields(off + self.sizes.structure, n_fields)?;
+        let functions = self.functions(end, n_methods, &name)?;
+        Ok(Boxed {
+            glib_name: name,
+            info: no_info(bit(flags, 0)),
+            doc: no_doc(),
+            symbol_prefix: None,
+            glib_type_name: self.opt_string(off + 8)?,
+            glib_get_type: self.opt_string(off + 12)?,
+            functions,
+        })
+    }
+
+    fn union(&self, off: usize) -> Result<Union> {
+        let flags = self.u16(off + 2)? as u32;
+        let name = self.string(self.u32(off + 4)? as usize)?;
+        let registered = !bit(flags, 1);
+        let n_fields = self.u16(off + 20)? as usize;
+        let n_functions = self.u16(off + 22)? as usize;
+        let (fields, end) = self.fields(off + self.sizes.union, n_fields)?;
+        let (constructor, method, functions) = Self::split(self.functions(end, n_functions, &name)?);
+        Ok(Union {
+            info: no_info(bit(flags, 0)),
+            doc: no_doc(),
+            c_type: Some(format!("{}{}", self.prefix, name)),
+            name: Some(name),
+            symbol_prefix: None,
+            glib_type_name: self.opt_string(off + 8)?.filter(|_| registered),
+            glib_get_type: self.opt_string(off + 12)?.filter(|_| registered),
+            fields,
+            constructor,
+            method,
+            functions,
+            record: vec![],
+        })
+    }
+
+    /// The type names in the list of directory indexes at `off`, and
+    /// the offset after the list, which is padded to 4 bytes
+    fn entry_names(&self, off: usize, n: usize) -> Result<(Vec<String>, usize)> {
+        let names = (0..n)
+            .map(|i| self.entry_name(self.u16(off + i * 2)? as usize))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((names, off + (n + n % 2) * 2))
+    }
+
+    fn method_names(&self, off: usize, n: usize) -> Result<Vec<String>> {
+        (0..n).map(|i| self.string(self.u32(off + i * self.sizes.function + 4)? as usize)).collect()
+    }
+
+    fn opt_entry(&self, index: u16) -> Result<Option<String>> {
+        match index {
+            0 => Ok(None),
+            i => self.entry_name(i as usize).map(Some),
+        }
+    }
+
+    fn class(&self, off: usize) -> Result<Class> {
+        let flags = self.u16(off + 2)? as u32;
+        let name = self.string(self.u32(off + 4)? as usize)?;
+        let count = |n: usize| self.u16(off + n).map(usize::from);
+        let (n_interfaces, n_fields, n_properties) = (count(20)?, count(22)?, count(24)?);
+        let (n_methods, n_signals, n_vfuncs, n_constants) = (count(26)?, count(28)?, count(30)?, count(32)?);
+
+        let (implements, next) = self.entry_names(off + self.sizes.object, n_interfaces)?;
+        let (fields, next) = self.fields(next, n_fields)?;
+        let properties_off = next;
+        let methods_off = properties_off + n_properties * self.sizes.property;
+        let signals_off = methods_off + n_methods * self.sizes.function;
+        let vfuncs_off = signals_off + n_signals * self.sizes.signal;
+        let constants_off = vfuncs_off + n_vfuncs * self.sizes.vfunc;
+
+        let method_names = self.method_names(methods_off, n_methods)?;
+        let properties = (0..n_properties)
+            .map(|i| self.property(properties_off + i * self.sizes.property, &method_names))
+            .collect::<Result<Vec<_>>>()?;
+        let (constructor, method, functions) = Self::split(self.functions(methods_off, n_methods, &name)?);
+        let signals = (0..n_signals)
+            .map(|i| self.signal(signals_off + i * self.sizes.signal))
+            .collect::<Result<Vec<_>>>()?;
+        let virtual_method = (0..n_vfuncs)
+            .map(|i| self.vfunc(vfuncs_off + i * self.sizes.vfunc, &name, &method_names))
+            .collect::<Result<Vec<_>>>()?;
+        let constant = (0..n_constants)
+            .map(|i| self.constant(constants_off + i * self.sizes.constant))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Class {
+            info: no_info(bit(flags, 0)),
+            doc: no_doc(),
+            glib_type_name: self.opt_string(off + 8)?.unwrap_or_default(),
+            glib_get_type: self.opt_string(off + 12)?.unwrap_or_default(),
+            parent: self.opt_entry(self.u16(off + 16)?)?,
+            glib_type_struct: self.opt_entry(self.u16(off + 18)?)?,
+            ref_func: self.opt_string(off + 36)?,
+            unref_func: self.opt_string(off + 40)?,
+            set_value_func: self.opt_string(off + 44)?,
+            get_value_func: self.opt_string(off + 48)?,
+            ctype: Some(format!("{}{}", self.prefix, name)),
+            symbol_prefix: None,
+            abstracts: bit(flags, 1).then(|| "1".to_string()),
+            glib_fundamental: bit(flags, 2).then(|| "1".to_string()),
+            finals: bit(flags, 3).then(|| "1".to_string()),
+            name,
+            constructor,
+            functions,
+            method,
+            virtual_method,
+            callbacks: vec![],
+            record: vec![],
+            fields,
+            signals,
+            unions: vec![],
+            constant,
+            properties,
+            implements: implements.into_iter().map(|name| Implement { name }).collect(),
+        })
+    }
+
+    fn interface(&self, off: usize) -> Result<Interface> {
+        let flags = self.u16(off + 2)? as u32;
+        let name = self.string(self.u32(off + 4)? as usize)?;
+        let count = |n: usize| self.u16(off + n).map(usize::from);
+        let (n_prerequisites, n_properties, n_methods) = (count(18)?, count(20)?, count(22)?);
+        let (n_signals, n_vfuncs, n_constants) = (count(24)?, count(26)?, count(28)?);
+
+        let (prerequisites, properties_off) = self.entry_names(off + self.sizes.interface, n_prerequisites)?;
+        let methods_off = properties_off + n_properties * self.sizes.property;
+        let signals_off = methods_off + n_methods * self.sizes.function;
+        let vfuncs_off = signals_off + n_signals * self.sizes.signal;
+        let constants_off = vfuncs_off + n_vfuncs * self.sizes.vfunc;
+
+        let method_names = self.method_names(methods_off, n_methods)?;
+        let properties = (0..n_properties)
+            .map(|i| self.property(properties_off + i * self.sizes.property, &method_names))
+            .collect::<Result<Vec<_>>>()?;
+        let (mut constructor, method, functions) = Self::split(self.functions(methods_off, n_methods, &name)?);
+        let signals = (0..n_signals)
+            .map(|i| self.signal(signals_off + i * self.sizes.signal))
+            .collect::<Result<Vec<_>>>()?;
+        let virtual_method = (0..n_vfuncs)
+            .map(|i| self.vfunc(vfuncs_off + i * self.sizes.vfunc, &name, &method_names))
+            .collect::<Result<Vec<_>>>()?;
+        let constant = (0..n_constants)
+            .map(|i| self.constant(constants_off + i * self.sizes.constant))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Interface {
+            info: no_info(bit(flags, 0)),
+            doc: no_doc(),
+            glib_type_name: self.opt_string(off + 8)?.unwrap_or_default(),
+            glib_get_type: self.opt_string(off + 12)?.unwrap_or_default(),
+            symbol_prefix: None,
+            c_type: Some(format!("{}{}", self.prefix, name)),
+            glib_type_struct: self.opt_entry(self.u16(off + 16)?)?,
+            constructor: constructor.pop(),
+            prerequisites,
+            implements: vec![],
+            functions,
+            method,
+            virtual_method,
+            callbacks: vec![],
+            fields: vec![],
+            properties,
+            signals,
+            constant,
+            name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_typelib;
+
+    #[test]
+    fn gmodule_works() {
+        let data = std::fs::read("testdata/typelibs/GModule-2.0.typelib").unwrap();
+        let repo = parse_typelib(&data).unwrap();
+        assert_eq!(repo.include[0].name, "GLib");
+        let ns = &repo.namespace[0];
+        assert_eq!(ns.name.as_deref(), Some("GModule"));
+        assert_eq!(ns.version.as_deref(), Some("2.0"));
+
+        let module = ns.get_record("Module").unwrap();
+        let symbol = module.method.iter().find(|m| m.name == "symbol").unwrap();
+        assert_eq!(symbol.c_identifier.as_deref(), Some("g_module_symbol"));
+        assert!(symbol.parameters[0].instance);
+        assert_eq!(symbol.parameters.len(), 3);
+
+        let flags = &ns.bitfield[0];
+        assert_eq!(flags.name, "ModuleFlags");
+        assert_eq!(flags.members[1].value, "2");
+        assert_eq!(flags.members[1].c_identifier.as_deref(), Some("G_MODULE_BIND_LOCAL"));
+    }
+}
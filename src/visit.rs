@@ -0,0 +1,273 @@
+//! Walking the library model. A `Visitor` gets called for every element of
+//! a namespace, the default methods walk into the children so a backend
+//! only overrides the elements it renders. Overriding a method without
+//! calling the matching `walk_` function skips the children.
+//!
+//! The stubs, the vimdoc output, the html search index, lint and query are
+//! built on it. Output that lists the children of a type in an order of its
+//! own, like fields before methods, writes them to parts it joins after the
+//! walk.
+use anyhow::Result;
+
+use crate::library::*;
+
+/// The type an element is declared in
+#[derive(Clone, Copy)]
+pub enum Parent<'a> {
+    Class(&'a Class),
+    Interface(&'a Interface),
+    Record(&'a Record),
+    Union(&'a Union),
+    Enum(&'a Enumeration),
+    Bitfield(&'a Bitfield),
+    Boxed(&'a Boxed),
+}
+
+impl<'a> Parent<'a> {
+    pub fn name(&self) -> Option<&'a str> {
+        match self {
+            Parent::Class(c) => Some(&c.name),
+            Parent::Interface(i) => Some(&i.name),
+            Parent::Record(r) => Some(&r.name),
+            Parent::Union(u) => u.name.as_deref(),
+            Parent::Enum(e) => Some(&e.name),
+            Parent::Bitfield(b) => Some(&b.name),
+            Parent::Boxed(b) => Some(&b.glib_name),
+        }
+    }
+}
+
+/// Where the visited element is
+#[derive(Clone, Copy)]
+pub struct Context<'a> {
+    /// The name of the namespace
+    pub ns: &'a str,
+    /// None for elements declared directly in the namespace
+    pub parent: Option<Parent<'a>>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(ns: &'a str) -> Context<'a> {
+        Context { ns, parent: None }
+    }
+
+    fn child(&self, parent: Parent<'a>) -> Context<'a> {
+        Context { ns: self.ns, parent: Some(parent) }
+    }
+
+    /// The name of the parent, qualified with the namespace, like `Gtk.Widget`.
+    /// The namespace for elements declared in the namespace.
+    pub fn qualified(&self) -> String {
+        match self.parent.and_then(|p| p.name()) {
+            Some(name) => format!("{}.{}", self.ns, name),
+            None => self.ns.to_string(),
+        }
+    }
+
+    /// The qualified name of `name` declared here, like `Gtk.Widget.show`
+    pub fn path(&self, name: &str) -> String {
+        format!("{}.{}", self.qualified(), name)
+    }
+}
+
+#[allow(unused_variables)]
+pub trait Visitor {
+    fn visit_namespace<'a>(&mut self, ns: &'a Namespace, cx: Context<'a>) -> Result<()> {
+        walk_namespace(self, ns, cx)
+    }
+    fn visit_class<'a>(&mut self, class: &'a Class, cx: Context<'a>) -> Result<()> {
+        walk_class(self, class, cx)
+    }
+    fn visit_interface<'a>(&mut self, interface: &'a Interface, cx: Context<'a>) -> Result<()> {
+        walk_interface(self, interface, cx)
+    }
+    fn visit_record<'a>(&mut self, record: &'a Record, cx: Context<'a>) -> Result<()> {
+        walk_record(self, record, cx)
+    }
+    fn visit_union<'a>(&mut self, union: &'a Union, cx: Context<'a>) -> Result<()> {
+        walk_union(self, union, cx)
+    }
+    fn visit_enum<'a>(&mut self, e: &'a Enumeration, cx: Context<'a>) -> Result<()> {
+        walk_enum(self, e, cx)
+    }
+    fn visit_bitfield<'a>(&mut self, b: &'a Bitfield, cx: Context<'a>) -> Result<()> {
+        walk_bitfield(self, b, cx)
+    }
+    fn visit_boxed<'a>(&mut self, boxed: &'a Boxed, cx: Context<'a>) -> Result<()> {
+        walk_boxed(self, boxed, cx)
+    }
+    /// Functions, constructors, methods, virtual methods and callbacks,
+    /// `fun.typ` tells them apart
+    fn visit_function<'a>(&mut self, fun: &'a Function, cx: Context<'a>) -> Result<()> {
+        walk_function(self, fun, cx)
+    }
+    fn visit_signal<'a>(&mut self, signal: &'a Signal, cx: Context<'a>) -> Result<()> {
+        walk_signal(self, signal, cx)
+    }
+    /// The parameters of functions and signals, not the return value
+    fn visit_parameter<'a>(&mut self, param: &'a Parameter, cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
+    fn visit_property<'a>(&mut self, prop: &'a Property, cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
+    fn visit_field<'a>(&mut self, field: &'a Field, cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
+    fn visit_member<'a>(&mut self, member: &'a Member, cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
+    fn visit_constant<'a>(&mut self, constant: &'a Constant, cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
+    fn visit_alias<'a>(&mut self, alias: &'a Alias, cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
+    fn visit_docsection<'a>(&mut self, section: &'a DocSection, cx: Context<'a>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Visit every namespace of `repo`
+pub fn walk_repository<V: Visitor + ?Sized>(v: &mut V, repo: &Repository) -> Result<()> {
+    for ns in repo.namespace.iter() {
+        if let Some(ref name) = ns.name {
+            v.visit_namespace(ns, Context::new(name))?;
+        }
+    }
+    Ok(())
+}
+
+macro_rules! visit_all {
+    ( $v:expr, $visit:ident, $items:expr, $cx:expr ) => {
+        for item in $items.iter() {
+            $v.$visit(item, $cx)?;
+        }
+    };
+}
+
+pub fn walk_namespace<'a, V: Visitor + ?Sized>(v: &mut V, ns: &'a Namespace, cx: Context<'a>) -> Result<()> {
+    visit_all!(v, visit_class, ns.classes, cx);
+    visit_all!(v, visit_interface, ns.interfaces, cx);
+    visit_all!(v, visit_record, ns.record, cx);
+    visit_all!(v, visit_union, ns.unions, cx);
+    visit_all!(v, visit_boxed, ns.boxed, cx);
+    visit_all!(v, visit_function, ns.callback, cx);
+    visit_all!(v, visit_function, ns.functions, cx);
+    visit_all!(v, visit_enum, ns.enums, cx);
+    visit_all!(v, visit_bitfield, ns.bitfield, cx);
+    visit_all!(v, visit_constant, ns.constant, cx);
+    visit_all!(v, visit_alias, ns.alias, cx);
+    visit_all!(v, visit_docsection, ns.docsections, cx);
+    Ok(())
+}
+
+pub fn walk_class<'a, V: Visitor + ?Sized>(v: &mut V, class: &'a Class, cx: Context<'a>) -> Result<()> {
+    let cx = cx.child(Parent::Class(class));
+    visit_all!(v, visit_function, class.constructor, cx);
+    visit_all!(v, visit_function, class.method, cx);
+    visit_all!(v, visit_function, class.functions, cx);
+    visit_all!(v, visit_function, class.virtual_method, cx);
+    visit_all!(v, visit_function, class.callbacks, cx);
+    visit_all!(v, visit_property, class.properties, cx);
+    visit_all!(v, visit_signal, class.signals, cx);
+    visit_all!(v, visit_field, class.fields, cx);
+    visit_all!(v, visit_constant, class.constant, cx);
+    visit_all!(v, visit_record, class.record, cx);
+    visit_all!(v, visit_union, class.unions, cx);
+    Ok(())
+}
+
+pub fn walk_interface<'a, V: Visitor + ?Sized>(v: &mut V, interface: &'a Interface, cx: Context<'a>) -> Result<()> {
+    let cx = cx.child(Parent::Interface(interface));
+    visit_all!(v, visit_function, interface.constructor, cx);
+    visit_all!(v, visit_function, interface.method, cx);
+    visit_all!(v, visit_function, interface.functions, cx);
+    visit_all!(v, visit_function, interface.virtual_method, cx);
+    visit_all!(v, visit_function, interface.callbacks, cx);
+    visit_all!(v, visit_property, interface.properties, cx);
+    visit_all!(v, visit_signal, interface.signals, cx);
+    visit_all!(v, visit_field, interface.fields, cx);
+    visit_all!(v, visit_constant, interface.constant, cx);
+    Ok(())
+}
+
+pub fn walk_record<'a, V: Visitor + ?Sized>(v: &mut V, record: &'a Record, cx: Context<'a>) -> Result<()> {
+    let cx = cx.child(Parent::Record(record));
+    visit_all!(v, visit_function, record.constructor, cx);
+    visit_all!(v, visit_function, record.method, cx);
+    visit_all!(v, visit_function, record.functions, cx);
+    visit_all!(v, visit_field, record.fields, cx);
+    visit_all!(v, visit_union, record.unions, cx);
+    Ok(())
+}
+
+pub fn walk_union<'a, V: Visitor + ?Sized>(v: &mut V, union: &'a Union, cx: Context<'a>) -> Result<()> {
+    let cx = cx.child(Parent::Union(union));
+    visit_all!(v, visit_function, union.constructor, cx);
+    visit_all!(v, visit_function, union.method, cx);
+    visit_all!(v, visit_function, union.functions, cx);
+    visit_all!(v, visit_field, union.fields, cx);
+    visit_all!(v, visit_record, union.record, cx);
+    Ok(())
+}
+
+pub fn walk_enum<'a, V: Visitor + ?Sized>(v: &mut V, e: &'a Enumeration, cx: Context<'a>) -> Result<()> {
+    let cx = cx.child(Parent::Enum(e));
+    visit_all!(v, visit_member, e.members, cx);
+    visit_all!(v, visit_function, e.functions, cx);
+    Ok(())
+}
+
+pub fn walk_bitfield<'a, V: Visitor + ?Sized>(v: &mut V, b: &'a Bitfield, cx: Context<'a>) -> Result<()> {
+    let cx = cx.child(Parent::Bitfield(b));
+    visit_all!(v, visit_member, b.members, cx);
+    visit_all!(v, visit_function, b.functions, cx);
+    Ok(())
+}
+
+pub fn walk_boxed<'a, V: Visitor + ?Sized>(v: &mut V, boxed: &'a Boxed, cx: Context<'a>) -> Result<()> {
+    let cx = cx.child(Parent::Boxed(boxed));
+    visit_all!(v, visit_function, boxed.functions, cx);
+    Ok(())
+}
+
+pub fn walk_function<'a, V: Visitor + ?Sized>(v: &mut V, fun: &'a Function, cx: Context<'a>) -> Result<()> {
+    visit_all!(v, visit_parameter, fun.parameters, cx);
+    Ok(())
+}
+
+pub fn walk_signal<'a, V: Visitor + ?Sized>(v: &mut V, signal: &'a Signal, cx: Context<'a>) -> Result<()> {
+    visit_all!(v, visit_parameter, signal.parameters, cx);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use super::{Context, Visitor, walk_repository};
+    use crate::library::*;
+    use crate::parse::parse_gir;
+
+    #[derive(Default)]
+    struct Methods(Vec<String>);
+
+    impl Visitor for Methods {
+        fn visit_function<'a>(&mut self, fun: &'a Function, cx: Context<'a>) -> Result<()> {
+            if fun.typ == FunctionType::Method {
+                self.0.push(format!("{}:{}", cx.qualified(), fun.name));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn methods_works() {
+        let f = std::fs::File::open("testdata/girs/GMime-3.0.gir").unwrap();
+        let repo = parse_gir(std::io::BufReader::new(f)).unwrap();
+        let mut methods = Methods::default();
+        walk_repository(&mut methods, &repo).unwrap();
+        assert!(methods.0.contains(&"GMime.Message:get_subject".to_string()));
+        assert!(methods.0.contains(&"GMime.ContentType:get_mime_type".to_string()));
+    }
+}
@@ -0,0 +1,609 @@
+use std::io::Write;
+use anyhow::Result;
+
+use crate::library::*;
+
+const CORE_NS: &str = "http://www.gtk.org/introspection/core/1.0";
+const C_NS: &str = "http://www.gtk.org/introspection/c/1.0";
+const GLIB_NS: &str = "http://www.gtk.org/introspection/glib/1.0";
+
+/// The attributes of an element, in the order they are written
+#[derive(Default)]
+struct Attrs(Vec<(&'static str, String)>);
+
+impl Attrs {
+    fn new() -> Attrs {
+        Attrs::default()
+    }
+
+    fn add(mut self, name: &'static str, value: &str) -> Attrs {
+        self.0.push((name, value.to_string()));
+        self
+    }
+
+    fn opt(self, name: &'static str, value: &Option<String>) -> Attrs {
+        match value {
+            Some(value) => self.add(name, value),
+            None => self,
+        }
+    }
+
+    fn bool(self, name: &'static str, value: Option<bool>) -> Attrs {
+        match value {
+            Some(value) => self.add(name, if value { "1" } else { "0" }),
+            None => self,
+        }
+    }
+
+    /// Only written when it differs from the default of the attribute
+    fn flag(self, name: &'static str, value: bool, default: bool) -> Attrs {
+        if value == default {
+            self
+        } else {
+            self.bool(name, Some(value))
+        }
+    }
+
+    fn info(self, info: &InfoAttrs) -> Attrs {
+        self.bool("introspectable", info.introspectable)
+            .bool("deprecated", info.deprecated)
+            .opt("deprecated-version", &info.deprecated_version)
+            .opt("version", &info.version)
+            .opt("stability", &info.stability)
+    }
+}
+
+fn escape(str: &str) -> String {
+    let mut out = String::with_capacity(str.len());
+    for c in str.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Writes indented xml elements
+struct XmlWriter<'a, W: Write> {
+    w: &'a mut W,
+    depth: usize,
+}
+
+impl<'a, W: Write> XmlWriter<'a, W> {
+    fn start_tag(&mut self, name: &str, attrs: &Attrs) -> Result<()> {
+        write!(self.w, "{}<{}", "  ".repeat(self.depth), name)?;
+        for (key, value) in attrs.0.iter() {
+            // keep newlines in attribute values
+            write!(self.w, " {}=\"{}\"", key, escape(value).replace('\n', "&#10;"))?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, name: &str, attrs: Attrs) -> Result<()> {
+        self.start_tag(name, &attrs)?;
+        writeln!(self.w, ">")?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn close(&mut self, name: &str) -> Result<()> {
+        self.depth -= 1;
+        writeln!(self.w, "{}</{}>", "  ".repeat(self.depth), name)?;
+        Ok(())
+    }
+
+    fn empty(&mut self, name: &str, attrs: Attrs) -> Result<()> {
+        self.start_tag(name, &attrs)?;
+        writeln!(self.w, "/>")?;
+        Ok(())
+    }
+
+    /// An element that only contains text, which is written as is
+    fn text(&mut self, name: &str, attrs: Attrs, text: &str) -> Result<()> {
+        self.start_tag(name, &attrs)?;
+        writeln!(self.w, ">{}</{}>", escape(text), name)?;
+        Ok(())
+    }
+}
+
+/// Write `repo` as a GIR 1.2 xml file
+pub fn write_gir<W: Write>(repo: &Repository, w: &mut W) -> Result<()> {
+    writeln!(w, "<?xml version=\"1.0\"?>")?;
+    let mut x = XmlWriter { w, depth: 0 };
+    let version = match repo.version {
+        Some(Version(major, minor, 0)) => format!("{}.{}", major, minor),
+        Some(Version(major, minor, micro)) => format!("{}.{}.{}", major, minor, micro),
+        None => "1.2".to_string(),
+    };
+    x.open("repository", Attrs::new()
+        .add("version", &version)
+        .add("xmlns", repo.xmlns.as_deref().unwrap_or(CORE_NS))
+        .add("xmlns:c", C_NS)
+        .add("xmlns:glib", GLIB_NS)
+        .opt("c:identifier-prefixes", &repo.identifier_prefixes)
+        .opt("c:symbol-prefixes", &repo.symbol_prefixes))?;
+    for include in repo.include.iter() {
+        x.empty("include", Attrs::new().add("name", &include.name).opt("version", &include.version))?;
+    }
+    for package in repo.package.iter() {
+        x.empty("package", Attrs::new().add("name", &package.name))?;
+    }
+    for cinclude in repo.cinclude.iter() {
+        x.empty("c:include", Attrs::new().add("name", &cinclude.name))?;
+    }
+    for ns in repo.namespace.iter() {
+        write_namespace(ns, &mut x)?;
+    }
+    x.close("repository")
+}
+
+fn write_namespace<W: Write>(ns: &Namespace, x: &mut XmlWriter<W>) -> Result<()> {
+    x.open("namespace", Attrs::new()
+        .opt("name", &ns.name)
+        .opt("version", &ns.version)
+        .opt("shared-library", &ns.shared_library)
+        .opt("c:identifier-prefixes", &ns.identifier_prefixes)
+        .opt("c:symbol-prefixes", &ns.symbol_prefixes)
+        .opt("c:prefix", &ns.prefix))?;
+    for alias in ns.alias.iter() {
+        x.open("alias", Attrs::new()
+            .add("name", &alias.name)
+            .add("c:type", &alias.c_type)
+            .info(&alias.info))?;
+        write_doc(&alias.doc, x)?;
+        write_anytype(&alias.typ, x)?;
+        x.close("alias")?;
+    }
+    for class in ns.classes.iter() {
+        write_class(class, x)?;
+    }
+    for interface in ns.interfaces.iter() {
+        write_interface(interface, x)?;
+    }
+    for record in ns.record.iter() {
+        write_record(record, x)?;
+    }
+    for union in ns.unions.iter() {
+        write_union(union, x)?;
+    }
+    for boxed in ns.boxed.iter() {
+        x.open("glib:boxed", Attrs::new()
+            .add("glib:name", &boxed.glib_name)
+            .opt("c:symbol-prefix", &boxed.symbol_prefix)
+            .opt("glib:type-name", &boxed.glib_type_name)
+            .opt("glib:get-type", &boxed.glib_get_type)
+            .info(&boxed.info))?;
+        write_doc(&boxed.doc, x)?;
+        write_functions(&boxed.functions, x)?;
+        x.close("glib:boxed")?;
+    }
+    for e in ns.enums.iter() {
+        x.open("enumeration", Attrs::new()
+            .add("name", &e.name)
+            .add("c:type", &e.c_type)
+            .opt("glib:type-name", &e.glib_type_name)
+            .opt("glib:get-type", &e.glib_get_type)
+            .opt("glib:error-domain", &e.glib_error_domain)
+            .info(&e.info))?;
+        write_doc(&e.doc, x)?;
+        write_members(&e.members, x)?;
+        write_functions(&e.functions, x)?;
+        x.close("enumeration")?;
+    }
+    for b in ns.bitfield.iter() {
+        x.open("bitfield", Attrs::new()
+            .add("name", &b.name)
+            .add("c:type", &b.c_type)
+            .opt("glib:type-name", &b.glib_type_name)
+            .opt("glib:get-type", &b.glib_get_type)
+            .info(&b.info))?;
+        write_doc(&b.doc, x)?;
+        write_members(&b.members, x)?;
+        write_functions(&b.functions, x)?;
+        x.close("bitfield")?;
+    }
+    write_functions(&ns.callback, x)?;
+    write_functions(&ns.functions, x)?;
+    for m in ns.macros.iter() {
+        x.open("function-macro", Attrs::new()
+            .add("name", &m.name)
+            .opt("c:identifier", &m.c_identifier)
+            .info(&m.info))?;
+        write_doc(&m.doc, x)?;
+        // the parameters are always there, even if empty
+        x.open("parameters", Attrs::new())?;
+        for param in m.parameters.iter() {
+            x.open("parameter", Attrs::new().add("name", &param.name))?;
+            write_doc(&param.doc, x)?;
+            x.close("parameter")?;
+        }
+        x.close("parameters")?;
+        x.close("function-macro")?;
+    }
+    write_constants(&ns.constant, x)?;
+    for section in ns.docsections.iter() {
+        x.open("docsection", Attrs::new().add("name", &section.name))?;
+        write_doc(&section.doc, x)?;
+        x.close("docsection")?;
+    }
+    x.close("namespace")
+}
+
+fn write_doc<W: Write>(doc: &InfoElements, x: &mut XmlWriter<W>) -> Result<()> {
+    let versioned = [
+        ("doc-version", &doc.doc_version),
+        ("doc-stability", &doc.doc_stability),
+    ];
+    for (name, d) in versioned {
+        if let Some(d) = d {
+            x.text(name, Attrs::new()
+                .opt("xml:space", &d.preserve_space)
+                .opt("xml:whitespace", &d.preserve_white), &d.content)?;
+        }
+    }
+    if let Some(ref d) = doc.doc {
+        x.text("doc", Attrs::new()
+            .opt("xml:space", &d.preserve_space)
+            .opt("xml:whitespace", &d.preserve_white)
+            .add("filename", &d.filename)
+            .add("line", &d.line)
+            .opt("column", &d.column), &d.content)?;
+    }
+    if let Some(ref d) = doc.doc_deprecated {
+        x.text("doc-deprecated", Attrs::new()
+            .opt("xml:space", &d.preserve_space)
+            .opt("xml:whitespace", &d.preserve_white), &d.content)?;
+    }
+    if let Some(ref pos) = doc.doc_pos {
+        x.empty("source-position", Attrs::new()
+            .add("filename", &pos.filename)
+            .add("line", &pos.line)
+            .opt("column", &pos.column))?;
+    }
+    Ok(())
+}
+
+fn has_doc(doc: &InfoElements) -> bool {
+    doc.doc.is_some() || doc.doc_version.is_some() || doc.doc_stability.is_some()
+        || doc.doc_deprecated.is_some() || doc.doc_pos.is_some()
+}
+
+fn write_anytype<W: Write>(typ: &AnyType, x: &mut XmlWriter<W>) -> Result<()> {
+    match typ {
+        AnyType::Type(t) => {
+            let attrs = Attrs::new()
+                .opt("name", &t.name)
+                .opt("c:type", &t.ctype)
+                .bool("introspectable", t.introspectable);
+            if t.children.is_empty() && !has_doc(&t.doc) {
+                return x.empty("type", attrs);
+            }
+            x.open("type", attrs)?;
+            write_doc(&t.doc, x)?;
+            for child in t.children.iter() {
+                write_anytype(child, x)?;
+            }
+            x.close("type")
+        }
+        AnyType::Array(a) => {
+            x.open("array", Attrs::new()
+                .opt("name", &a.name)
+                .bool("zero-terminated", a.zero_terminated)
+                .opt("fixed-size", &a.fixed_size.map(|s| s.to_string()))
+                .bool("introspectable", a.introspectable)
+                .opt("length", &a.length.map(|l| l.to_string()))
+                .opt("c:type", &a.ctype))?;
+            write_anytype(&a.typ, x)?;
+            x.close("array")
+        }
+        AnyType::Callback(fun) => write_function(fun, x),
+        AnyType::VarArg => x.empty("varargs", Attrs::new()),
+    }
+}
+
+fn direction(direction: &ParameterDirection) -> Option<&'static str> {
+    match direction {
+        ParameterDirection::In => Some("in"),
+        ParameterDirection::Out => Some("out"),
+        ParameterDirection::InOut => Some("inout"),
+        ParameterDirection::None | ParameterDirection::Return => None,
+    }
+}
+
+fn transfer(transfer: &Transfer) -> &'static str {
+    match transfer {
+        Transfer::None => "none",
+        Transfer::Container => "container",
+        Transfer::Full => "full",
+    }
+}
+
+fn write_param<W: Write>(name: &str, param: &Parameter, x: &mut XmlWriter<W>) -> Result<()> {
+    let mut attrs = Attrs::new();
+    if !param.name.is_empty() {
+        attrs = attrs.add("name", &param.name);
+    }
+    let attrs = attrs
+        .opt("transfer-ownership", &param.transfer.as_ref().map(|t| transfer(t).to_string()))
+        .opt("direction", &param.direction.as_ref().and_then(direction).map(String::from))
+        .flag("caller-allocates", param.caller_allocates, false)
+        .flag("nullable", param.nullable, false)
+        .flag("allow-none", param.allow_none, false)
+        .flag("optional", param.optional, false)
+        .flag("skip", param.skip, false)
+        .bool("introspectable", param.introspectable)
+        .opt("scope", &param.scope)
+        .opt("closure", &param.closure)
+        .opt("destroy", &param.destroy);
+    x.open(name, attrs)?;
+    write_doc(&param.doc, x)?;
+    write_anytype(&param.typ, x)?;
+    x.close(name)
+}
+
+fn write_callable_body<W: Write>(params: &[Parameter], ret: &Option<Parameter>, x: &mut XmlWriter<W>) -> Result<()> {
+    if let Some(ref ret) = ret {
+        write_param("return-value", ret, x)?;
+    }
+    if !params.is_empty() {
+        x.open("parameters", Attrs::new())?;
+        for param in params.iter() {
+            let name = if param.instance { "instance-parameter" } else { "parameter" };
+            write_param(name, param, x)?;
+        }
+        x.close("parameters")?;
+    }
+    Ok(())
+}
+
+fn write_function<W: Write>(fun: &Function, x: &mut XmlWriter<W>) -> Result<()> {
+    let name = match fun.typ {
+        FunctionType::Function | FunctionType::Member => "function",
+        FunctionType::Callback => "callback",
+        FunctionType::Constructor => "constructor",
+        FunctionType::Method => "method",
+        FunctionType::Virtual => "virtual-method",
+    };
+    // introspectable is also part of the info
+    let attrs = Attrs::new()
+        .add("name", &fun.name)
+        .opt("c:identifier", &fun.c_identifier)
+        .opt("c:type", &fun.c_type)
+        .opt("invoker", &fun.invoker)
+        .opt("shadowed-by", &fun.shadowed_by)
+        .opt("shadows", &fun.shadows)
+        .opt("moved-to", &fun.moved_to)
+        .bool("throws", fun.throws)
+        .info(&fun.info);
+    x.open(name, attrs)?;
+    write_doc(&fun.doc, x)?;
+    write_callable_body(&fun.parameters, &fun.ret, x)?;
+    x.close(name)
+}
+
+fn write_functions<W: Write>(funs: &[Function], x: &mut XmlWriter<W>) -> Result<()> {
+    for fun in funs.iter() {
+        write_function(fun, x)?;
+    }
+    Ok(())
+}
+
+fn write_members<W: Write>(members: &[Member], x: &mut XmlWriter<W>) -> Result<()> {
+    for member in members.iter() {
+        let attrs = Attrs::new()
+            .add("name", &member.name)
+            .add("value", &member.value)
+            .opt("c:identifier", &member.c_identifier)
+            .opt("glib:nick", &member.glib_nick)
+            .info(&member.info);
+        if has_doc(&member.doc) {
+            x.open("member", attrs)?;
+            write_doc(&member.doc, x)?;
+            x.close("member")?;
+        } else {
+            x.empty("member", attrs)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_constants<W: Write>(constants: &[Constant], x: &mut XmlWriter<W>) -> Result<()> {
+    for constant in constants.iter() {
+        x.open("constant", Attrs::new()
+            .add("name", &constant.name)
+            .add("value", &constant.value)
+            .opt("c:type", &constant.c_type)
+            .opt("c:identifier", &constant.c_identifier)
+            .info(&constant.info))?;
+        write_doc(&constant.doc, x)?;
+        if let Some(ref typ) = constant.typ {
+            write_anytype(typ, x)?;
+        }
+        x.close("constant")?;
+    }
+    Ok(())
+}
+
+fn write_fields<W: Write>(fields: &[Field], x: &mut XmlWriter<W>) -> Result<()> {
+    for field in fields.iter() {
+        x.open("field", Attrs::new()
+            .add("name", &field.name)
+            .flag("writable", field.writeable, false)
+            .flag("readable", field.readable, true)
+            .flag("private", field.private, false)
+            .opt("bits", &field.bits.map(|b| b.to_string()))
+            .info(&field.info))?;
+        write_doc(&field.doc, x)?;
+        write_anytype(&field.typ, x)?;
+        x.close("field")?;
+    }
+    Ok(())
+}
+
+fn write_properties<W: Write>(props: &[Property], x: &mut XmlWriter<W>) -> Result<()> {
+    for prop in props.iter() {
+        x.open("property", Attrs::new()
+            .add("name", &prop.name)
+            .flag("writable", prop.writable, false)
+            .flag("readable", prop.readable, true)
+            .flag("construct", prop.construct, false)
+            .flag("construct-only", prop.construct_only, false)
+            .opt("setter", &prop.setter)
+            .opt("getter", &prop.getter)
+            .opt("transfer-ownership", &prop.transfer.as_ref().map(|t| transfer(t).to_string()))
+            .info(&prop.info))?;
+        write_doc(&prop.doc, x)?;
+        write_anytype(&prop.typ, x)?;
+        x.close("property")?;
+    }
+    Ok(())
+}
+
+fn write_signals<W: Write>(signals: &[Signal], x: &mut XmlWriter<W>) -> Result<()> {
+    for signal in signals.iter() {
+        x.open("glib:signal", Attrs::new()
+            .add("name", &signal.name)
+            .bool("detailed", signal.detailed)
+            .opt("when", &signal.when)
+            .bool("action", signal.action)
+            .bool("no-hooks", signal.no_hooks)
+            .bool("no-recurse", signal.no_recurse)
+            .opt("emitter", &signal.emitter)
+            .info(&signal.info))?;
+        write_doc(&signal.doc, x)?;
+        write_callable_body(&signal.parameters, &signal.ret, x)?;
+        x.close("glib:signal")?;
+    }
+    Ok(())
+}
+
+fn write_class<W: Write>(class: &Class, x: &mut XmlWriter<W>) -> Result<()> {
+    x.open("class", Attrs::new()
+        .add("name", &class.name)
+        .opt("c:type", &class.ctype)
+        .opt("c:symbol-prefix", &class.symbol_prefix)
+        .opt("parent", &class.parent)
+        .add("glib:type-name", &class.glib_type_name)
+        .add("glib:get-type", &class.glib_get_type)
+        .opt("glib:type-struct", &class.glib_type_struct)
+        .opt("glib:ref-func", &class.ref_func)
+        .opt("glib:unref-func", &class.unref_func)
+        .opt("glib:set-value-func", &class.set_value_func)
+        .opt("glib:get-value-func", &class.get_value_func)
+        .opt("abstract", &class.abstracts)
+        .opt("glib:fundamental", &class.glib_fundamental)
+        .opt("final", &class.finals)
+        .info(&class.info))?;
+    write_doc(&class.doc, x)?;
+    for implement in class.implements.iter() {
+        x.empty("implements", Attrs::new().add("name", &implement.name))?;
+    }
+    write_functions(&class.constructor, x)?;
+    write_functions(&class.functions, x)?;
+    write_functions(&class.method, x)?;
+    write_functions(&class.virtual_method, x)?;
+    write_fields(&class.fields, x)?;
+    write_properties(&class.properties, x)?;
+    write_signals(&class.signals, x)?;
+    for union in class.unions.iter() {
+        write_union(union, x)?;
+    }
+    write_constants(&class.constant, x)?;
+    for record in class.record.iter() {
+        write_record(record, x)?;
+    }
+    write_functions(&class.callbacks, x)?;
+    x.close("class")
+}
+
+fn write_interface<W: Write>(interface: &Interface, x: &mut XmlWriter<W>) -> Result<()> {
+    x.open("interface", Attrs::new()
+        .add("name", &interface.name)
+        .opt("c:type", &interface.c_type)
+        .opt("c:symbol-prefix", &interface.symbol_prefix)
+        .add("glib:type-name", &interface.glib_type_name)
+        .add("glib:get-type", &interface.glib_get_type)
+        .opt("glib:type-struct", &interface.glib_type_struct)
+        .info(&interface.info))?;
+    write_doc(&interface.doc, x)?;
+    for prerequisite in interface.prerequisites.iter() {
+        x.empty("prerequisite", Attrs::new().add("name", prerequisite))?;
+    }
+    for implement in interface.implements.iter() {
+        x.empty("implements", Attrs::new().add("name", implement))?;
+    }
+    if let Some(ref constructor) = interface.constructor {
+        write_function(constructor, x)?;
+    }
+    write_functions(&interface.functions, x)?;
+    write_functions(&interface.method, x)?;
+    write_functions(&interface.virtual_method, x)?;
+    write_fields(&interface.fields, x)?;
+    write_properties(&interface.properties, x)?;
+    write_signals(&interface.signals, x)?;
+    write_constants(&interface.constant, x)?;
+    write_functions(&interface.callbacks, x)?;
+    x.close("interface")
+}
+
+fn write_record<W: Write>(record: &Record, x: &mut XmlWriter<W>) -> Result<()> {
+    x.open("record", Attrs::new()
+        .add("name", &record.name)
+        .opt("c:type", &record.c_type)
+        .bool("disguised", record.disguised)
+        .bool("foreign", record.foreign)
+        .opt("c:symbol-prefix", &record.symbol_prefix)
+        .opt("glib:type-name", &record.glib_type_name)
+        .opt("glib:get-type", &record.glib_get_type)
+        .opt("glib:is-gtype-struct-for", &record.glib_is_gtype_struct_for)
+        .info(&record.info))?;
+    write_doc(&record.doc, x)?;
+    write_fields(&record.fields, x)?;
+    for union in record.unions.iter() {
+        write_union(union, x)?;
+    }
+    write_functions(&record.constructor, x)?;
+    write_functions(&record.functions, x)?;
+    write_functions(&record.method, x)?;
+    x.close("record")
+}
+
+fn write_union<W: Write>(union: &Union, x: &mut XmlWriter<W>) -> Result<()> {
+    x.open("union", Attrs::new()
+        .opt("name", &union.name)
+        .opt("c:type", &union.c_type)
+        .opt("c:symbol-prefix", &union.symbol_prefix)
+        .opt("glib:type-name", &union.glib_type_name)
+        .opt("glib:get-type", &union.glib_get_type)
+        .info(&union.info))?;
+    write_doc(&union.doc, x)?;
+    write_fields(&union.fields, x)?;
+    for record in union.record.iter() {
+        write_record(record, x)?;
+    }
+    write_functions(&union.constructor, x)?;
+    write_functions(&union.functions, x)?;
+    write_functions(&union.method, x)?;
+    x.close("union")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_gir;
+    use crate::parse::parse_gir;
+
+    #[test]
+    fn roundtrip_works() {
+        let f = std::fs::File::open("testdata/girs/GMime-3.0.gir").unwrap();
+        let repo = parse_gir(std::io::BufReader::new(f)).unwrap();
+        let mut gir = vec![];
+        write_gir(&repo, &mut gir).unwrap();
+        let back = parse_gir(gir.as_slice()).unwrap();
+        // the model has no PartialEq, compare the serialized form instead
+        assert_eq!(serde_json::to_value(&repo).unwrap(), serde_json::to_value(&back).unwrap());
+    }
+}